@@ -0,0 +1,576 @@
+//! Derive macros for [`toiletcli`](https://docs.rs/toiletcli) flags.
+//!
+//! Should be used through `toiletcli::flags` with `derive` feature enabled,
+//! see documentation of `Flags` and `Subcommand` traits there.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields,
+          GenericArgument, Ident, LitStr, PathArguments, Token, Type};
+
+/// Implements `toiletcli::flags::Flags` for a struct with named fields.
+///
+/// Every field is a flag, unless it's marked with `#[arguments]` or
+/// `#[subcommand]`. Flags can be described with `#[flag(...)]`:
+/// - String literals are aliases, like `"-v", "--verbose"`. Without them, the
+///   alias is the field name in kebab case, like `--dry-run` for `dry_run`;
+/// - `description = "..."`, `value_name = "..."`, `env = "..."` are the same
+///   as in `FlagSpec`, and are shown in help rendered from
+///   `Flags::with_flag_specs`;
+/// - `default = "..."` is parsed into the field before parsing arguments, and
+///   is shown in help. Defaults of numbers, `bool` and `char` are checked at
+///   compile time, and defaults of other types are checked when parsing;
+/// - `required` makes the flag required;
+/// - `negatable` makes a `bool` field a `NegatableFlag`;
+/// - `count` makes a `usize` field a `RepeatFlag`.
+///
+/// Every key can be used once, and a field can have one `#[flag(...)]`.
+///
+/// Type of the flag depends on the type of the field: `bool` is `BoolFlag`,
+/// `String` is `StringFlag`, `PathBuf` is `PathFlag`, `Vec<String>` is
+/// `ManyFlag`, other `Vec<T>` is `TypedManyFlag`, and everything else is
+/// `TypedFlag`. `Option<T>` is a `TypedFlag` of `T`, which is `None` when the
+/// flag is not present.
+///
+/// `#[arguments]` field collects arguments that are not flags, and can be a
+/// `Vec` of anything that can be made from `String`. Without it, arguments
+/// that are not flags are an error.
+///
+/// `#[subcommand]` field is an `Option` of an enum that derives
+/// `Subcommand`, and is `None` when there are no arguments left. Without
+/// `Option`, the subcommand is required.
+#[proc_macro_derive(Flags, attributes(flag, arguments, subcommand))]
+pub fn derive_flags(input: TokenStream) -> TokenStream
+{
+  let input = parse_macro_input!(input as DeriveInput);
+  flags(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// Implements `toiletcli::flags::Subcommand` for an enum.
+///
+/// Variants are either unit variants, which don't take any flags or
+/// arguments, or tuple variants with a single struct that derives `Flags`.
+/// The name of the subcommand is the name of the variant in kebab case, like
+/// `remote-add` for `RemoteAdd`, and can be changed with
+/// `#[subcommand(name = "...")]`.
+#[proc_macro_derive(Subcommand, attributes(subcommand))]
+pub fn derive_subcommand(input: TokenStream) -> TokenStream
+{
+  let input = parse_macro_input!(input as DeriveInput);
+  subcommand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+// Contents of `#[flag(...)]`.
+#[derive(Default)]
+struct FlagAttr
+{
+  aliases: Vec<LitStr>,
+  description: Option<LitStr>,
+  value_name: Option<LitStr>,
+  env: Option<LitStr>,
+  default: Option<LitStr>,
+  required: Option<Ident>,
+  negatable: Option<Ident>,
+  count: Option<Ident>,
+}
+
+// Value of a flag attribute, either `key = "value"` or just `key`.
+trait AttrValue
+{
+  fn is_set(&self) -> bool;
+
+  fn set(&mut self, key: Ident, input: ParseStream) -> syn::Result<()>;
+}
+
+impl AttrValue for Option<LitStr>
+{
+  fn is_set(&self) -> bool
+  {
+    self.is_some()
+  }
+
+  fn set(&mut self, _key: Ident, input: ParseStream) -> syn::Result<()>
+  {
+    input.parse::<Token![=]>()?;
+    *self = Some(input.parse()?);
+    Ok(())
+  }
+}
+
+// Keys without a value are kept, so errors can point to them.
+impl AttrValue for Option<Ident>
+{
+  fn is_set(&self) -> bool
+  {
+    self.is_some()
+  }
+
+  fn set(&mut self, key: Ident, _input: ParseStream) -> syn::Result<()>
+  {
+    *self = Some(key);
+    Ok(())
+  }
+}
+
+impl Parse for FlagAttr
+{
+  fn parse(input: ParseStream) -> syn::Result<Self>
+  {
+    let mut attr = FlagAttr::default();
+
+    while !input.is_empty() {
+      if input.peek(LitStr) {
+        attr.aliases.push(input.parse()?);
+      } else {
+        let key: Ident = input.parse()?;
+        let value: &mut dyn AttrValue = match key.to_string().as_str() {
+          "description" => &mut attr.description,
+          "value_name" => &mut attr.value_name,
+          "env" => &mut attr.env,
+          "default" => &mut attr.default,
+          "required" => &mut attr.required,
+          "negatable" => &mut attr.negatable,
+          "count" => &mut attr.count,
+          _ => return Err(Error::new(key.span(), "Unknown flag attribute")),
+        };
+        if value.is_set() {
+          return Err(Error::new(key.span(), "Duplicate flag attribute"));
+        }
+        value.set(key, input)?;
+      }
+
+      if !input.is_empty() {
+        input.parse::<Token![,]>()?;
+      }
+    }
+
+    Ok(attr)
+  }
+}
+
+// Contents of `#[subcommand(...)]` on enum variants.
+#[derive(Default)]
+struct SubcommandAttr
+{
+  name: Option<LitStr>,
+}
+
+impl Parse for SubcommandAttr
+{
+  fn parse(input: ParseStream) -> syn::Result<Self>
+  {
+    let key: Ident = input.parse()?;
+    if key != "name" {
+      return Err(Error::new(key.span(), "Unknown subcommand attribute"));
+    }
+    input.parse::<Token![=]>()?;
+    Ok(Self { name: Some(input.parse()?) })
+  }
+}
+
+// Returns the last identifier of type path and its first generic argument,
+// like `Vec` and `String` for `std::vec::Vec<String>`.
+fn type_name(ty: &Type) -> Option<(String, Option<&Type>)>
+{
+  let Type::Path(path) = ty else {
+    return None;
+  };
+  let segment = path.path.segments.last()?;
+  let argument = match &segment.arguments {
+    PathArguments::AngleBracketed(arguments) => {
+      arguments.args.iter().find_map(|argument| match argument {
+                             GenericArgument::Type(ty) => Some(ty),
+                             _ => None,
+                           })
+    }
+    _ => None,
+  };
+  Some((segment.ident.to_string(), argument))
+}
+
+fn is_type(ty: &Type, name: &str) -> bool
+{
+  type_name(ty).is_some_and(|(n, _)| n == name)
+}
+
+// Checks a default value of a type that is known here, so mistakes are found
+// at compile time. Values of other types are checked when parsing.
+fn check_default(name: &str, value: &str) -> Result<(), String>
+{
+  fn parse<T>(value: &str) -> Result<(), String>
+    where T: FromStr,
+          T::Err: Display
+  {
+    value.parse::<T>().map(drop).map_err(|err| err.to_string())
+  }
+
+  match name {
+    "bool" => parse::<bool>(value),
+    "char" => parse::<char>(value),
+    "u8" => parse::<u8>(value),
+    "u16" => parse::<u16>(value),
+    "u32" => parse::<u32>(value),
+    "u64" => parse::<u64>(value),
+    "u128" => parse::<u128>(value),
+    "usize" => parse::<usize>(value),
+    "i8" => parse::<i8>(value),
+    "i16" => parse::<i16>(value),
+    "i32" => parse::<i32>(value),
+    "i64" => parse::<i64>(value),
+    "i128" => parse::<i128>(value),
+    "isize" => parse::<isize>(value),
+    "f32" => parse::<f32>(value),
+    "f64" => parse::<f64>(value),
+    _ => Ok(()),
+  }
+}
+
+// Returns the only attribute called `name`, or an error at the second one.
+fn single_attr<'a>(attrs: &'a [Attribute],
+                   name: &str)
+                   -> syn::Result<Option<&'a Attribute>>
+{
+  let mut attrs = attrs.iter().filter(|attr| attr.path().is_ident(name));
+  let first = attrs.next();
+  if let Some(second) = attrs.next() {
+    let message = format!("Only one #[{}(...)] can be used", name);
+    return Err(Error::new_spanned(second, message));
+  }
+  Ok(first)
+}
+
+// `dry_run` to `dry-run`.
+fn field_kebab(ident: &Ident) -> String
+{
+  ident.to_string().trim_start_matches("r#").replace('_', "-")
+}
+
+// `RemoteAdd` to `remote-add`.
+fn variant_kebab(ident: &Ident) -> String
+{
+  let mut kebab = String::new();
+  for ch in ident.to_string().chars() {
+    if ch.is_uppercase() && !kebab.is_empty() {
+      kebab.push('-');
+    }
+    kebab.extend(ch.to_lowercase());
+  }
+  kebab
+}
+
+fn flags(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+  let Data::Struct(data) = &input.data else {
+    return Err(Error::new_spanned(input,
+                                  "Flags can only be derived for structs"));
+  };
+  let Fields::Named(fields) = &data.fields else {
+    return Err(Error::new_spanned(input,
+                                  "Flags can only be derived for structs with \
+                                   named fields"));
+  };
+
+  let mut locals = vec![];
+  let mut defaults = vec![];
+  let mut specs = vec![];
+  let mut arguments = None;
+  let mut subcommand = None;
+  let mut assignments = vec![];
+
+  for (index, field) in fields.named.iter().enumerate() {
+    let ident = field.ident.as_ref().expect("unreachable");
+    // Named by index, so fields can't clash with other generated variables.
+    let variable = format_ident!("__toiletcli_field_{}", index);
+    let ty = &field.ty;
+
+    if field.attrs.iter().any(|attr| attr.path().is_ident("arguments")) {
+      if arguments.is_some() {
+        return Err(Error::new_spanned(field,
+                                      "Only one field can be arguments"));
+      }
+      assignments.push(quote! { #ident: #variable });
+      arguments = Some(variable);
+      continue;
+    }
+
+    if field.attrs.iter().any(|attr| attr.path().is_ident("subcommand")) {
+      if subcommand.is_some() {
+        return Err(Error::new_spanned(field,
+                                      "Only one field can be a subcommand"));
+      }
+      assignments.push(quote! { #ident: #variable });
+      subcommand = Some((variable, ty));
+      continue;
+    }
+
+    let attr = match single_attr(&field.attrs, "flag")? {
+      Some(attr) => attr.parse_args()?,
+      None => FlagAttr::default(),
+    };
+
+    let (name, argument) = type_name(ty).unwrap_or_default();
+    // Options are parsed into a wrapper, which is `None` until it is set.
+    let option = argument.filter(|_| name == "Option");
+    if let Some(count) = attr.count.as_ref().filter(|_| name != "usize") {
+      return Err(Error::new(count.span(),
+                            "`count` can only be used with `usize` fields"));
+    }
+    if let Some(negatable) = attr.negatable.as_ref().filter(|_| name != "bool")
+    {
+      return Err(Error::new(negatable.span(),
+                            "`negatable` can only be used with `bool` fields"));
+    }
+
+    let is_vec = name == "Vec";
+    let flag_type = if option.is_some() {
+      quote! { TypedFlag }
+    } else if attr.count.is_some() {
+      quote! { RepeatFlag }
+    } else if name == "bool" && attr.negatable.is_some() {
+      quote! { NegatableFlag }
+    } else if name == "bool" {
+      quote! { BoolFlag }
+    } else if name == "String" {
+      quote! { StringFlag }
+    } else if name == "PathBuf" {
+      quote! { PathFlag }
+    } else if is_vec && argument.is_some_and(|a| is_type(a, "String")) {
+      quote! { ManyFlag }
+    } else if is_vec {
+      quote! { TypedManyFlag }
+    } else {
+      quote! { TypedFlag }
+    };
+
+    let local_ty = match option {
+      Some(inner) => quote! { ::toiletcli::flags::OptionValue<#inner> },
+      None => quote! { #ty },
+    };
+    locals.push(quote! {
+      let mut #variable: #local_ty = ::core::default::Default::default();
+    });
+    assignments.push(match option {
+                       Some(_) => quote! { #ident: #variable.0 },
+                       None => quote! { #ident: #variable },
+                     });
+
+    let aliases = if attr.aliases.is_empty() {
+      vec![LitStr::new(&format!("--{}", field_kebab(ident)), ident.span())]
+    } else {
+      attr.aliases.clone()
+    };
+
+    let mut spec = quote! {};
+    if let Some(default) = &attr.default {
+      if is_vec {
+        return Err(Error::new_spanned(default,
+                                      "Lists can't have a default value"));
+      }
+      if let Err(reason) = check_default(&name, &default.value()) {
+        let message = format!("Invalid default value: {}", reason);
+        return Err(Error::new_spanned(default, message));
+      }
+      let flag = aliases.iter()
+                        .find(|alias| alias.value().len() > 2)
+                        .unwrap_or(&aliases[0]);
+      defaults.push(quote! {
+        if let ::core::result::Result::Err(reason) =
+          ::toiletcli::flags::FlagValue::set_from_str(&mut #variable, #default)
+        {
+          return ::core::result::Result::Err(
+            ::toiletcli::flags::invalid_default(#flag, #default, reason));
+        }
+      });
+      spec.extend(quote! { .default_value(#default) });
+    }
+    if let Some(description) = &attr.description {
+      spec.extend(quote! { .description(#description) });
+    }
+    if let Some(value_name) = &attr.value_name {
+      spec.extend(quote! { .value_name(#value_name) });
+    }
+    if let Some(env) = &attr.env {
+      spec.extend(quote! { .env(#env) });
+    }
+    if attr.required.is_some() {
+      spec.extend(quote! { .required(true) });
+    }
+
+    specs.push(quote! {
+      ::toiletcli::flags::FlagSpec::new(
+        ::toiletcli::flags::FlagType::#flag_type(&mut #variable),
+        ::std::vec![#(#aliases),*],
+      )#spec
+    });
+  }
+
+  let parse = match (&subcommand, &arguments) {
+    (Some(..), Some(..)) => {
+      return Err(Error::new_spanned(input,
+                                    "Arguments and subcommand can't be used \
+                                     together"));
+    }
+    (Some((variable, ty)), None) => {
+      let (is_optional, inner) = match type_name(ty) {
+        Some((name, Some(inner))) if name == "Option" => (true, inner),
+        _ => (false, *ty),
+      };
+      let value = if is_optional {
+        quote! {
+          if __subcommand.is_empty() {
+            ::core::option::Option::None
+          } else {
+            ::core::option::Option::Some(
+              <#inner as ::toiletcli::flags::Subcommand>::from_subcommand(
+                &__subcommand, __args)?)
+          }
+        }
+      } else {
+        quote! {
+          <#inner as ::toiletcli::flags::Subcommand>::from_subcommand(
+            &__subcommand, __args)?
+        }
+      };
+      quote! {
+        let __subcommand = {
+          let mut __flags = ::std::vec![#(#specs),*];
+          ::toiletcli::flags::parse_flags_until_subcommand_with(__args,
+                                                                &mut __flags,
+                                                                __options)?
+        };
+        let #variable = #value;
+      }
+    }
+    (None, arguments) => {
+      let assignment = match arguments {
+        Some(variable) => quote! {
+          let #variable = __arguments
+            .into_iter()
+            .map(::core::convert::Into::into)
+            .collect();
+        },
+        None => quote! {
+          ::toiletcli::flags::check_no_arguments(&__arguments)?;
+        },
+      };
+      quote! {
+        let __arguments = {
+          let mut __flags: ::std::vec::Vec<::toiletcli::flags::FlagSpec> =
+            ::std::vec![#(#specs),*];
+          ::toiletcli::flags::parse_flags_with(__args, &mut __flags, __options)?
+        };
+        #assignment
+      }
+    }
+  };
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) =
+    input.generics.split_for_impl();
+
+  Ok(quote! {
+    impl #impl_generics ::toiletcli::flags::Flags for #name #ty_generics
+      #where_clause
+    {
+      fn from_args_with<__Args>(__args: &mut __Args,
+                                __options: &::toiletcli::flags::ParseOptions)
+        -> ::core::result::Result<Self, ::toiletcli::flags::FlagError>
+        where __Args: ::core::iter::Iterator<Item = ::std::string::String>
+      {
+        #(#locals)*
+        #(#defaults)*
+        #parse
+        ::core::result::Result::Ok(Self { #(#assignments),* })
+      }
+
+      fn with_flag_specs<__R, __F>(__f: __F) -> __R
+        where __F: ::core::ops::FnOnce(&[::toiletcli::flags::FlagSpec]) -> __R
+      {
+        #(#locals)*
+        let __flags: ::std::vec::Vec<::toiletcli::flags::FlagSpec> =
+          ::std::vec![#(#specs),*];
+        __f(&__flags)
+      }
+    }
+  })
+}
+
+fn subcommand(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+  let Data::Enum(data) = &input.data else {
+    return Err(Error::new_spanned(input,
+                                  "Subcommand can only be derived for enums"));
+  };
+
+  let mut names = vec![];
+  let mut arms = vec![];
+
+  for variant in &data.variants {
+    let attr = match single_attr(&variant.attrs, "subcommand")? {
+      Some(attr) => attr.parse_args()?,
+      None => SubcommandAttr::default(),
+    };
+    let name = attr.name
+                   .map(|name| name.value())
+                   .unwrap_or_else(|| variant_kebab(&variant.ident));
+
+    let ident = &variant.ident;
+    let value = match &variant.fields {
+      Fields::Unit => quote! {
+        {
+          let mut __flags: ::std::vec::Vec<::toiletcli::flags::Flag> =
+            ::std::vec![];
+          let __arguments =
+            ::toiletcli::flags::parse_flags(__args, &mut __flags)?;
+          ::toiletcli::flags::check_no_arguments(&__arguments)?;
+          Self::#ident
+        }
+      },
+      Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+        let ty = &fields.unnamed[0].ty;
+        quote! {
+          Self::#ident(<#ty as ::toiletcli::flags::Flags>::from_args(__args)?)
+        }
+      }
+      _ => {
+        return Err(Error::new_spanned(variant,
+                                      "Subcommand variants should either be \
+                                       unit variants or have a single field"));
+      }
+    };
+
+    arms.push(quote! { #name => ::core::result::Result::Ok(#value), });
+    names.push(name);
+  }
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) =
+    input.generics.split_for_impl();
+
+  Ok(quote! {
+    impl #impl_generics ::toiletcli::flags::Subcommand for #name #ty_generics
+      #where_clause
+    {
+      const SUBCOMMANDS: &'static [&'static str] = &[#(#names),*];
+
+      fn from_subcommand<__Args>(__name: &str, __args: &mut __Args)
+        -> ::core::result::Result<Self, ::toiletcli::flags::FlagError>
+        where __Args: ::core::iter::Iterator<Item = ::std::string::String>
+      {
+        match __name {
+          #(#arms)*
+          _ => {
+            ::toiletcli::flags::check_subcommand(__name, Self::SUBCOMMANDS)?;
+            ::core::result::Result::Err(
+              ::toiletcli::flags::missing_subcommand(Self::SUBCOMMANDS))
+          }
+        }
+      }
+    }
+  })
+}
@@ -51,13 +51,23 @@ fn main() -> ExitCode
   let program_name =
     name_from_path(&_args.next().expect("Path should be provided"));
 
-  let mut show_help;
-  let mut color;
-
-  let mut flags = toiletcli::flags!(
-      color: StringFlag,   ["-c", "--color"],
-      show_help: BoolFlag, ["--help"]
-  );
+  let mut show_help = false;
+  let mut color = String::new();
+
+  let mut flags =
+    vec![FlagSpec::new(FlagType::StringFlag(&mut color), vec!["-c", "--color"])
+           .description("Color output.")
+           .value_name("COLOR"),
+         FlagSpec::new(FlagType::BoolFlag(&mut show_help), vec!["--help"])
+           .description("Display this message."),];
+
+  // Flags hold references to the variables, so help is rendered before they
+  // are read.
+  let help = Help::new(&program_name).args("<file> [file2, file3, ...]")
+                                     .about("Output a file to standard \
+                                             output. A demo for `toiletcli` \
+                                             crate.")
+                                     .render(&flags);
 
   let args = parse_flags(&mut _args, &mut flags);
 
@@ -69,11 +79,7 @@ fn main() -> ExitCode
   let args = args.unwrap();
 
   if show_help {
-    println!("USAGE: {} [-options] <file> [file2, file3, ...]", program_name);
-    println!("Output a file to standart output. A demo for `toiletcli` crate.");
-    println!("");
-    println!("OPTIONS: -c, --color <color>\tColor output.");
-    println!("             --help         \tDisplay this message.");
+    print!("{}", help);
     return ExitCode::SUCCESS;
   }
 
@@ -6,7 +6,9 @@
 //! Short flags of [`BoolFlag`](type@FlagType::BoolFlag) type can be combined,
 //! eg. `-vAsn` will set `true` to all `-v`, `-A`, `-s`, `-n` flags.
 //!
-//! Flags which take a value can't be combined.
+//! Flags which take a value can't be combined, unless
+//! [`ParseOptions::attached_values`](fn@ParseOptions::attached_values) is
+//! used.
 //!
 //! The intended usage of flags which take a value is `-k <value>`/`-k=<value>`,
 //! with a key `-k` and a value of `<value>`.
@@ -14,9 +16,76 @@
 //! When parsing whole input, a special flag `--` will cause the rest of the
 //! input to be treated as arguments, ignoring the `--` itself. When parsing
 //! only until a subcommand, `--` will be treated as an argument.
+//!
+//! Arguments that are not valid unicode, like from
+//! [`std::env::args_os`](fn@std::env::args_os), can be parsed with
+//! [`parse_flags_os`](fn@parse_flags_os).
+//!
+//! Errors know which part of arguments caused them, and can be rendered with
+//! a caret under it with [`FlagError::render`](fn@FlagError::render).
+//!
+//! Flags can also be set from configuration files with
+//! [`Config`](struct@Config), beneath arguments and environment variables.
+//!
+//! Every error, instead of only the first one, can be found with
+//! [`parse_flags_collect_errors`](fn@parse_flags_collect_errors).
+//!
+//! Flags can be described with [`FlagSpec`](struct@FlagSpec), which allows
+//! rendering help with [`Help`](struct@Help), shell completion scripts with
+//! [`Completion`](struct@Completion), man pages with
+//! [`ManPage`](struct@ManPage) and a JSON description with
+//! [`JsonSpec`](struct@JsonSpec).
+//!
+//! Nested subcommands with their own flags can be parsed with
+//! [`Command`](struct@Command).
+//!
+//! Arguments that are left after flags can be assigned to named
+//! [`Positional`](struct@Positional) arguments.
+//!
+//! Flags that are only known at runtime can be registered in
+//! [`Parser`](struct@Parser), which owns its values.
 
+use std::any::Any;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use parser::ValueStore;
+use response_files::ResponseFiles;
+
+#[cfg(feature = "colors")]
+use crate::colors::{Color, Style};
+
+mod command;
+mod completion;
+mod config;
+#[cfg(feature = "derive")]
+mod derive;
+mod help;
+mod json;
+mod man;
+mod parser;
+mod positional;
+mod provenance;
+mod response_files;
+
+pub use command::*;
+pub use completion::*;
+pub use config::*;
+#[cfg(feature = "derive")]
+pub use derive::*;
+pub use help::*;
+pub use json::*;
+pub use man::*;
+pub use parser::*;
+pub use positional::*;
+pub use provenance::*;
 
 /// Enum that contains a mutable reference to be modified.
 ///
@@ -37,9 +106,20 @@ pub enum FlagType<'a>
 {
   /// Will change reference to `true` if present.
   BoolFlag(&'a mut bool),
+  /// Will change reference to `true` if present, or to `false` if present
+  /// with `--no-` prefix, like `--no-cache` for `--cache`. Last one wins.
+  NegatableFlag(&'a mut bool),
   /// Requires a value.
   /// Will change reference to value passed after that flag if present.
   StringFlag(&'a mut String),
+  /// Requires a value.
+  /// Works the same as [`StringFlag`](type@FlagType::StringFlag), but keeps
+  /// the value as it was passed, even if it is not valid unicode.
+  PathFlag(&'a mut PathBuf),
+  /// Takes a value only when it is attached, like `--color=always` or
+  /// `-calways`. Will change reference to the second value when present
+  /// without a value. Never takes the next argument as its value.
+  OptionalFlag(&'a mut String, &'a str),
   /// Requires at least one value.
   /// Works the same as [`StringFlag`](type@FlagType::StringFlag), but will
   /// include all values if flag was used multiple times.
@@ -47,945 +127,3989 @@ pub enum FlagType<'a>
   /// Will count the number of times a letter is repeated. Long version
   /// increases count by 1.
   RepeatFlag(&'a mut usize),
+  /// Requires a value.
+  /// Works the same as [`StringFlag`](type@FlagType::StringFlag), but parses
+  /// the value with [`FromStr`](trait@FromStr).
+  TypedFlag(&'a mut dyn FlagValue),
+  /// Requires at least one value.
+  /// Works the same as [`ManyFlag`](type@FlagType::ManyFlag), but parses
+  /// each value with [`FromStr`](trait@FromStr).
+  TypedManyFlag(&'a mut dyn FlagValues),
+  /// Requires at least one value.
+  /// Collects values like `-D NAME=value` into a map, split on the first `=`.
+  /// Value without `=`, like `-D NAME`, sets the key to an empty string.
+  /// Keys that are set more than once are handled as the second value says.
+  MapFlag(&'a mut HashMap<String, String>, DuplicateKeys),
+  /// Requires at least one value.
+  /// Works the same as [`TypedManyFlag`](type@FlagType::TypedManyFlag), but
+  /// also splits each value, like `--tags a,b,c`, as the second value says.
+  /// Use [`Vec<String>`](struct@Vec) for values that are not parsed.
+  ListFlag(&'a mut dyn FlagValues, Separator),
 }
 
-#[derive(Debug, PartialEq)]
-pub enum FlagErrorType
+/// How values of [`ListFlag`](type@FlagType::ListFlag) are split. Separator
+/// can be escaped with a backslash, like `a\,b`, and a backslash with
+/// another one. Empty items are skipped.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{FlagType, Separator, parse_flags};
+///
+/// let mut tags: Vec<String> = vec![];
+/// let mut ports: Vec<u16> = vec![];
+///
+/// let mut flags = vec![
+///     (FlagType::ListFlag(&mut tags, Separator::new(',').dedup(true)),
+///      vec!["--tags"]),
+///     (FlagType::ListFlag(&mut ports, Separator::new(':')), vec!["-p"]),
+/// ];
+///
+/// let mut args = ["--tags", "a,b\\,c", "--tags=a", "-p", "80:443"];
+/// parse_flags(&mut args.iter().map(|x| x.to_string()), &mut flags).unwrap();
+///
+/// assert_eq!(tags, vec!["a", "b,c"]);
+/// assert_eq!(ports, vec![80, 443]);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Separator
 {
-  CannotCombine,
-  NoValueProvided,
-  ExtraValueProvided,
-  Unknown,
+  separator: char,
+  dedup: bool,
 }
 
-#[derive(Debug)]
-pub struct FlagError
+impl Separator
 {
-  pub error_type: FlagErrorType,
-  /// Contains the flag that caused this error.
-  pub flag: String,
-}
+  /// Values are split on `separator`.
+  pub fn new(separator: char) -> Self
+  {
+    Self { separator, dedup: false }
+  }
 
-impl Error for FlagError {}
+  /// Whether values that were already present are skipped, so every value is
+  /// kept only once. Values are compared by their
+  /// [`Debug`](trait@fmt::Debug) representation. Values the variable had
+  /// before parsing are kept, and new values that are equal to them are
+  /// skipped.
+  pub fn dedup(mut self, dedup: bool) -> Self
+  {
+    self.dedup = dedup;
+    self
+  }
 
-impl fmt::Display for FlagError
-{
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+  // Splits a value on the separator, unless it's escaped.
+  fn split(&self, value: &str) -> Vec<String>
   {
-    match self.error_type {
-      FlagErrorType::CannotCombine => {
-        write!(f, "Flag {} requires a value and can't be combined", self.flag)
-      }
-      FlagErrorType::NoValueProvided => {
-        write!(f, "No value provided for {}", self.flag)
-      }
-      FlagErrorType::ExtraValueProvided => {
-        write!(f, "Flag {} does not take a value", self.flag)
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut chars = value.chars();
+
+    while let Some(ch) = chars.next() {
+      match ch {
+        '\\' => match chars.next() {
+          Some(next) if next == self.separator || next == '\\' => {
+            item.push(next)
+          }
+          Some(next) => {
+            item.push(ch);
+            item.push(next);
+          }
+          None => item.push(ch),
+        },
+        ch if ch == self.separator => items.push(std::mem::take(&mut item)),
+        ch => item.push(ch),
       }
-      FlagErrorType::Unknown => write!(f, "Unknown flag {}", self.flag),
     }
+    items.push(item);
+
+    items.retain(|item| !item.is_empty());
+    items
   }
 }
 
-/// A pair with a reference to be modified and flag aliases.
+/// What [`MapFlag`](type@FlagType::MapFlag) does when a key is set more than
+/// once.
 ///
 /// # Example
 /// ```rust
-/// use toiletcli::flags::FlagType;
+/// use std::collections::HashMap;
+/// use toiletcli::flags::{DuplicateKeys, FlagType, parse_flags};
 ///
-/// let mut color = String::new();
-/// let mut show_help = false;
+/// let mut defines = HashMap::new();
 ///
 /// let mut flags = vec![
-///     (FlagType::StringFlag(&mut color),   vec!["--color", "-c"]),
-///     (FlagType::BoolFlag(&mut show_help), vec!["--help"]),
+///     (FlagType::MapFlag(&mut defines, DuplicateKeys::Last), vec!["-D"]),
 /// ];
+///
+/// let mut args = ["-D", "MODE=1", "-D", "DEBUG", "-D=MODE=2"];
+/// parse_flags(&mut args.iter().map(|x| x.to_string()), &mut flags).unwrap();
+///
+/// assert_eq!(defines["MODE"], "2");
+/// assert_eq!(defines["DEBUG"], "");
 /// ```
-pub type Flag<'a> = (FlagType<'a>, Vec<&'a str>);
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DuplicateKeys
+{
+  /// Duplicate key is an invalid value.
+  Error,
+  /// The first value is kept.
+  First,
+  /// The last value is kept.
+  Last,
+}
 
-/// Construct [`[Flag]`](type@Flag) variable more quickly.
+/// A value of [`TypedFlag`](type@FlagType::TypedFlag). Implemented for every
+/// type that implements [`FromStr`](trait@FromStr).
 ///
 /// # Example
 /// ```rust
-/// use toiletcli::flags;
-/// use toiletcli::flags::FlagType;
+/// use toiletcli::colors::Color;
+/// use toiletcli::flags::{FlagType, parse_flags};
 ///
-/// let mut color = String::new();
-/// let mut show_help = false;
+/// let mut count: u32 = 0;
+/// let mut color = Color::None;
 ///
 /// let mut flags = vec![
-///     (FlagType::StringFlag(&mut color),   vec!["--color", "-c"]),
-///     (FlagType::BoolFlag(&mut show_help), vec!["--help"], ),
+///     (FlagType::TypedFlag(&mut count), vec!["-n"]),
+///     (FlagType::TypedFlag(&mut color), vec!["--color"]),
 /// ];
 ///
-/// let mut color_macro;
-/// let mut show_help_macro;
-///
-/// let mut flags_macro = flags!(
-///     color_macro: StringFlag,   ["--color", "-c"],
-///     show_help_macro: BoolFlag, ["--help"]
-/// );
+/// let mut args = ["-n", "5", "--color=red"].iter().map(|x| x.to_string());
+/// parse_flags(&mut args, &mut flags).unwrap();
 ///
-/// assert_eq!(flags, flags_macro);
+/// assert_eq!(count, 5);
+/// assert_eq!(color, Color::Red);
 /// ```
-#[macro_export]
-macro_rules! flags {
-  ($($name:ident: $ty:ident, [$($strings:tt)*]),*) => {
-    {
-      $($name = Default::default();)*
-      let mut flags = vec![];
-      $(flags.push((FlagType::$ty(&mut $name), vec![$($strings)*]));)*
-      flags
-    }
-  };
+pub trait FlagValue: fmt::Debug
+{
+  /// Replaces the value with the parsed one. Returns the reason on error.
+  fn set_from_str(&mut self, value: &str) -> Result<(), String>;
+
+  /// The value itself, so it can be read as its type, see
+  /// [`CommandFlags::get`](fn@CommandFlags::get).
+  fn as_any(&self) -> &dyn Any;
 }
 
-// Check flags in flag array for malformed flags in debug builds.
-#[cfg(debug_assertions)]
-fn check_flags(flags: &[Flag])
+impl<T> FlagValue for T
+  where T: FromStr + fmt::Debug + 'static,
+        T::Err: fmt::Display
 {
-  const SPACE_HELP: &str = "Flags should not contain whitespace.";
-  const LEN_HELP: &str =
-    "Flags should be made of either a single dash with one letter, like \
-        '-h', or two dashes with a word, like '--help'.";
-  const LONG_HELP: &str =
-    "Long flags should start with two dashes, like '--help' or '--color'.";
-  const SHORT_HELP: &str =
-    "Flags should start with '-' or '--', like '--help' or '-h'.";
+  fn set_from_str(&mut self, value: &str) -> Result<(), String>
+  {
+    *self = value.parse().map_err(|err: T::Err| err.to_string())?;
+    Ok(())
+  }
 
-  for (_, flag_strings) in flags {
-    for flag in flag_strings {
-      assert!(!flag.contains(char::is_whitespace),
-              "Invalid flag '{}'. {}",
-              flag,
-              SPACE_HELP);
-      assert!(flag.len() >= 2, "Invalid flag '{}'. {}", flag, LEN_HELP);
-      if flag.len() > 2 {
-        assert!(flag.starts_with("--"),
-                "Invalid long flag: '{}'. {}",
-                flag,
-                LONG_HELP);
-      } else {
-        assert!(flag.starts_with('-'),
-                "Invalid flag: '{}'. {}",
-                flag,
-                SHORT_HELP);
+  fn as_any(&self) -> &dyn Any
+  {
+    self
+  }
+}
+
+/// Values of [`TypedManyFlag`](type@FlagType::TypedManyFlag). Implemented for
+/// [`Vec<T>`](struct@Vec) of every type that implements
+/// [`FromStr`](trait@FromStr).
+pub trait FlagValues: fmt::Debug
+{
+  /// Appends the parsed value. Returns the reason on error.
+  fn push_from_str(&mut self, value: &str) -> Result<(), String>;
+
+  /// Appends every parsed value, or none of them when one could not be
+  /// parsed, for [`ListFlag`](type@FlagType::ListFlag). With `dedup`, values
+  /// that are already present, or came earlier in `values`, are skipped, but
+  /// values that were present before are never removed. Returns the value that
+  /// could not be parsed and the reason on error.
+  fn extend_from_strs(&mut self,
+                      values: &[String],
+                      dedup: bool)
+                      -> Result<(), (String, String)>;
+
+  /// Values themselves, so they can be read as their type, see
+  /// [`CommandFlags::get_all`](fn@CommandFlags::get_all).
+  fn as_any(&self) -> &dyn Any;
+}
+
+impl<T> FlagValues for Vec<T>
+  where T: FromStr + fmt::Debug + 'static,
+        T::Err: fmt::Display
+{
+  fn push_from_str(&mut self, value: &str) -> Result<(), String>
+  {
+    self.push(value.parse().map_err(|err: T::Err| err.to_string())?);
+    Ok(())
+  }
+
+  fn extend_from_strs(&mut self,
+                      values: &[String],
+                      dedup: bool)
+                      -> Result<(), (String, String)>
+  {
+    let mut parsed = Vec::with_capacity(values.len());
+    for value in values {
+      let reason = |err: T::Err| (value.clone(), err.to_string());
+      parsed.push(value.parse().map_err(reason)?);
+    }
+
+    if !dedup {
+      self.extend(parsed);
+      return Ok(());
+    }
+
+    // Values don't have to be comparable, so they are compared by their debug
+    // representation.
+    let mut seen =
+      self.iter().map(|value| format!("{:?}", value)).collect::<HashSet<_>>();
+    for value in parsed {
+      if seen.insert(format!("{:?}", value)) {
+        self.push(value);
       }
     }
+    Ok(())
+  }
+
+  fn as_any(&self) -> &dyn Any
+  {
+    self
   }
 }
 
-fn parse_arg<Args>(arg: &String,
-                   args: &mut Args,
-                   flags: &mut [Flag])
-                   -> Result<bool, FlagError>
-  where Args: Iterator<Item = String>
+// Typed values are compared by their debug representation, since there is no
+// way to compare them otherwise.
+impl PartialEq for dyn FlagValue + '_
 {
-  // Split flags that look like `-k=value` to key and value. Otherwise we'll use
-  // the next argument from the iterator as key value.
-  let (arg_flag, arg_val): (&str, Option<&str>) =
-    if let Some(pos) = arg.find('=') {
-      let (f, v) = arg.split_at(pos);
-      (f, Some(v.strip_prefix('=').expect("unreachable")))
-    } else {
-      (arg, None)
-    };
+  fn eq(&self, other: &Self) -> bool
+  {
+    format!("{:?}", self) == format!("{:?}", other)
+  }
+}
 
-  // Decide whether arg is a long flag or short flag. We want chars to start
-  // with letters, with all dashed skipped over.
-  let mut arg_chars = arg_flag.chars().peekable();
-  if arg_chars.next() != Some('-') {
-    return Ok(false);
+impl PartialEq for dyn FlagValues + '_
+{
+  fn eq(&self, other: &Self) -> bool
+  {
+    format!("{:?}", self) == format!("{:?}", other)
   }
-  let is_long = if arg_chars.peek() == Some(&'-') {
-    arg_chars.next();
-    true
-  } else {
-    false
-  };
+}
 
-  let mut found_long = false;
-  let mut last_short_flag_with_value: Option<char> = None;
-  let mut is_first_short_flag = true;
+#[derive(Debug, PartialEq)]
+pub enum FlagErrorType
+{
+  CannotCombine,
+  NoValueProvided,
+  ExtraValueProvided,
+  Unknown,
+  /// Long flag is a prefix of several flags, see
+  /// [`ParseOptions::abbreviations`](fn@ParseOptions::abbreviations).
+  AmbiguousPrefix
+  {
+    /// Flags that start with the prefix.
+    candidates: Vec<String>,
+  },
+  /// Response file could not be read, see
+  /// [`ParseOptions::response_files`](fn@ParseOptions::response_files).
+  ResponseFile
+  {
+    /// Why the file could not be read.
+    reason: String,
+  },
+  /// Subcommand is not one of the known ones, see
+  /// [`check_subcommand`](fn@check_subcommand).
+  UnknownSubcommand
+  {
+    /// Every known subcommand.
+    subcommands: Vec<String>,
+  },
+  /// Subcommand is required, but there was none, see
+  /// [`Command::subcommand_required`](fn@Command::subcommand_required).
+  MissingSubcommand
+  {
+    /// Every known subcommand.
+    subcommands: Vec<String>,
+  },
+  /// Value could not be parsed.
+  InvalidValue
+  {
+    /// Value as it was provided.
+    value: String,
+    /// Why the value could not be parsed.
+    reason: String,
+  },
+  /// Required flags were not present, see
+  /// [`FlagSpec::required`](fn@FlagSpec::required).
+  MissingRequired
+  {
+    /// Every missing flag.
+    flags: Vec<String>,
+  },
+  /// Flags can't be used together, see
+  /// [`ParseOptions::conflicts`](fn@ParseOptions::conflicts).
+  Conflicts
+  {
+    /// Conflicting flags as they were used.
+    flags: Vec<String>,
+  },
+  /// None of the flags were present, see
+  /// [`ParseOptions::exactly_one`](fn@ParseOptions::exactly_one).
+  MissingOneOf
+  {
+    /// Flags of which one is required.
+    flags: Vec<String>,
+  },
+  /// Flag requires other flags that were not present, see
+  /// [`ParseOptions::requires`](fn@ParseOptions::requires).
+  Requires
+  {
+    /// Every missing flag.
+    required: Vec<String>,
+  },
+  /// Configuration file could not be read, or has an error, see
+  /// [`Config`](struct@Config).
+  ConfigFile
+  {
+    /// Path to the file.
+    path: String,
+    /// Line with the error, starting from 1. It's 0 when the file could not
+    /// be read.
+    line: usize,
+    /// What is wrong.
+    reason: String,
+  },
+  /// Value is not valid unicode, but the flag requires it to be, see
+  /// [`parse_flags_os`](fn@parse_flags_os).
+  InvalidUnicode
+  {
+    /// Value as it was provided.
+    value: OsString,
+  },
+  /// Required positional arguments were not present, see
+  /// [`parse_positionals`](fn@parse_positionals).
+  MissingArguments
+  {
+    /// Names of every missing argument.
+    names: Vec<String>,
+  },
+  /// There are more arguments than positionals can take, see
+  /// [`parse_positionals`](fn@parse_positionals).
+  UnexpectedArgument,
+}
 
-  // This iterates the characters of the arg, in case this arg consists of
-  // several short flags. If this is a long flag, we'll just break out after the
-  // first loop.
-  while let Some(ch) = arg_chars.next() {
-    let mut found_short = false;
+/// Part of arguments that caused an error.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ArgPosition
+{
+  /// Index of the argument in the iterator that was parsed. When response
+  /// files are expanded, it's the index among expanded arguments.
+  pub index: usize,
+  /// Byte range in the argument, like the `x` in `-vxq`.
+  pub span: Range<usize>,
+}
 
-    // Linear search over the provided flags vector.
-    for (search_flag_kind, search_flag_names) in &mut *flags {
-      for search_flag_name in search_flag_names {
-        // When searching for a flag, search_flag_name can be either in the long
-        // format (--flag) or short format (-f). If the argument is a long flag,
-        // it should be compared to the entire search flag name string. If it's
-        // a short flag, and if the search_flag_name is also a short flag, check
-        // if it ends with ch.
-        if is_long && arg_flag == *search_flag_name {
-          found_long = true;
-        } else if !is_long &&
-                  search_flag_name.len() == 2 &&
-                  search_flag_name.ends_with(ch)
-        {
-          found_short = true;
-        } else {
-          // We didn't find anything.
-          continue;
-        }
+#[derive(Debug)]
+pub struct FlagError
+{
+  pub error_type: FlagErrorType,
+  /// Contains the flag that caused this error.
+  pub flag: String,
+  /// Known flags or subcommands that are similar to an unknown one, closest
+  /// first.
+  pub suggestions: Vec<String>,
+  /// Where in arguments the error is. Errors that are not caused by a single
+  /// argument, like a missing required flag, don't have a position. Boxed to
+  /// keep errors small.
+  pub position: Option<Box<ArgPosition>>,
+}
 
-        // Flags that take a value cannot be combined.
-        if let Some(first) = last_short_flag_with_value {
-          let error = FlagError { error_type: FlagErrorType::CannotCombine,
-                                  flag: format!("-{}", first) };
-          return Err(error);
-        }
+impl FlagError
+{
+  fn new(error_type: FlagErrorType, flag: String) -> Self
+  {
+    Self { error_type, flag, suggestions: vec![], position: None }
+  }
 
-        match search_flag_kind {
-          FlagType::BoolFlag(value) => {
-            // Prohibit specifying a value with a boolean flag.
-            if arg_val.is_some() {
-              let flag_name = if !is_long {
-                format!("-{}", ch)
-              } else {
-                arg_flag.to_string()
-              };
-              let error = FlagError { error_type:
-                                        FlagErrorType::ExtraValueProvided,
-                                      flag: flag_name };
-              return Err(error);
-            }
-            **value = true;
-          }
+  fn at(mut self, position: ArgPosition) -> Self
+  {
+    self.position = Some(Box::new(position));
+    self
+  }
 
-          FlagType::RepeatFlag(value) => {
-            // Prohibit specifying a value with a repeat flag too.
-            if arg_val.is_some() {
-              let flag_name = if !is_long {
-                format!("-{}", ch)
-              } else {
-                arg_flag.to_string()
-              };
-              let error = FlagError { error_type:
-                                        FlagErrorType::ExtraValueProvided,
-                                      flag: flag_name };
-              return Err(error);
-            }
-            **value += 1;
-          }
+  // Moves the position by `offset` arguments, when arguments were parsed
+  // starting from the middle.
+  fn after(mut self, offset: usize) -> Self
+  {
+    if let Some(position) = &mut self.position {
+      position.index += offset;
+    }
+    self
+  }
 
-          FlagType::StringFlag(value) => {
-            let v = if let Some(v) = arg_val {
-              Some(v.to_string())
-            } else if let Some(next_arg) = args.next() {
-              Some(next_arg.clone())
-            } else {
-              None
-            };
-
-            if v.is_none() || (!is_long && arg_chars.peek().is_some()) {
-              let flag_name = if !is_long {
-                format!("-{}", ch)
-              } else {
-                arg_flag.to_string()
-              };
-              let error_type =
-                if is_first_short_flag && arg_chars.peek().is_none() {
-                  FlagErrorType::NoValueProvided
-                } else {
-                  FlagErrorType::CannotCombine
-                };
-              return Err(FlagError { error_type, flag: flag_name });
-            }
-
-            **value = v.expect("unreachable");
-
-            if !is_long {
-              last_short_flag_with_value = Some(ch);
-            }
-          }
+  /// Returns the error message with arguments below it, and a caret under
+  /// the part that caused the error. `args` should be the arguments that were
+  /// parsed, in the same order. Only the message is returned when the error
+  /// does not have a position.
+  ///
+  /// Caret is colored, unless `colored` is `false`,
+  /// [`should_use_colors`](fn@crate::common::should_use_colors) returns
+  /// `false`, or `colors` feature is disabled.
+  ///
+  /// # Example
+  /// ```rust
+  /// use toiletcli::flags;
+  /// use toiletcli::flags::{FlagType, parse_flags};
+  ///
+  /// let mut verbose;
+  /// let mut quiet;
+  ///
+  /// let mut flags = flags!(
+  ///     verbose: BoolFlag, ["-v"],
+  ///     quiet: BoolFlag,   ["-q"]
+  /// );
+  ///
+  /// let argv = ["file", "-vxq"];
+  /// let mut args = argv.iter().map(|x| x.to_string());
+  ///
+  /// let err = parse_flags(&mut args, &mut flags).unwrap_err();
+  ///
+  /// assert_eq!(err.render(&argv, false),
+  ///            "Unknown flag -x\n\
+  ///             \x20 file -vxq\n\
+  ///             \x20        ^");
+  /// ```
+  pub fn render<S>(&self, args: &[S], colored: bool) -> String
+    where S: AsRef<OsStr>
+  {
+    let message = self.to_string();
+    let Some(position) = self.position.as_deref() else {
+      return message;
+    };
 
-          FlagType::ManyFlag(vec) => {
-            let v = if let Some(v) = arg_val {
-              Some(v.to_string())
-            } else if let Some(next_arg) = args.next() {
-              Some(next_arg.clone())
-            } else {
-              None
-            };
-
-            if v.is_none() || (!is_long && arg_chars.peek().is_some()) {
-              let flag_name = if !is_long {
-                format!("-{}", ch)
-              } else {
-                arg_flag.to_string()
-              };
-              let error_type =
-                if is_first_short_flag && arg_chars.peek().is_none() {
-                  FlagErrorType::NoValueProvided
-                } else {
-                  FlagErrorType::CannotCombine
-                };
-              return Err(FlagError { error_type, flag: flag_name });
-            }
-
-            vec.push(v.expect("unreachable"));
-
-            if !is_long {
-              last_short_flag_with_value = Some(ch);
-            }
-          }
-        }
+    let mut line = String::new();
+    let mut column = 0;
+    let mut width = 1;
 
-        if !is_long {
-          is_first_short_flag = false;
-        };
+    for (i, arg) in args.iter().enumerate() {
+      let arg = arg.as_ref();
+      let (text, is_quoted) = quote_arg(arg);
+      if i > 0 {
+        line.push(' ');
       }
+      if i == position.index {
+        let bytes = arg.as_encoded_bytes();
+        let start = position.span.start.min(bytes.len());
+        let end = position.span.end.clamp(start, bytes.len());
+        let before = String::from_utf8_lossy(&bytes[..start]);
+        let inside = String::from_utf8_lossy(&bytes[start..end]);
+
+        column = line.chars().count() +
+                 before.chars().count() +
+                 is_quoted as usize;
+        width = inside.chars().count().max(1);
+      }
+      line.push_str(&text);
     }
 
-    if found_long {
-      break;
-    } else if is_long {
-      let error = FlagError { error_type: FlagErrorType::Unknown,
-                              flag: arg_flag.to_string() };
-      return Err(error);
+    if position.index >= args.len() {
+      return message;
     }
 
-    // We saw every character and haven't matched anything.
-    if !found_short {
-      let error = FlagError { error_type: FlagErrorType::Unknown,
-                              flag: format!("-{}", ch) };
-      return Err(error);
-    }
+    let caret = "^".repeat(width);
+    format!("{}\n  {}\n  {}{}",
+            message,
+            line,
+            " ".repeat(column),
+            style_caret(&caret, colored))
   }
-
-  Ok(true)
 }
 
-/// Consumes and parses flags and arguments from
-/// [`Iterator<String>`](type@Iterator<String>).
-///
-/// # Returns
-/// ## Ok
-/// All arguments that are not flags. Can be empty.
-/// Changes references passed in the enums according to parsed flags.
-///
-/// ## Err
-/// - Unknown flag;
-/// - No value provided for a flag that requires it;
-/// - Short flag that takes a value was combined with other flag.
-///
-/// # Example
-/// ```rust
-/// use std::env::args;
-/// use toiletcli::flags;
-/// use toiletcli::flags::{FlagType, parse_flags};
-///
-/// let mut color = String::new();
-/// let mut show_help = false;
-///
-/// let mut flags = flags!(
-///     color: StringFlag,   ["--color", "-c"],
-///     show_help: BoolFlag, ["--help"]
-/// );
-///
-/// let args = parse_flags(&mut args(), &mut flags);
-/// ```
-pub fn parse_flags<Args>(args: &mut Args,
-                         flags: &mut [Flag])
-                         -> Result<Vec<String>, FlagError>
-  where Args: Iterator<Item = String>
+// Returns the argument as it would be typed in a shell, and whether it was
+// quoted.
+fn quote_arg(arg: &OsStr) -> (String, bool)
 {
-  #[cfg(debug_assertions)]
-  check_flags(flags);
+  let text = arg.to_string_lossy();
+  if text.is_empty() || text.contains(char::is_whitespace) {
+    (format!("'{}'", text), true)
+  } else {
+    (text.to_string(), false)
+  }
+}
 
-  let mut parsed_arguments: Vec<String> = vec![];
-  let mut ignore_rest = false;
+#[cfg(feature = "colors")]
+fn style_caret(caret: &str, colored: bool) -> String
+{
+  if colored {
+    format!("{}{}{}{}", Style::Bold, Color::Red, caret, Style::Reset)
+  } else {
+    caret.to_string()
+  }
+}
 
-  while let Some(arg) = args.next() {
-    // Treat the rest of the input as arguments after encountering '--'.
-    if arg == "--" {
-      ignore_rest = true;
-      continue;
+#[cfg(not(feature = "colors"))]
+fn style_caret(caret: &str, _colored: bool) -> String
+{
+  caret.to_string()
+}
+
+impl Error for FlagError {}
+
+impl fmt::Display for FlagError
+{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+  {
+    match &self.error_type {
+      FlagErrorType::CannotCombine => {
+        write!(f, "Flag {} requires a value and can't be combined", self.flag)?
+      }
+      FlagErrorType::NoValueProvided => {
+        write!(f, "No value provided for {}", self.flag)?
+      }
+      FlagErrorType::ExtraValueProvided => {
+        write!(f, "Flag {} does not take a value", self.flag)?
+      }
+      FlagErrorType::Unknown => write!(f, "Unknown flag {}", self.flag)?,
+      FlagErrorType::AmbiguousPrefix { candidates } => {
+        write!(f,
+               "Flag {} is ambiguous, could be {}",
+               self.flag,
+               candidates.join(" or "))?
+      }
+      FlagErrorType::ResponseFile { reason } => {
+        write!(f, "Could not read response file {}: {}", self.flag, reason)?
+      }
+      FlagErrorType::UnknownSubcommand { subcommands } => {
+        write!(f, "Unknown subcommand {}", self.flag)?;
+        // Similar ones are more helpful, so list every one only without them.
+        if self.suggestions.is_empty() && !subcommands.is_empty() {
+          write!(f, ", expected one of {}", subcommands.join(", "))?
+        }
+      }
+      FlagErrorType::MissingSubcommand { subcommands } => {
+        write!(f, "Missing subcommand")?;
+        if !subcommands.is_empty() {
+          write!(f, ", expected one of {}", subcommands.join(", "))?
+        }
+      }
+      FlagErrorType::InvalidValue { value, reason } => {
+        write!(f, "Invalid value '{}' for {}: {}", value, self.flag, reason)?
+      }
+      FlagErrorType::MissingRequired { flags } => {
+        if flags.len() == 1 {
+          write!(f, "Missing required flag {}", self.flag)?
+        } else {
+          write!(f, "Missing required flags {}", flags.join(", "))?
+        }
+      }
+      FlagErrorType::Conflicts { flags } => {
+        write!(f, "Flags {} can't be used together", flags.join(" and "))?
+      }
+      FlagErrorType::MissingOneOf { flags } => {
+        write!(f, "One of {} is required", flags.join(" or "))?
+      }
+      FlagErrorType::Requires { required } => {
+        write!(f, "Flag {} requires {}", self.flag, required.join(" and "))?
+      }
+      FlagErrorType::InvalidUnicode { value } => {
+        write!(f,
+               "Invalid value '{}' for {}: not valid unicode",
+               value.to_string_lossy(),
+               self.flag)?
+      }
+      FlagErrorType::ConfigFile { path, line, reason } => {
+        if *line == 0 {
+          write!(f, "{}: {}", path, reason)?
+        } else {
+          write!(f, "{}:{}: {}", path, line, reason)?
+        }
+      }
+      FlagErrorType::MissingArguments { names } => {
+        if names.len() == 1 {
+          write!(f, "Missing argument {}", self.flag)?
+        } else {
+          write!(f, "Missing arguments {}", names.join(", "))?
+        }
+      }
+      FlagErrorType::UnexpectedArgument => {
+        write!(f, "Unexpected argument {}", self.flag)?
+      }
+    }
+    if !self.suggestions.is_empty() {
+      write!(f, ", did you mean {}?", self.suggestions.join(" or "))?;
     }
+    Ok(())
+  }
+}
 
-    // Treat '-' as an argument, otherwise try to parse a flag.
-    if ignore_rest || arg == "-" || !parse_arg(&arg, args, flags)? {
-      parsed_arguments.push(arg);
+/// Every error found by
+/// [`parse_flags_collect_errors`](fn@parse_flags_collect_errors), in the
+/// order they were found. Displayed as one error per line.
+#[derive(Debug, Default)]
+pub struct FlagErrors
+{
+  pub errors: Vec<FlagError>,
+}
+
+impl FlagErrors
+{
+  pub fn is_empty(&self) -> bool
+  {
+    self.errors.is_empty()
+  }
+}
+
+impl Error for FlagErrors {}
+
+impl fmt::Display for FlagErrors
+{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+  {
+    for (i, error) in self.errors.iter().enumerate() {
+      if i > 0 {
+        writeln!(f)?;
+      }
+      write!(f, "{}", error)?;
+    }
+    Ok(())
+  }
+}
+
+// Maximum amount of suggestions for an unknown name.
+const MAX_SUGGESTIONS: usize = 3;
+
+// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize
+{
+  let b = b.chars().collect::<Vec<_>>();
+  let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+  for (i, a_ch) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, b_ch) in b.iter().enumerate() {
+      let substitution = diagonal + if a_ch == *b_ch { 0 } else { 1 };
+      diagonal = row[j + 1];
+      row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
     }
   }
 
-  Ok(parsed_arguments)
+  row[b.len()]
 }
 
-/// Works the same way as [`parse_flags`](fn@parse_flags), but stops when it
-/// encounters the first argument. Consumes all flags before the first argument
-/// from `args` iterator, so `args` can be used again to parse the remaining
-/// contents. Will return `Ok("".to_string())` if no arguments were provided.
+// Returns known names that are similar to the unknown one, closest first. Name
+// is similar when it's a few edits away, or when it starts with the unknown
+// one.
+fn suggestions<'s, Names>(unknown: &str, known: Names) -> Vec<String>
+  where Names: Iterator<Item = &'s str>
+{
+  let unknown_word = unknown.trim_start_matches('-');
+  if unknown_word.is_empty() {
+    return vec![];
+  }
+  let max_distance = (unknown_word.chars().count() / 3).max(1);
+
+  let mut similar = known.filter_map(|name| {
+                           let word = name.trim_start_matches('-');
+                           let distance = edit_distance(unknown_word, word);
+                           if distance <= max_distance ||
+                              word.starts_with(unknown_word)
+                           {
+                             Some((distance, name))
+                           } else {
+                             None
+                           }
+                         })
+                         .collect::<Vec<_>>();
+
+  similar.sort();
+  similar.dedup();
+  similar.into_iter()
+         .take(MAX_SUGGESTIONS)
+         .map(|(_, name)| name.to_string())
+         .collect()
+}
+
+/// Checks whether subcommand returned by
+/// [`parse_flags_until_subcommand`](fn@parse_flags_until_subcommand) is one of
+/// the known ones. Empty subcommand is not checked.
 ///
 /// # Returns
-/// ## Ok
-/// First argument that is not a flag, or empty string when there is no
-/// arguments. Changes references passed in the enums according to parsed flags.
-///
 /// ## Err
-/// - Unknown flag;
-/// - No value provided for a flag that requires it;
-/// - Short flag that takes a value was combined with other flag.
+/// Unknown subcommand, with similar known subcommands as suggestions and all
+/// known subcommands.
 ///
-/// ### Example
-/// ```no_run
-/// use std::env::args;
-/// use toiletcli::flags;
-/// use toiletcli::flags::{FlagType, parse_flags, parse_flags_until_subcommand};
+/// # Example
+/// ```rust
+/// use toiletcli::flags::check_subcommand;
 ///
-/// let mut args = args();
+/// let err = check_subcommand("rmeove", &["add", "remove"]).unwrap_err();
 ///
-/// // Most often, path to the program will be the first argument.
-/// // This will prevent the function from parsing, as path to the program does not start with '-'.
-/// let program_name = args.next().unwrap();
+/// assert_eq!(err.to_string(),
+///            "Unknown subcommand rmeove, did you mean remove?");
+/// ```
+pub fn check_subcommand(subcommand: &str,
+                        subcommands: &[&str])
+                        -> Result<(), FlagError>
+{
+  if subcommand.is_empty() || subcommands.contains(&subcommand) {
+    return Ok(());
+  }
+
+  let error_type =
+    FlagErrorType::UnknownSubcommand { subcommands: to_strings(subcommands) };
+  let mut error = FlagError::new(error_type, subcommand.to_string());
+  error.suggestions = suggestions(subcommand, subcommands.iter().copied());
+
+  Err(error)
+}
+
+/// Options that change how flags are parsed, see
+/// [`parse_flags_with`](fn@parse_flags_with).
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions
+{
+  abbreviations: bool,
+  attached_values: bool,
+  response_files: bool,
+  groups: Vec<FlagGroup>,
+  configs: Vec<Config>,
+}
+
+// Constraint between flags, checked after all flags are parsed. Flags are
+// referred to by any of their aliases.
+#[derive(Debug, Clone)]
+enum FlagGroup
+{
+  Conflicts(Vec<String>),
+  ExactlyOne(Vec<String>),
+  Requires(String, Vec<String>),
+}
+
+impl ParseOptions
+{
+  pub fn new() -> Self
+  {
+    Self::default()
+  }
+
+  /// Allow long flags to be abbreviated to their unique prefix, like
+  /// `--verb` for `--verbose`. Exact matches always win over prefixes.
+  pub fn abbreviations(&mut self, abbreviations: bool) -> &mut Self
+  {
+    self.abbreviations = abbreviations;
+    self
+  }
+
+  /// Allow short flags to have their value attached, like POSIX `getopt`
+  /// does. The rest of short flags after a flag that takes a value becomes its
+  /// value, like `-n5` or `-vofile.txt`.
+  pub fn attached_values(&mut self, attached_values: bool) -> &mut Self
+  {
+    self.attached_values = attached_values;
+    self
+  }
+
+  /// Replace arguments like `@path` with contents of the file at `path`,
+  /// split by whitespace. Quotes can be used to group words, and backslash
+  /// escapes whitespace, quotes and another backslash, so paths like
+  /// `C:\dir\file` are kept as they are. Files can include other files. Use
+  /// `@@` for an argument that starts with a literal `@`. Files are not
+  /// expanded after `--`, and are never expanded when parsing until a
+  /// subcommand.
+  pub fn response_files(&mut self, response_files: bool) -> &mut Self
+  {
+    self.response_files = response_files;
+    self
+  }
+
+  /// Allow at most one of the flags to be present, otherwise parsing fails
+  /// with [`FlagErrorType::Conflicts`](type@FlagErrorType::Conflicts).
+  ///
+  /// When one of the flags is present in arguments, others are not set from
+  /// their environment variables or configuration, so arguments can override
+  /// them.
+  ///
+  /// # Panics
+  /// Parsing panics in debug builds when one of the flags does not exist. In
+  /// release builds, such flags are never present. The same is true for
+  /// [`exactly_one`](fn@ParseOptions::exactly_one) and
+  /// [`requires`](fn@ParseOptions::requires).
+  ///
+  /// # Example
+  /// ```rust
+  /// use toiletcli::flags;
+  /// use toiletcli::flags::{FlagType, ParseOptions, parse_flags_with};
+  ///
+  /// let mut json;
+  /// let mut table;
+  ///
+  /// let mut flags = flags!(
+  ///     json: BoolFlag,  ["-j", "--json"],
+  ///     table: BoolFlag, ["--table"]
+  /// );
+  ///
+  /// let mut options = ParseOptions::new();
+  /// options.conflicts(&["--json", "--table"]);
+  ///
+  /// let mut args = ["-j", "--table"].iter().map(|x| x.to_string());
+  /// let err = parse_flags_with(&mut args, &mut flags, &options).unwrap_err();
+  ///
+  /// assert_eq!(err.to_string(),
+  ///            "Flags -j and --table can't be used together");
+  /// ```
+  pub fn conflicts(&mut self, flags: &[&str]) -> &mut Self
+  {
+    self.groups.push(FlagGroup::Conflicts(to_strings(flags)));
+    self
+  }
+
+  /// Require exactly one of the flags to be present, otherwise parsing fails
+  /// with [`FlagErrorType::Conflicts`](type@FlagErrorType::Conflicts) or
+  /// [`FlagErrorType::MissingOneOf`](type@FlagErrorType::MissingOneOf). Like
+  /// with [`conflicts`](fn@ParseOptions::conflicts), a flag that is present in
+  /// arguments overrides others that are set from environment or
+  /// configuration.
+  pub fn exactly_one(&mut self, flags: &[&str]) -> &mut Self
+  {
+    self.groups.push(FlagGroup::ExactlyOne(to_strings(flags)));
+    self
+  }
+
+  /// Require all of `required` flags to be present when `flag` is present,
+  /// otherwise parsing fails with
+  /// [`FlagErrorType::Requires`](type@FlagErrorType::Requires).
+  pub fn requires(&mut self, flag: &str, required: &[&str]) -> &mut Self
+  {
+    let group = FlagGroup::Requires(flag.to_string(), to_strings(required));
+    self.groups.push(group);
+    self
+  }
+
+  /// Set flags that are not present in arguments, and don't have their
+  /// environment variable set, from `section` of the configuration file. Use
+  /// an empty section for the program itself, and the name of a subcommand
+  /// for its flags.
+  ///
+  /// Can be called many times to layer configuration files, like system,
+  /// user and project ones. A flag set in a later file overrides the same
+  /// flag in earlier files, and flags set only in earlier files are kept.
+  ///
+  /// # Example
+  /// ```rust
+  /// use toiletcli::flags;
+  /// use toiletcli::flags::{Config, FlagType, ParseOptions, parse_flags_with};
+  ///
+  /// let mut color;
+  /// let mut verbose;
+  ///
+  /// let mut flags = flags!(
+  ///     color: StringFlag, ["--color"],
+  ///     verbose: BoolFlag, ["-v", "--verbose"]
+  /// );
+  ///
+  /// let config = Config::parse("tool.conf", "color = never\nverbose\n");
+  ///
+  /// let mut options = ParseOptions::new();
+  /// options.config(&config.unwrap(), "");
+  ///
+  /// let mut args = ["--color=always"].iter().map(|x| x.to_string());
+  /// parse_flags_with(&mut args, &mut flags, &options).unwrap();
+  ///
+  /// assert_eq!(color, "always");
+  /// assert!(verbose);
+  /// ```
+  pub fn config(&mut self, config: &Config, section: &str) -> &mut Self
+  {
+    self.configs.push(config.section(section));
+    self
+  }
+}
+
+fn to_strings(strings: &[&str]) -> Vec<String>
+{
+  strings.iter().map(|string| string.to_string()).collect()
+}
+
+/// A pair with a reference to be modified and flag aliases.
 ///
-/// let mut v_flag;
+/// # Example
+/// ```rust
+/// use toiletcli::flags::FlagType;
 ///
-/// let mut main_flags = flags![
-///     v_flag: BoolFlag, ["-v"]
+/// let mut color = String::new();
+/// let mut show_help = false;
+///
+/// let mut flags = vec![
+///     (FlagType::StringFlag(&mut color),   vec!["--color", "-c"]),
+///     (FlagType::BoolFlag(&mut show_help), vec!["--help"]),
 /// ];
+/// ```
+pub type Flag<'a> = (FlagType<'a>, Vec<&'a str>);
+
+/// A [`Flag`](type@Flag) with additional information about it, which is used
+/// when rendering help. Can be passed to [`parse_flags`](fn@parse_flags)
+/// instead of plain [`Flag`](type@Flag) pairs.
 ///
-/// let subcommand = parse_flags_until_subcommand(&mut args, &mut main_flags).unwrap();
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{FlagSpec, FlagType};
 ///
-/// let mut d_flag;
+/// let mut color = String::new();
+/// let mut show_help = false;
 ///
-/// let mut sub_flags = flags![
-///     d_flag: BoolFlag, ["-d"]
+/// let mut flags = vec![
+///     FlagSpec::new(FlagType::StringFlag(&mut color), vec!["-c", "--color"])
+///       .description("Color output.")
+///       .value_name("COLOR")
+///       .default_value("none"),
+///     FlagSpec::new(FlagType::BoolFlag(&mut show_help), vec!["--help"])
+///       .description("Display this message."),
 /// ];
-///
-/// let subcommand_args = parse_flags(&mut args, &mut sub_flags);
 /// ```
-pub fn parse_flags_until_subcommand<Args>(args: &mut Args,
-                                          flags: &mut [Flag])
-                                          -> Result<String, FlagError>
-  where Args: Iterator<Item = String>
+#[derive(Debug, PartialEq)]
+pub struct FlagSpec<'a>
 {
-  #[cfg(debug_assertions)]
-  check_flags(flags);
+  flag_type: FlagType<'a>,
+  aliases: Vec<&'a str>,
+  description: &'a str,
+  value_name: Option<&'a str>,
+  default_value: Option<&'a str>,
+  env: Option<&'a str>,
+  value_hint: ValueHint<'a>,
+  required: bool,
+}
+
+impl<'a> FlagSpec<'a>
+{
+  pub fn new(flag_type: FlagType<'a>, aliases: Vec<&'a str>) -> Self
+  {
+    Self { flag_type,
+           aliases,
+           description: "",
+           value_name: None,
+           default_value: None,
+           env: None,
+           value_hint: ValueHint::Any,
+           required: false }
+  }
+
+  /// Text that describes this flag in help.
+  pub fn description(mut self, description: &'a str) -> Self
+  {
+    self.description = description;
+    self
+  }
+
+  /// Placeholder for the value in help, like `COLOR` in `--color <COLOR>`.
+  pub fn value_name(mut self, value_name: &'a str) -> Self
+  {
+    self.value_name = Some(value_name);
+    self
+  }
+
+  /// Default value that is shown in help. Only used for display, the referenced
+  /// variable is expected to already hold it.
+  pub fn default_value(mut self, default_value: &'a str) -> Self
+  {
+    self.default_value = Some(default_value);
+    self
+  }
+
+  /// Environment variable to use when flag is not present in arguments.
+  ///
+  /// [`BoolFlag`](type@FlagType::BoolFlag) accepts `1`, `true`, `yes`, `on`
+  /// and `0`, `false`, `no`, `off` or an empty value.
+  /// [`RepeatFlag`](type@FlagType::RepeatFlag) accepts a number.
+  /// [`ManyFlag`](type@FlagType::ManyFlag) and
+  /// [`TypedManyFlag`](type@FlagType::TypedManyFlag) accept values separated
+  /// by `,` or `:`.
+  pub fn env(mut self, env: &'a str) -> Self
+  {
+    self.env = Some(env);
+    self
+  }
+
+  /// Kind of values this flag takes, used by [`Completion`](struct@Completion)
+  /// to complete them.
+  pub fn value_hint(mut self, value_hint: ValueHint<'a>) -> Self
+  {
+    self.value_hint = value_hint;
+    self
+  }
+
+  /// Whether this flag has to be present in arguments, or set by its
+  /// environment variable or a configuration file. Parsing will fail with
+  /// [`FlagErrorType::MissingRequired`](type@FlagErrorType::MissingRequired)
+  /// otherwise.
+  pub fn required(mut self, required: bool) -> Self
+  {
+    self.required = required;
+    self
+  }
+}
+
+impl<'a> From<Flag<'a>> for FlagSpec<'a>
+{
+  fn from((flag_type, aliases): Flag<'a>) -> Self
+  {
+    Self::new(flag_type, aliases)
+  }
+}
+
+/// An entry of the flag table, which is either a plain [`Flag`](type@Flag) or
+/// a [`FlagSpec`](struct@FlagSpec).
+pub trait FlagEntry<'a>
+{
+  fn flag_type(&self) -> &FlagType<'a>;
+
+  fn flag_type_mut(&mut self) -> &mut FlagType<'a>;
+
+  fn aliases(&self) -> &[&'a str];
+
+  fn description(&self) -> &str
+  {
+    ""
+  }
+
+  fn value_name(&self) -> Option<&str>
+  {
+    None
+  }
+
+  fn default_value(&self) -> Option<&str>
+  {
+    None
+  }
+
+  fn env(&self) -> Option<&str>
+  {
+    None
+  }
+
+  fn value_hint(&self) -> ValueHint<'_>
+  {
+    ValueHint::Any
+  }
+
+  fn is_required(&self) -> bool
+  {
+    false
+  }
+}
+
+impl<'a> FlagEntry<'a> for Flag<'a>
+{
+  fn flag_type(&self) -> &FlagType<'a>
+  {
+    &self.0
+  }
+
+  fn flag_type_mut(&mut self) -> &mut FlagType<'a>
+  {
+    &mut self.0
+  }
+
+  fn aliases(&self) -> &[&'a str]
+  {
+    &self.1
+  }
+}
+
+impl<'a> FlagEntry<'a> for FlagSpec<'a>
+{
+  fn flag_type(&self) -> &FlagType<'a>
+  {
+    &self.flag_type
+  }
+
+  fn flag_type_mut(&mut self) -> &mut FlagType<'a>
+  {
+    &mut self.flag_type
+  }
+
+  fn aliases(&self) -> &[&'a str]
+  {
+    &self.aliases
+  }
+
+  fn description(&self) -> &str
+  {
+    self.description
+  }
+
+  fn value_name(&self) -> Option<&str>
+  {
+    self.value_name
+  }
+
+  fn default_value(&self) -> Option<&str>
+  {
+    self.default_value
+  }
+
+  fn env(&self) -> Option<&str>
+  {
+    self.env
+  }
+
+  fn value_hint(&self) -> ValueHint<'_>
+  {
+    self.value_hint
+  }
+
+  fn is_required(&self) -> bool
+  {
+    self.required
+  }
+}
+
+impl FlagType<'_>
+{
+  /// Returns `true` if this flag requires a value.
+  pub fn takes_value(&self) -> bool
+  {
+    match self {
+      FlagType::BoolFlag(_) |
+      FlagType::NegatableFlag(_) |
+      FlagType::OptionalFlag(..) |
+      FlagType::RepeatFlag(_) => false,
+      FlagType::StringFlag(_) |
+      FlagType::PathFlag(_) |
+      FlagType::ManyFlag(_) |
+      FlagType::TypedFlag(_) |
+      FlagType::TypedManyFlag(_) |
+      FlagType::MapFlag(..) |
+      FlagType::ListFlag(..) => true,
+    }
+  }
+
+  /// Returns `true` if this flag collects all of its values.
+  pub fn takes_many(&self) -> bool
+  {
+    matches!(self,
+             FlagType::ManyFlag(_) |
+             FlagType::TypedManyFlag(_) |
+             FlagType::MapFlag(..) |
+             FlagType::ListFlag(..))
+  }
+
+  /// Returns `true` if long aliases of this flag can be prefixed with `--no-`.
+  pub fn is_negatable(&self) -> bool
+  {
+    matches!(self, FlagType::NegatableFlag(_))
+  }
+
+  // Sets a value of a flag that takes a value. Returns the reason when value
+  // could not be parsed.
+  fn set_value(&mut self, value: String) -> Result<(), String>
+  {
+    match self {
+      FlagType::StringFlag(string) | FlagType::OptionalFlag(string, _) => {
+        **string = value
+      }
+      FlagType::PathFlag(path) => **path = PathBuf::from(value),
+      FlagType::ManyFlag(vec) => vec.push(value),
+      FlagType::TypedFlag(typed) => typed.set_from_str(&value)?,
+      FlagType::TypedManyFlag(typed) => typed.push_from_str(&value)?,
+      FlagType::MapFlag(map, duplicates) => {
+        insert_pair(map, *duplicates, value)?
+      }
+      FlagType::ListFlag(typed, separator) => {
+        let items = separator.split(&value);
+        typed.extend_from_strs(&items, separator.dedup)
+             .map_err(|(item, reason)| format!("'{}': {}", item, reason))?;
+      }
+      FlagType::BoolFlag(_) |
+      FlagType::NegatableFlag(_) |
+      FlagType::RepeatFlag(_) => unreachable!("flag does not take a value"),
+    }
+    Ok(())
+  }
+
+  // Sets a value that might not be valid unicode. Only path flags can take
+  // such values.
+  fn set_os_value(&mut self, value: OsString) -> Result<(), FlagErrorType>
+  {
+    if let FlagType::PathFlag(path) = self {
+      **path = PathBuf::from(value);
+      return Ok(());
+    }
+
+    let value = value.into_string()
+                     .map_err(|value| FlagErrorType::InvalidUnicode { value })?;
+    self.set_value(value.clone())
+        .map_err(|reason| FlagErrorType::InvalidValue { value, reason })
+  }
+
+  // Sets a value that did not come from arguments, like from an environment
+  // variable. Unlike arguments, this can set any kind of flag.
+  fn set_from_str(&mut self, value: &str) -> Result<(), String>
+  {
+    match self {
+      FlagType::BoolFlag(boolean) | FlagType::NegatableFlag(boolean) => {
+        **boolean = bool_from_str(value)?;
+      }
+      FlagType::RepeatFlag(count) => {
+        **count = value.parse().map_err(|err| format!("{}", err))?;
+      }
+      FlagType::ManyFlag(_) | FlagType::TypedManyFlag(_) => {
+        for v in split_values(value) {
+          self.set_value(v.to_string())?;
+        }
+      }
+      FlagType::MapFlag(..) => {
+        for pair in split_pairs(value) {
+          self.set_value(pair.to_string())?;
+        }
+      }
+      FlagType::StringFlag(_) |
+      FlagType::PathFlag(_) |
+      FlagType::OptionalFlag(..) |
+      FlagType::TypedFlag(_) |
+      FlagType::ListFlag(..) => {
+        self.set_value(value.to_string())?;
+      }
+    }
+    Ok(())
+  }
+}
+
+// Flag tables set values through their references.
+impl<'a, F> ValueStore for [F]
+  where F: FlagEntry<'a>
+{
+  fn set_bool(&mut self, index: usize, value: bool)
+  {
+    match self[index].flag_type_mut() {
+      FlagType::BoolFlag(boolean) | FlagType::NegatableFlag(boolean) => {
+        **boolean = value
+      }
+      _ => unreachable!("flag is not a boolean"),
+    }
+  }
+
+  fn increment(&mut self, index: usize)
+  {
+    match self[index].flag_type_mut() {
+      FlagType::RepeatFlag(count) => **count += 1,
+      _ => unreachable!("flag is not a repeat flag"),
+    }
+  }
+
+  fn set_os_value(&mut self,
+                  index: usize,
+                  value: OsString)
+                  -> Result<(), FlagErrorType>
+  {
+    self[index].flag_type_mut().set_os_value(value)
+  }
+
+  fn set_value(&mut self, index: usize, value: String) -> Result<(), String>
+  {
+    self[index].flag_type_mut().set_value(value)
+  }
+
+  fn set_from_str(&mut self, index: usize, value: &str) -> Result<(), String>
+  {
+    self[index].flag_type_mut().set_from_str(value)
+  }
+}
+
+// Parses a boolean the way environment variables spell it.
+fn bool_from_str(value: &str) -> Result<bool, String>
+{
+  match value.to_lowercase().as_str() {
+    "1" | "true" | "yes" | "on" => Ok(true),
+    "" | "0" | "false" | "no" | "off" => Ok(false),
+    _ => Err("expected a boolean".to_string()),
+  }
+}
+
+// Splits text with many values, like from an environment variable.
+fn split_values(value: &str) -> impl Iterator<Item = &str>
+{
+  value.split([',', ':']).filter(|v| !v.is_empty())
+}
+
+// Splits text with many `key=value` pairs. Values of maps often have colons in
+// them, like URLs, so only commas separate pairs.
+fn split_pairs(value: &str) -> impl Iterator<Item = &str>
+{
+  value.split(',').filter(|pair| !pair.is_empty())
+}
+
+// Inserts `key=value` into a map. A pair without `=` has an empty value.
+fn insert_pair(map: &mut HashMap<String, String>,
+               duplicates: DuplicateKeys,
+               pair: String)
+               -> Result<(), String>
+{
+  let (key, value) = match pair.split_once('=') {
+    Some((key, value)) => (key.to_string(), value.to_string()),
+    None => (pair, String::new()),
+  };
+  if key.is_empty() {
+    return Err("expected 'key=value'".to_string());
+  }
+
+  match (map.entry(key), duplicates) {
+    (Entry::Vacant(entry), _) => {
+      entry.insert(value);
+    }
+    (Entry::Occupied(mut entry), DuplicateKeys::Last) => {
+      entry.insert(value);
+    }
+    (Entry::Occupied(_), DuplicateKeys::First) => (),
+    (Entry::Occupied(entry), DuplicateKeys::Error) => {
+      return Err(format!("duplicate key {}", entry.key()));
+    }
+  }
+  Ok(())
+}
+
+/// Construct [`[Flag]`](type@Flag) variable more quickly.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags;
+/// use toiletcli::flags::FlagType;
+///
+/// let mut color = String::new();
+/// let mut show_help = false;
+///
+/// let mut flags = vec![
+///     (FlagType::StringFlag(&mut color),   vec!["--color", "-c"]),
+///     (FlagType::BoolFlag(&mut show_help), vec!["--help"], ),
+/// ];
+///
+/// let mut color_macro;
+/// let mut show_help_macro;
+///
+/// let mut flags_macro = flags!(
+///     color_macro: StringFlag,   ["--color", "-c"],
+///     show_help_macro: BoolFlag, ["--help"]
+/// );
+///
+/// assert_eq!(flags, flags_macro);
+/// ```
+///
+/// Variables of [`TypedFlag`](type@FlagType::TypedFlag) and
+/// [`TypedManyFlag`](type@FlagType::TypedManyFlag) need a type annotation,
+/// and their type should implement [`Default`](trait@Default).
+///
+/// ```rust
+/// use toiletcli::flags;
+/// use toiletcli::flags::FlagType;
+///
+/// let mut jobs: usize;
+/// let mut ports: Vec<u16>;
+///
+/// let mut flags = flags!(
+///     jobs: TypedFlag,      ["-j", "--jobs"],
+///     ports: TypedManyFlag, ["-p", "--port"]
+/// );
+/// ```
+#[macro_export]
+macro_rules! flags {
+  ($($name:ident: $ty:ident, [$($strings:tt)*]),*) => {
+    {
+      $($name = Default::default();)*
+      let mut flags = vec![];
+      $(flags.push((FlagType::$ty(&mut $name), vec![$($strings)*]));)*
+      flags
+    }
+  };
+}
+
+// Check flags in flag array for malformed flags, and flag groups for flags
+// that don't exist, in debug builds.
+#[cfg(debug_assertions)]
+fn check_flags(flags: &[ParserFlag], groups: &[FlagGroup])
+{
+  const SPACE_HELP: &str = "Flags should not contain whitespace.";
+  const LEN_HELP: &str =
+    "Flags should be made of either a single dash with one letter, like \
+        '-h', or two dashes with a word, like '--help'.";
+  const LONG_HELP: &str =
+    "Long flags should start with two dashes, like '--help' or '--color'.";
+  const SHORT_HELP: &str =
+    "Flags should start with '-' or '--', like '--help' or '-h'.";
+  const NEGATED_HELP: &str =
+    "Negatable flags already take their '--no-' form, like '--no-cache' for \
+        '--cache'.";
+
+  for entry in flags {
+    for flag in &entry.aliases {
+      assert!(!flag.contains(char::is_whitespace),
+              "Invalid flag '{}'. {}",
+              flag,
+              SPACE_HELP);
+      assert!(flag.len() >= 2, "Invalid flag '{}'. {}", flag, LEN_HELP);
+      if flag.len() > 2 {
+        assert!(flag.starts_with("--"),
+                "Invalid long flag: '{}'. {}",
+                flag,
+                LONG_HELP);
+      } else {
+        assert!(flag.starts_with('-'),
+                "Invalid flag: '{}'. {}",
+                flag,
+                SHORT_HELP);
+      }
+    }
+  }
+
+  // Otherwise, the negated form would set both flags.
+  let negated_names = flags.iter()
+                           .filter(|entry| entry.kind.is_negatable())
+                           .flat_map(|entry| &entry.aliases)
+                           .filter_map(|alias| negated(alias));
+  for name in negated_names {
+    assert!(!flags.iter().any(|entry| entry.aliases.contains(&name)),
+            "Invalid flag '{}'. {}",
+            name,
+            NEGATED_HELP);
+  }
+
+  // A typo would silently disable the constraint otherwise.
+  for group in groups {
+    let names = match group {
+      FlagGroup::Conflicts(names) | FlagGroup::ExactlyOne(names) => {
+        names.iter().collect::<Vec<_>>()
+      }
+      FlagGroup::Requires(name, required) => {
+        std::iter::once(name).chain(required).collect()
+      }
+    };
+    for name in names {
+      assert!(find_flag(flags, name).is_some(),
+              "Unknown flag '{}' in a flag group.",
+              name);
+    }
+  }
+}
+
+// Returns negated form of a long alias, like `--no-cache` for `--cache`.
+fn negated(alias: &str) -> Option<String>
+{
+  if alias.len() > 2 {
+    alias.strip_prefix("--").map(|name| format!("--no-{}", name))
+  } else {
+    None
+  }
+}
+
+// Returns all long names a flag can be used with, including negated ones.
+fn long_names<A>(aliases: &[A], is_negatable: bool) -> Vec<String>
+  where A: AsRef<str>
+{
+  let aliases = aliases.iter()
+                       .map(|alias| alias.as_ref())
+                       .filter(|alias| alias.len() > 2);
+  let mut names: Vec<String> = aliases.clone().map(|a| a.to_string()).collect();
+  if is_negatable {
+    names.extend(aliases.filter_map(negated));
+  }
+  names
+}
+
+// Returns long names of flags that are similar to an unknown one.
+fn long_flag_suggestions(flags: &[ParserFlag], unknown: &str) -> Vec<String>
+{
+  let long_names =
+    flags.iter().flat_map(ParserFlag::long_names).collect::<Vec<_>>();
+  suggestions(unknown, long_names.iter().map(|name| name.as_str()))
+}
+
+// Returns the full long name of the flag that starts with `prefix`. Exact
+// matches are returned as is, as well as prefixes that do not match any flag.
+fn expand_prefix(prefix: &str,
+                 flags: &[ParserFlag])
+                 -> Result<String, FlagError>
+{
+  if prefix.len() <= 2 {
+    return Ok(prefix.to_string());
+  }
+
+  let names = flags.iter().map(ParserFlag::long_names).collect::<Vec<_>>();
+
+  if names.iter().flatten().any(|name| name == prefix) {
+    return Ok(prefix.to_string());
+  }
+
+  // Take only one name from each flag, since several aliases of the same flag
+  // are not ambiguous.
+  let candidates =
+    names.iter()
+         .filter_map(|names| names.iter().find(|name| name.starts_with(prefix)))
+         .cloned()
+         .collect::<Vec<_>>();
+
+  match candidates.len() {
+    0 => Ok(prefix.to_string()),
+    1 => Ok(candidates.into_iter().next().expect("unreachable")),
+    _ => {
+      let error_type = FlagErrorType::AmbiguousPrefix { candidates };
+      Err(FlagError::new(error_type, prefix.to_string()))
+    }
+  }
+}
+
+// Returns the part of `string` that starts at byte `index`.
+fn os_str_from(string: &OsStr, index: usize) -> &OsStr
+{
+  let bytes = &string.as_encoded_bytes()[index..];
+  // SAFETY: `index` is always either right after an ASCII character, or right
+  // before a valid unicode character or the end of a valid unicode part, so
+  // both parts are valid.
+  unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+}
+
+// Consumes the rest of short flags and returns it as a value, like `5` in
+// `-n5`, with its span in the argument.
+fn rest_of_short<Chars>(arg: &OsStr,
+                        arg_chars: &mut Peekable<Chars>)
+                        -> (OsString, Range<usize>)
+  where Chars: Iterator<Item = (usize, char)>
+{
+  let end = arg.len();
+  let rest = match arg_chars.peek() {
+    Some((index, _)) => (os_str_from(arg, *index).to_os_string(), *index..end),
+    None => (OsString::new(), end..end),
+  };
+  arg_chars.for_each(drop);
+  rest
+}
+
+// How a flag was set: the name it was set with, like `-c` or the name of its
+// environment variable, and where its value came from.
+#[derive(Debug, Clone)]
+struct Seen
+{
+  name: String,
+  source: Source,
+}
+
+// Parses `arg`, which is the argument at `index`. Errors point to the part of
+// arguments that caused them.
+fn parse_arg<Args, V>(arg: &OsStr,
+                      index: usize,
+                      args: &mut Args,
+                      flags: &[ParserFlag],
+                      values: &mut V,
+                      seen: &mut [Option<Seen>],
+                      options: &ParseOptions)
+                      -> Result<bool, FlagError>
+  where Args: Iterator<Item = OsString>,
+        V: ValueStore + ?Sized
+{
+  // Split flags that look like `-k=value` to key and value. Otherwise we'll use
+  // the next argument from the iterator as key value.
+  let bytes = arg.as_encoded_bytes();
+  let (flag_bytes, arg_val): (&[u8], Option<&OsStr>) =
+    match bytes.iter().position(|byte| *byte == b'=') {
+      Some(pos) => (&bytes[..pos], Some(os_str_from(arg, pos + 1))),
+      None => (bytes, None),
+    };
+
+  if flag_bytes.first() != Some(&b'-') {
+    return Ok(false);
+  }
+
+  // Flags are matched as unicode. The part that is not valid unicode can only
+  // be a value of a short flag, like in `-n<value>`, and is represented by a
+  // single replacement character.
+  let (arg_flag, is_unicode) = match std::str::from_utf8(flag_bytes) {
+    Ok(arg_flag) => (arg_flag, true),
+    Err(err) => {
+      let valid = &flag_bytes[..err.valid_up_to()];
+      (std::str::from_utf8(valid).expect("unreachable"), false)
+    }
+  };
+  let invalid_rest =
+    (!is_unicode).then_some((arg_flag.len(), char::REPLACEMENT_CHARACTER));
+
+  // Decide whether arg is a long flag or short flag. We want chars to start
+  // with letters, with all dashed skipped over.
+  let mut arg_chars = arg_flag.char_indices().chain(invalid_rest).peekable();
+  arg_chars.next();
+  let is_long = if arg_chars.peek().is_some_and(|(_, ch)| *ch == '-') {
+    arg_chars.next();
+    true
+  } else {
+    false
+  };
+
+  let here = |span: Range<usize>| ArgPosition { index, span };
+  // Everything after `=`, if there is one.
+  let value_span = flag_bytes.len() + 1..bytes.len();
+
+  if is_long && !is_unicode {
+    let flag = String::from_utf8_lossy(flag_bytes).to_string();
+    let error = FlagError::new(FlagErrorType::Unknown, flag);
+    return Err(error.at(here(0..flag_bytes.len())));
+  }
+
+  // Expand unambiguous prefix of a long flag to the full name.
+  let typed_flag = arg_flag;
+  let expanded_flag;
+  let arg_flag = if is_long && options.abbreviations {
+    expanded_flag = expand_prefix(arg_flag, flags)
+      .map_err(|error| error.at(here(0..typed_flag.len())))?;
+    expanded_flag.as_str()
+  } else {
+    arg_flag
+  };
+
+  let mut found_long = false;
+  let mut last_short_flag_with_value: Option<(usize, char)> = None;
+  let mut is_first_short_flag = true;
+
+  // This iterates the characters of the arg, in case this arg consists of
+  // several short flags. If this is a long flag, we'll just break out after the
+  // first loop.
+  while let Some((offset, ch)) = arg_chars.next() {
+    let mut found_short = false;
+
+    // Part of the argument that is the current flag.
+    let flag_span = if is_long {
+      0..typed_flag.len()
+    } else {
+      offset..offset + ch.len_utf8()
+    };
+
+    // With attached values, `=value` belongs only to the last short flag, like
+    // in `-vn=5`. Otherwise it belongs to every flag of the argument.
+    let has_value = arg_val.is_some() &&
+                    (is_long ||
+                     !options.attached_values ||
+                     arg_chars.peek().is_none());
+
+    // Linear search over the provided flags vector.
+    for (flag_index, (search_flag, is_seen)) in
+      flags.iter().zip(seen.iter_mut()).enumerate()
+    {
+      // When searching for a flag, search_flag_name can be either in the long
+      // format (--flag) or short format (-f). If the argument is a long flag,
+      // it should be compared to the entire search flag name string. If it's
+      // a short flag, and if the search_flag_name is also a short flag, check
+      // if it ends with ch.
+      let is_match = search_flag.aliases.iter().any(|search_flag_name| {
+                       if is_long {
+                         arg_flag == *search_flag_name
+                       } else {
+                         search_flag_name.len() == 2 &&
+                         search_flag_name.ends_with(ch)
+                       }
+                     });
+      // Negatable flags also match their long aliases with `--no-` prefix.
+      let is_negated =
+        is_long &&
+        !is_match &&
+        search_flag.kind.is_negatable() &&
+        search_flag.aliases
+                   .iter()
+                   .any(|name| negated(name).is_some_and(|n| n == arg_flag));
+
+      if !is_match && !is_negated {
+        // We didn't find anything.
+        continue;
+      } else if is_long {
+        found_long = true;
+      } else {
+        found_short = true;
+      }
+
+      // Remember the flag as it was typed.
+      let name = if is_long {
+        typed_flag.to_string()
+      } else {
+        format!("-{}", ch)
+      };
+      *is_seen = Some(Seen { name, source: Source::Argument { index } });
+
+      // Flags that take a value cannot be combined.
+      if let Some((first_offset, first)) = last_short_flag_with_value {
+        let error =
+          FlagError::new(FlagErrorType::CannotCombine, format!("-{}", first));
+        let span = first_offset..first_offset + first.len_utf8();
+        return Err(error.at(here(span)));
+      }
+
+      let flag_name = if !is_long {
+        format!("-{}", ch)
+      } else {
+        arg_flag.to_string()
+      };
+
+      match &search_flag.kind {
+        FlagKind::Bool | FlagKind::Negatable => {
+          // Prohibit specifying a value with a boolean flag.
+          if has_value {
+            let error =
+              FlagError::new(FlagErrorType::ExtraValueProvided, flag_name);
+            return Err(error.at(here(value_span.clone())));
+          }
+          values.set_bool(flag_index, !is_negated);
+        }
+
+        FlagKind::Repeat => {
+          // Prohibit specifying a value with a repeat flag too.
+          if has_value {
+            let error =
+              FlagError::new(FlagErrorType::ExtraValueProvided, flag_name);
+            return Err(error.at(here(value_span.clone())));
+          }
+          values.increment(flag_index);
+        }
+
+        FlagKind::Optional(implicit_value) => {
+          // Rest of the short flag is the value, like `-calways`.
+          let (v, span) = if !is_long && arg_chars.peek().is_some() {
+            rest_of_short(arg, &mut arg_chars)
+          } else if let Some(v) = arg_val {
+            (v.to_os_string(), value_span.clone())
+          } else {
+            (OsString::from(implicit_value), flag_span.clone())
+          };
+          if let Err(error_type) = values.set_os_value(flag_index, v) {
+            let error = FlagError::new(error_type, flag_name);
+            return Err(error.at(here(span)));
+          }
+        }
+
+        // Every other flag takes a value.
+        _ => {
+          // Value is either a part of this argument, or the next argument.
+          let v = if options.attached_values &&
+                     !is_long &&
+                     arg_chars.peek().is_some()
+          {
+            let (v, span) = rest_of_short(arg, &mut arg_chars);
+            Some((v, here(span)))
+          } else if let Some(v) = arg_val {
+            Some((v.to_os_string(), here(value_span.clone())))
+          } else {
+            args.next().map(|v| {
+                          let span = 0..v.len();
+                          (v, ArgPosition { index: index + 1, span })
+                        })
+          };
+
+          if v.is_none() || (!is_long && arg_chars.peek().is_some()) {
+            // With attached values, the last flag of `-vo` can take a value,
+            // there just isn't one.
+            let can_take_value = is_first_short_flag || options.attached_values;
+            let error_type =
+              if can_take_value && arg_chars.peek().is_none() {
+                FlagErrorType::NoValueProvided
+              } else {
+                FlagErrorType::CannotCombine
+              };
+            let error = FlagError::new(error_type, flag_name);
+            return Err(error.at(here(flag_span.clone())));
+          }
+
+          let (v, position) = v.expect("unreachable");
+
+          if let Err(error_type) = values.set_os_value(flag_index, v) {
+            let error = FlagError::new(error_type, flag_name);
+            return Err(error.at(position));
+          }
+
+          if !is_long {
+            last_short_flag_with_value = Some((offset, ch));
+          }
+        }
+      }
+
+      if !is_long {
+        is_first_short_flag = false;
+      };
+    }
+
+    if found_long {
+      break;
+    } else if is_long {
+      let mut error =
+        FlagError::new(FlagErrorType::Unknown, arg_flag.to_string());
+      error.suggestions = long_flag_suggestions(flags, arg_flag);
+      return Err(error.at(here(flag_span)));
+    }
+
+    // We saw every character and haven't matched anything.
+    if !found_short {
+      let error = FlagError::new(FlagErrorType::Unknown, format!("-{}", ch));
+      return Err(error.at(here(flag_span)));
+    }
+  }
+
+  Ok(true)
+}
+
+// Sets flags that were not present in arguments from their environment
+// variables. Flags that were set are seen as their variable name.
+fn parse_env<V>(flags: &[ParserFlag],
+                values: &mut V,
+                seen: &mut [Option<Seen>],
+                groups: &[FlagGroup],
+                errors: &mut Vec<FlagError>)
+  where V: ValueStore + ?Sized
+{
+  for (index, flag) in flags.iter().enumerate() {
+    if seen[index].is_some() || overridden(flags, seen, groups, index) {
+      continue;
+    }
+    let Some(env) = flag.env.clone() else {
+      continue;
+    };
+
+    let Some(value) = std::env::var_os(&env) else {
+      continue;
+    };
+    let source = Source::Env { name: env.clone() };
+    seen[index] = Some(Seen { name: env.clone(), source });
+
+    // Paths are taken as they are.
+    if flag.kind == FlagKind::Path {
+      values.set_os_value(index, value).expect("paths take any value");
+      continue;
+    }
+
+    let value = match value.into_string() {
+      Ok(value) => value,
+      Err(value) => {
+        let error_type = FlagErrorType::InvalidUnicode { value };
+        errors.push(FlagError::new(error_type, env));
+        continue;
+      }
+    };
+
+    if let Err(reason) = values.set_from_str(index, &value) {
+      let error_type = FlagErrorType::InvalidValue { value, reason };
+      errors.push(FlagError::new(error_type, env));
+    }
+  }
+}
+
+// Returns the name to refer to a flag by, which is its first long alias.
+fn display_name<A>(aliases: &[A]) -> String
+  where A: AsRef<str>
+{
+  let mut aliases = aliases.iter().map(|alias| alias.as_ref());
+  aliases.clone()
+         .find(|alias| alias.len() > 2)
+         .or(aliases.next())
+         .map(|alias| alias.to_string())
+         .unwrap_or_default()
+}
+
+// Adds an error with all required flags that were not seen.
+fn check_required(flags: &[ParserFlag],
+                  seen: &[Option<Seen>],
+                  errors: &mut Vec<FlagError>)
+{
+  let missing = flags.iter()
+                     .zip(seen)
+                     .filter(|(flag, seen)| flag.required && seen.is_none())
+                     .map(|(flag, _)| display_name(&flag.aliases))
+                     .collect::<Vec<_>>();
+
+  if let Some(first) = missing.first() {
+    let flag = first.clone();
+    let error_type = FlagErrorType::MissingRequired { flags: missing };
+    errors.push(FlagError::new(error_type, flag));
+  }
+}
+
+// Returns the index of a flag that has `name` as one of its aliases. Flags
+// in groups are checked by `check_flags`, so a flag that does not exist is
+// only possible in release builds, where it's never present.
+fn find_flag(flags: &[ParserFlag], name: &str) -> Option<usize>
+{
+  flags.iter()
+       .position(|flag| flag.aliases.iter().any(|alias| alias == name))
+}
+
+// Whether a flag is in a conflicting group with another flag that was present
+// in arguments. Arguments take priority over the environment and
+// configuration, so such flags are not set from them.
+fn overridden(flags: &[ParserFlag],
+              seen: &[Option<Seen>],
+              groups: &[FlagGroup],
+              index: usize)
+              -> bool
+{
+  let from_arguments = |name: &String| {
+    let Some(other) = find_flag(flags, name) else {
+      return false;
+    };
+    other != index &&
+    matches!(seen[other],
+             Some(Seen { source: Source::Argument { .. }, .. }))
+  };
+
+  groups.iter().any(|group| match group {
+                 FlagGroup::Conflicts(names) | FlagGroup::ExactlyOne(names) => {
+                   names.iter()
+                        .any(|name| find_flag(flags, name) == Some(index)) &&
+                   names.iter().any(from_arguments)
+                 }
+                 FlagGroup::Requires(..) => false,
+               })
+}
+
+// Adds an error for every group that is not satisfied.
+fn check_groups(flags: &[ParserFlag],
+                seen: &[Option<Seen>],
+                groups: &[FlagGroup],
+                errors: &mut Vec<FlagError>)
+{
+  // Returns the flag as it was used, if it was.
+  let used = |name: &String| {
+    find_flag(flags, name).and_then(|index| seen[index].as_ref())
+                          .map(|seen| seen.name.clone())
+  };
+
+  for group in groups {
+    match group {
+      FlagGroup::Conflicts(names) | FlagGroup::ExactlyOne(names) => {
+        let used = names.iter().filter_map(used).collect::<Vec<_>>();
+        if used.len() > 1 {
+          let flag = used[0].clone();
+          let error_type = FlagErrorType::Conflicts { flags: used };
+          errors.push(FlagError::new(error_type, flag));
+        } else if used.is_empty() &&
+                  matches!(group, FlagGroup::ExactlyOne(_))
+        {
+          let error_type = FlagErrorType::MissingOneOf { flags: names.clone() };
+          let flag = names.first().cloned().unwrap_or_default();
+          errors.push(FlagError::new(error_type, flag));
+        }
+      }
+      FlagGroup::Requires(name, required) => {
+        let Some(flag) = used(name) else {
+          continue;
+        };
+        let missing = required.iter()
+                              .filter(|name| used(name).is_none())
+                              .cloned()
+                              .collect::<Vec<_>>();
+        if !missing.is_empty() {
+          let error_type = FlagErrorType::Requires { required: missing };
+          errors.push(FlagError::new(error_type, flag));
+        }
+      }
+    }
+  }
+}
+
+// Sets flags from the environment and checks constraints between flags after
+// all arguments were parsed. Stops at the first kind of check that fails,
+// unless `collect` is set.
+fn finish_flags<V>(flags: &[ParserFlag],
+                   values: &mut V,
+                   seen: &mut [Option<Seen>],
+                   options: &ParseOptions,
+                   errors: &mut Vec<FlagError>,
+                   collect: bool)
+  where V: ValueStore + ?Sized
+{
+  parse_env(flags, values, seen, &options.groups, errors);
+  // Later configuration files go first, since flags that were already set
+  // are skipped.
+  for config in options.configs.iter().rev() {
+    if collect || errors.is_empty() {
+      let groups = &options.groups;
+      config::apply_config(flags, values, seen, groups, config, errors);
+    }
+  }
+  if collect || errors.is_empty() {
+    check_required(flags, seen, errors);
+  }
+  if collect || errors.is_empty() {
+    check_groups(flags, seen, &options.groups, errors);
+  }
+}
+
+// Returns the first error, if there is one.
+fn first_error(errors: Vec<FlagError>) -> Result<(), FlagError>
+{
+  match errors.into_iter().next() {
+    Some(error) => Err(error),
+    None => Ok(()),
+  }
+}
+
+/// Consumes and parses flags and arguments from
+/// [`Iterator<String>`](type@Iterator<String>).
+///
+/// # Returns
+/// ## Ok
+/// All arguments that are not flags. Can be empty.
+/// Changes references passed in the enums according to parsed flags.
+///
+/// ## Err
+/// - Unknown flag;
+/// - No value provided for a flag that requires it;
+/// - Short flag that takes a value was combined with other flag;
+/// - Value of a flag, its environment variable or configuration could not be
+///   parsed;
+/// - Required flag is missing.
+///
+/// # Example
+/// ```rust
+/// use std::env::args;
+/// use toiletcli::flags;
+/// use toiletcli::flags::{FlagType, parse_flags};
+///
+/// let mut color = String::new();
+/// let mut show_help = false;
+///
+/// let mut flags = flags!(
+///     color: StringFlag,   ["--color", "-c"],
+///     show_help: BoolFlag, ["--help"]
+/// );
+///
+/// let args = parse_flags(&mut args(), &mut flags);
+/// ```
+pub fn parse_flags<'a, Args, F>(args: &mut Args,
+                                 flags: &mut [F])
+                                 -> Result<Vec<String>, FlagError>
+  where Args: Iterator<Item = String>,
+        F: FlagEntry<'a>
+{
+  parse_flags_with(args, flags, &ParseOptions::default())
+}
+
+/// Works the same way as [`parse_flags`](fn@parse_flags), but with
+/// [`ParseOptions`](struct@ParseOptions).
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags;
+/// use toiletcli::flags::{FlagType, ParseOptions, parse_flags_with};
+///
+/// let mut verbose;
+///
+/// let mut flags = flags!(
+///     verbose: BoolFlag, ["--verbose"]
+/// );
+///
+/// let mut args = ["--verb"].iter().map(|x| x.to_string());
+///
+/// parse_flags_with(&mut args,
+///                  &mut flags,
+///                  ParseOptions::new().abbreviations(true)).unwrap();
+///
+/// assert!(verbose);
+/// ```
+pub fn parse_flags_with<'a, Args, F>(args: &mut Args,
+                                      flags: &mut [F],
+                                      options: &ParseOptions)
+                                      -> Result<Vec<String>, FlagError>
+  where Args: Iterator<Item = String>,
+        F: FlagEntry<'a>
+{
+  let mut args = args.by_ref().map(OsString::from);
+  let arguments = parse_flags_os_with(&mut args, flags, options)?;
+  Ok(arguments.into_iter().map(into_string).collect())
+}
+
+/// Works the same way as [`parse_flags`](fn@parse_flags), but takes
+/// [`Iterator<OsString>`](type@Iterator<OsString>), like
+/// [`std::env::args_os`](fn@std::env::args_os), which does not panic when
+/// arguments are not valid unicode.
+///
+/// Flags are still matched as unicode. Arguments and values of
+/// [`PathFlag`](type@FlagType::PathFlag) are kept as they were passed. Other
+/// flags will return
+/// [`FlagErrorType::InvalidUnicode`](type@FlagErrorType::InvalidUnicode) when
+/// their value is not valid unicode.
+///
+/// # Example
+/// ```rust
+/// use std::ffi::OsString;
+/// use std::path::PathBuf;
+/// use toiletcli::flags;
+/// use toiletcli::flags::{FlagType, parse_flags_os};
+///
+/// let mut output: PathBuf;
+///
+/// let mut flags = flags!(
+///     output: PathFlag, ["-o", "--output"]
+/// );
+///
+/// let mut args = ["-o", "out.txt", "in.txt"].iter().map(OsString::from);
+///
+/// let args = parse_flags_os(&mut args, &mut flags).unwrap();
+///
+/// assert_eq!(args, vec!["in.txt"]);
+/// assert_eq!(output, PathBuf::from("out.txt"));
+/// ```
+pub fn parse_flags_os<'a, Args, F>(args: &mut Args,
+                                    flags: &mut [F])
+                                    -> Result<Vec<OsString>, FlagError>
+  where Args: Iterator<Item = OsString>,
+        F: FlagEntry<'a>
+{
+  parse_flags_os_with(args, flags, &ParseOptions::default())
+}
+
+/// Works the same way as [`parse_flags_os`](fn@parse_flags_os), but with
+/// [`ParseOptions`](struct@ParseOptions).
+pub fn parse_flags_os_with<'a, Args, F>(args: &mut Args,
+                                         flags: &mut [F],
+                                         options: &ParseOptions)
+                                         -> Result<Vec<OsString>, FlagError>
+  where Args: Iterator<Item = OsString>,
+        F: FlagEntry<'a>
+{
+  let parser = Parser::describe(flags);
+  let mut seen = vec![None; flags.len()];
+  parse_flags_seen(args, &parser, flags, &mut seen, options)
+}
+
+/// Works the same way as [`parse_flags_with`](fn@parse_flags_with), but
+/// does not stop at the first error. Argument that caused an error is skipped,
+/// like the rest of short flags combined in it, and parsing continues with the
+/// next one.
+///
+/// # Returns
+/// Arguments that are not flags, and every error that was found. Flags and
+/// arguments are parsed as well as they could be, even when there are errors.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags;
+/// use toiletcli::flags::{FlagSpec, FlagType, ParseOptions,
+///                        parse_flags_collect_errors};
+///
+/// let mut verbose = false;
+/// let mut name = String::new();
+///
+/// let mut flags = vec![
+///     FlagSpec::new(FlagType::BoolFlag(&mut verbose), vec!["-v"]),
+///     FlagSpec::new(FlagType::StringFlag(&mut name), vec!["--name"])
+///       .required(true),
+/// ];
+///
+/// let argv = ["--verbsoe", "file", "-v=1", "-v"];
+/// let mut args = argv.iter().map(|x| x.to_string());
+///
+/// let (args, errors) =
+///   parse_flags_collect_errors(&mut args, &mut flags, &ParseOptions::new());
+///
+/// assert_eq!(args, vec!["file"]);
+/// assert_eq!(errors.to_string(),
+///            "Unknown flag --verbsoe\n\
+///             Flag -v does not take a value\n\
+///             Missing required flag --name");
+///
+/// drop(flags);
+/// assert!(verbose);
+/// ```
+pub fn parse_flags_collect_errors<'a, Args, F>(args: &mut Args,
+                                               flags: &mut [F],
+                                               options: &ParseOptions)
+                                               -> (Vec<String>, FlagErrors)
+  where Args: Iterator<Item = String>,
+        F: FlagEntry<'a>
+{
+  let mut args = args.by_ref().map(OsString::from);
+  let parser = Parser::describe(flags);
+  let mut seen = vec![None; flags.len()];
+  let mut errors = vec![];
+
+  let arguments = parse_flags_errors(&mut args,
+                                     &parser,
+                                     flags,
+                                     &mut seen,
+                                     options,
+                                     &mut errors,
+                                     true);
+  let arguments = arguments.into_iter().map(into_string).collect();
+
+  (arguments, FlagErrors { errors })
+}
+
+// Parses the whole input with flags of `parser`, setting their values in
+// `values`. Every flag that was set is seen as the name it was set with.
+fn parse_flags_seen<Args, V>(args: &mut Args,
+                             parser: &Parser,
+                             values: &mut V,
+                             seen: &mut [Option<Seen>],
+                             options: &ParseOptions)
+                             -> Result<Vec<OsString>, FlagError>
+  where Args: Iterator<Item = OsString>,
+        V: ValueStore + ?Sized
+{
+  let mut errors = vec![];
+  let arguments =
+    parse_flags_errors(args, parser, values, seen, options, &mut errors, false);
+  first_error(errors)?;
+  Ok(arguments)
+}
+
+// Parses the whole input, adding errors to `errors`. Stops at the first error,
+// unless `collect` is set.
+fn parse_flags_errors<Args, V>(args: &mut Args,
+                               parser: &Parser,
+                               values: &mut V,
+                               seen: &mut [Option<Seen>],
+                               options: &ParseOptions,
+                               errors: &mut Vec<FlagError>,
+                               collect: bool)
+                               -> Vec<OsString>
+  where Args: Iterator<Item = OsString>,
+        V: ValueStore + ?Sized
+{
+  let flags = parser.flags.as_slice();
+  #[cfg(debug_assertions)]
+  check_flags(flags, &options.groups);
+
+  let mut parsed_arguments: Vec<OsString> = vec![];
+  let mut ignore_rest = false;
+  let mut args = ResponseFiles::new(args, options.response_files);
+
+  while let Some(arg) = args.next() {
+    // Response files before this argument could fail to be read.
+    errors.extend(args.take_errors());
+    if !collect && !errors.is_empty() {
+      return parsed_arguments;
+    }
+
+    // Treat the rest of the input as arguments after encountering '--'.
+    if arg == "--" {
+      ignore_rest = true;
+      args.stop();
+      continue;
+    }
+
+    // Treat '-' as an argument, otherwise try to parse a flag.
+    let is_flag = if ignore_rest || arg == "-" {
+      Ok(false)
+    } else {
+      let index = args.consumed() - 1;
+      parse_arg(&arg, index, &mut args, flags, values, seen, options)
+    };
+
+    // Response file could fail to be read while looking for a flag value.
+    errors.extend(args.take_errors());
+
+    match is_flag {
+      Ok(true) => {}
+      Ok(false) => parsed_arguments.push(arg),
+      Err(error) => errors.push(error),
+    }
+    if !collect && !errors.is_empty() {
+      return parsed_arguments;
+    }
+  }
+
+  errors.extend(args.take_errors());
+  if collect || errors.is_empty() {
+    finish_flags(flags, values, seen, options, errors, collect);
+  }
+
+  parsed_arguments
+}
+
+/// Works the same way as [`parse_flags`](fn@parse_flags), but stops when it
+/// encounters the first argument. Consumes all flags before the first argument
+/// from `args` iterator, so `args` can be used again to parse the remaining
+/// contents. Will return `Ok("".to_string())` if no arguments were provided.
+///
+/// # Returns
+/// ## Ok
+/// First argument that is not a flag, or empty string when there is no
+/// arguments. Changes references passed in the enums according to parsed flags.
+///
+/// ## Err
+/// - Unknown flag;
+/// - No value provided for a flag that requires it;
+/// - Short flag that takes a value was combined with other flag;
+/// - Value of a flag, its environment variable or configuration could not be
+///   parsed;
+/// - Required flag is missing.
+///
+/// ### Example
+/// ```no_run
+/// use std::env::args;
+/// use toiletcli::flags;
+/// use toiletcli::flags::{FlagType, parse_flags, parse_flags_until_subcommand};
+///
+/// let mut args = args();
+///
+/// // Most often, path to the program will be the first argument.
+/// // This will prevent the function from parsing, as path to the program does not start with '-'.
+/// let program_name = args.next().unwrap();
+///
+/// let mut v_flag;
+///
+/// let mut main_flags = flags![
+///     v_flag: BoolFlag, ["-v"]
+/// ];
+///
+/// let subcommand = parse_flags_until_subcommand(&mut args, &mut main_flags).unwrap();
+///
+/// let mut d_flag;
+///
+/// let mut sub_flags = flags![
+///     d_flag: BoolFlag, ["-d"]
+/// ];
+///
+/// let subcommand_args = parse_flags(&mut args, &mut sub_flags);
+/// ```
+pub fn parse_flags_until_subcommand<'a, Args, F>(args: &mut Args,
+                                                  flags: &mut [F])
+                                                  -> Result<String, FlagError>
+  where Args: Iterator<Item = String>,
+        F: FlagEntry<'a>
+{
+  parse_flags_until_subcommand_with(args, flags, &ParseOptions::default())
+}
+
+/// Works the same way as
+/// [`parse_flags_until_subcommand`](fn@parse_flags_until_subcommand), but with
+/// [`ParseOptions`](struct@ParseOptions).
+pub fn parse_flags_until_subcommand_with<'a, Args, F>(args: &mut Args,
+                                                       flags: &mut [F],
+                                                       options: &ParseOptions)
+                                                       -> Result<String,
+                                                                 FlagError>
+  where Args: Iterator<Item = String>,
+        F: FlagEntry<'a>
+{
+  let mut args = args.by_ref().map(OsString::from);
+  let subcommand =
+    parse_flags_until_subcommand_os_with(&mut args, flags, options)?;
+  Ok(into_string(subcommand))
+}
+
+/// Works the same way as
+/// [`parse_flags_until_subcommand`](fn@parse_flags_until_subcommand), but
+/// takes [`Iterator<OsString>`](type@Iterator<OsString>), see
+/// [`parse_flags_os`](fn@parse_flags_os).
+pub fn parse_flags_until_subcommand_os<'a, Args, F>(args: &mut Args,
+                                                     flags: &mut [F])
+                                                     -> Result<OsString,
+                                                               FlagError>
+  where Args: Iterator<Item = OsString>,
+        F: FlagEntry<'a>
+{
+  parse_flags_until_subcommand_os_with(args, flags, &ParseOptions::default())
+}
+
+/// Works the same way as
+/// [`parse_flags_until_subcommand_os`](fn@parse_flags_until_subcommand_os),
+/// but with [`ParseOptions`](struct@ParseOptions).
+pub fn parse_flags_until_subcommand_os_with<'a, Args, F>(
+  args: &mut Args,
+  flags: &mut [F],
+  options: &ParseOptions)
+  -> Result<OsString, FlagError>
+  where Args: Iterator<Item = OsString>,
+        F: FlagEntry<'a>
+{
+  let parser = Parser::describe(flags);
+  let mut seen = vec![None; flags.len()];
+  parse_until_subcommand_seen(args, &parser, flags, &mut seen, options)
+}
+
+// Parses flags until the first argument that is not a flag, and returns it.
+fn parse_until_subcommand_seen<Args, V>(args: &mut Args,
+                                        parser: &Parser,
+                                        values: &mut V,
+                                        seen: &mut [Option<Seen>],
+                                        options: &ParseOptions)
+                                        -> Result<OsString, FlagError>
+  where Args: Iterator<Item = OsString>,
+        V: ValueStore + ?Sized
+{
+  let flags = parser.flags.as_slice();
+  #[cfg(debug_assertions)]
+  check_flags(flags, &options.groups);
+
+  let mut subcommand = OsString::new();
+  // Response files are never expanded here, arguments are only counted.
+  let mut args = ResponseFiles::new(args, false);
+
+  while let Some(arg) = args.next() {
+    let index = args.consumed() - 1;
+    // Treat '-'/'--' as arguments, otherwise try to parse a flag.
+    if arg == "-" ||
+       arg == "--" ||
+       !parse_arg(&arg, index, &mut args, flags, values, seen, options)?
+    {
+      subcommand = arg;
+      break;
+    }
+  }
+
+  let mut errors = vec![];
+  finish_flags(flags, values, seen, options, &mut errors, false);
+  first_error(errors)?;
+
+  Ok(subcommand)
+}
+
+// Converts arguments back to strings. They could only come from strings, since
+// response files are read as unicode too.
+fn into_string(arg: OsString) -> String
+{
+  arg.into_string().expect("arguments should be valid unicode")
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+
+  #[test]
+  fn flags_macro()
+  {
+    let mut color = String::new();
+    let mut show_help = false;
+
+    let flags = vec![(FlagType::StringFlag(&mut color), vec!["--color", "-c"]),
+                     (FlagType::BoolFlag(&mut show_help), vec!["--help"]),];
+
+    let mut color_macro;
+    let mut show_help_macro;
+
+    let flags_macro = flags!(
+        color_macro: StringFlag,   ["--color", "-c"],
+        show_help_macro: BoolFlag, ["--help"]
+    );
+
+    assert_eq!(flags, flags_macro);
+  }
+
+  #[test]
+  fn flag_everything_after()
+  {
+    let argv = vec!["program", "-v", "-rr", "--", "argument", "-file",
+                    "hello!", "-rrrr"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let mut v;
+    let mut r;
+
+    let mut flags = flags![
+        v: RepeatFlag, ["-v"],
+        r: RepeatFlag, ["-r"]
+    ];
+
+    let parsed_args = parse_flags(&mut args, &mut flags);
+
+    assert_eq!(parsed_args.unwrap(),
+               vec!["program", "argument", "-file", "hello!", "-rrrr"]);
+    assert_eq!(v, 1);
+    assert_eq!(r, 2);
+  }
+
+  #[test]
+  fn flag_everything_after_subcommand()
+  {
+    let argv = vec!["-v", "-rr", "--", "argument"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let mut v;
+    let mut r;
+
+    let mut flags = flags![
+        v: RepeatFlag, ["-v"],
+        r: RepeatFlag, ["-r"]
+    ];
+
+    let subcommand = parse_flags_until_subcommand(&mut args, &mut flags);
+    assert_eq!(&subcommand.unwrap(), "--");
+
+    let parsed_args = parse_flags(&mut args, &mut flags);
+
+    assert_eq!(parsed_args.unwrap(), vec!["argument"]);
+    assert_eq!(v, 1);
+    assert_eq!(r, 2);
+  }
+
+  #[test]
+  fn flag_repeat_flag()
+  {
+    let argv = vec!["program", "-vvvv", "-eee", "--test", "argument"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let mut v;
+    let mut e;
+    let mut t;
+    let mut unused;
+
+    let mut flags = flags![
+        v: RepeatFlag,      ["-v"],
+        e: RepeatFlag,      ["-e"],
+        unused: RepeatFlag, ["-u", "--unused"],
+        t: RepeatFlag,      ["-t", "--test"]
+    ];
+
+    let parsed_args = parse_flags(&mut args, &mut flags);
+
+    assert_eq!(v, 4);
+    assert_eq!(e, 3);
+    assert_eq!(unused, 0);
+    assert_eq!(t, 1);
+    assert_eq!(parsed_args.unwrap(), vec!["program", "argument"]);
+  }
+  #[test]
+  fn parse_flags_equals()
+  {
+    let argv = vec!["program",
+                    "arg_one",
+                    "-s=test1",
+                    "arg_two",
+                    "--long=test2",
+                    "--many=first",
+                    "arg_three",
+                    "--many",
+                    "second",
+                    "arg_four",];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let mut s;
+    let mut s2;
+    let mut m;
+
+    let mut flags = flags![
+        s: StringFlag,  ["-s"],
+        s2: StringFlag, ["--long"],
+        m: ManyFlag,    ["--many"]
+    ];
+
+    let parsed_args = parse_flags(&mut args, &mut flags).unwrap();
+
+    assert_eq!(parsed_args,
+               vec!["program", "arg_one", "arg_two", "arg_three", "arg_four"]);
+    assert_eq!(s, "test1");
+    assert_eq!(s2, "test2");
+    assert_eq!(m, vec!["first", "second"]);
+  }
+
+  #[test]
+  fn parse_flags_default()
+  {
+    let argv = vec!["program",
+                    "argument_one",
+                    "-aVns",
+                    "--long-specific",
+                    "something",
+                    "-vvvvv",
+                    "--many",
+                    "first",
+                    "--many",
+                    "second",
+                    "argument_two",];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let mut a = false;
+    let mut big_v = false;
+    let mut n = false;
+    let mut v = 0;
+    let mut s = false;
+
+    let mut long_specific = String::new();
+
+    let mut z = false;
+    let mut not_used = String::new();
+
+    let mut many = vec![];
+
+    let mut flags =
+      vec![(FlagType::BoolFlag(&mut a), vec!["-a"]),
+           (FlagType::BoolFlag(&mut big_v), vec!["-V"]),
+           (FlagType::BoolFlag(&mut n), vec!["-n"]),
+           (FlagType::BoolFlag(&mut s), vec!["-s"]),
+           (FlagType::StringFlag(&mut long_specific), vec!["--long-specific"]),
+           (FlagType::StringFlag(&mut not_used), vec!["--not-used"]),
+           (FlagType::RepeatFlag(&mut v), vec!["-v"]),
+           (FlagType::BoolFlag(&mut z), vec!["-z"]),
+           (FlagType::ManyFlag(&mut many), vec!["--many"]),];
+
+    let parsed_args = parse_flags(&mut args, &mut flags).unwrap();
+
+    assert_eq!(parsed_args, vec!["program", "argument_one", "argument_two"]);
+    assert_eq!(a && big_v && n && s, true);
+    assert_eq!(v, 5);
+    assert_eq!(z, false);
+    assert_eq!(long_specific, "something");
+    assert_eq!(not_used, "");
+    assert_eq!(many, vec!["first", "second"])
+  }
+
+  #[test]
+  fn parse_flags_no_arguments()
+  {
+    let argv = vec!["program", "-v", "-d"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let program_name = args.next().unwrap();
+
+    assert_eq!(program_name, "program".to_string());
+
+    let mut v;
+    let mut d;
+
+    let mut flags = flags![
+        v: BoolFlag, ["-v"],
+        d: BoolFlag, ["-d"]
+    ];
+
+    let parsed_args = parse_flags(&mut args, &mut flags);
+
+    assert_eq!(d, true);
+    assert_eq!(v, true);
+    assert!(parsed_args.unwrap().is_empty());
+  }
+
+  #[test]
+  fn parse_flags_subcommand()
+  {
+    let argv = vec!["program", "-v", "dump", "-d", "argument"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let program_name = args.next().unwrap();
+
+    assert_eq!(program_name, "program".to_string());
+
+    let mut v;
+
+    let mut main_flags = flags![
+        v: BoolFlag, ["-v"]
+    ];
+
+    let subcommand = parse_flags_until_subcommand(&mut args, &mut main_flags);
+
+    assert_eq!(v, true);
+    assert_eq!(subcommand.unwrap(), "dump".to_string());
+
+    let mut d;
+
+    let mut sub_flags = flags![
+        d: BoolFlag, ["-d"]
+    ];
+
+    let parsed_args = parse_flags(&mut args, &mut sub_flags);
+
+    assert_eq!(d, true);
+    assert_eq!(parsed_args.unwrap(), vec!["argument"]);
+  }
+
+  #[test]
+  fn parse_flags_subcommand_no_argument()
+  {
+    let argv = vec!["program", "-v", "-d"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let program_name = args.next().unwrap();
+
+    assert_eq!(program_name, "program".to_string());
+
+    let mut v;
+    let mut d;
+
+    let mut main_flags = flags![
+        v: BoolFlag, ["-v"],
+        d: BoolFlag, ["-d"]
+    ];
+
+    let subcommand = parse_flags_until_subcommand(&mut args, &mut main_flags);
+
+    assert_eq!(v && d, true);
+    assert!(subcommand.unwrap().is_empty());
+  }
+
+  #[test]
+  fn parse_flags_no_value_to_short_combined()
+  {
+    let argv = vec!["program", "-sb"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let program_name = args.next().unwrap();
+
+    assert_eq!(program_name, "program".to_string());
+
+    let mut v;
+    let mut d;
+
+    let mut main_flags = flags![
+        v: StringFlag, ["-s"],
+        d: BoolFlag,   ["-b"]
+    ];
+
+    let err = parse_flags(&mut args, &mut main_flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-s");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::CannotCombine);
+  }
+
+  #[test]
+  #[should_panic]
+  #[cfg(debug_assertions)]
+  fn parse_flags_malformed()
+  {
+    let args_vector = vec!["program".to_string()];
+
+    let mut malformed = false;
+
+    let mut flags = vec![(FlagType::BoolFlag(&mut malformed), vec!["m"])];
+
+    parse_flags(&mut args_vector.into_iter(), &mut flags).unwrap();
+  }
+
+  #[test]
+  #[should_panic]
+  #[cfg(debug_assertions)]
+  fn parse_flags_malformed_long()
+  {
+    let args_vector = vec!["program".to_string()];
+
+    let mut malformed = false;
+
+    let mut flags =
+      vec![(FlagType::BoolFlag(&mut malformed), vec!["-onedash"])];
+
+    parse_flags(&mut args_vector.into_iter(), &mut flags).unwrap();
+  }
+
+  #[test]
+  #[should_panic]
+  #[cfg(debug_assertions)]
+  fn parse_flags_malformed_space()
+  {
+    let args_vector = vec!["program".to_string()];
+
+    let mut malformed = false;
+
+    let mut flags =
+      vec![(FlagType::BoolFlag(&mut malformed), vec!["--space bar"])];
+
+    parse_flags(&mut args_vector.into_iter(), &mut flags).unwrap();
+  }
+
+  #[test]
+  #[should_panic]
+  #[cfg(debug_assertions)]
+  fn parse_flags_malformed_negated()
+  {
+    let args_vector = vec!["program".to_string()];
+
+    let mut cache = false;
+    let mut no_cache = false;
+
+    let mut flags =
+      vec![(FlagType::NegatableFlag(&mut cache), vec!["--cache"]),
+           (FlagType::BoolFlag(&mut no_cache), vec!["--no-cache"])];
+
+    parse_flags(&mut args_vector.into_iter(), &mut flags).unwrap();
+  }
+
+  #[test]
+  fn parse_flags_string_no_value()
+  {
+    let args_vector = vec!["program".to_string(), "-s".to_string()];
+
+    let mut s = String::new();
+
+    let mut flags = vec![(FlagType::StringFlag(&mut s), vec!["-s"])];
+
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-s");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::NoValueProvided);
+  }
+
+  #[test]
+  fn parse_flags_string_no_value_combine_order()
+  {
+    let args_vector = vec!["program".to_string(), "-bs".to_string()];
+
+    let mut s;
+    let mut b;
+
+    let mut flags = flags![
+        s: StringFlag, ["-s"],
+        b: BoolFlag,   ["-b"]
+    ];
+
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-s");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::CannotCombine);
+  }
+
+  #[test]
+  fn parse_flags_string_no_value_combine_order_2()
+  {
+    let args_vector = vec!["program".to_string(), "-sb".to_string()];
+
+    let mut s;
+    let mut b;
+
+    let mut flags = flags![
+        s: StringFlag, ["-s"],
+        b: BoolFlag,   ["-b"]
+    ];
+
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-s");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::CannotCombine);
+  }
+
+  #[test]
+  fn parse_flags_string_no_value_combine_order_many_3()
+  {
+    let args_vector = vec!["program".to_string(), "-cbm".to_string()];
+
+    let mut s;
+    let mut b;
+    let mut c;
+
+    let mut flags = flags![
+        s: ManyFlag, ["-m"],
+        b: BoolFlag, ["-b"],
+        c: BoolFlag, ["-c"]
+    ];
+
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-m");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::CannotCombine);
+  }
+
+  #[test]
+  fn parse_flags_string_no_value_combine_order_many_4()
+  {
+    let args_vector = vec!["program".to_string(), "-cmb".to_string()];
+
+    let mut s;
+    let mut b;
+    let mut c;
+
+    let mut flags = flags![
+        s: ManyFlag, ["-m"],
+        b: BoolFlag, ["-b"],
+        c: BoolFlag, ["-c"]
+    ];
+
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-m");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::CannotCombine);
+  }
+
+  #[test]
+  fn parse_flags_extra_value()
+  {
+    let args_vector = vec!["program".to_string(), "-s=test".to_string()];
+
+    let mut s = false;
+
+    let mut flags = vec![(FlagType::BoolFlag(&mut s), vec!["-s"])];
+
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-s");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::ExtraValueProvided);
+  }
+
+  #[test]
+  fn parse_flags_extra_value_combine()
+  {
+    let args_vector = vec!["program".to_string(), "-rs=test".to_string()];
 
-  while let Some(arg) = args.next() {
-    // Treat '-'/'--' as arguments, otherwise try to parse a flag.
-    if arg == "-" || arg == "--" || !parse_arg(&arg, args, flags)? {
-      return Ok(arg);
-    }
+    let mut s = false;
+    let mut r = 0;
+
+    let mut flags = vec![(FlagType::RepeatFlag(&mut r), vec!["-r"]),
+                         (FlagType::BoolFlag(&mut s), vec!["-s"])];
+
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-r");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::ExtraValueProvided);
   }
 
-  Ok("".to_string())
-}
+  #[test]
+  fn parse_flags_extra_value_repeat()
+  {
+    let args_vector = vec!["program".to_string(), "-r=test".to_string()];
 
-#[cfg(test)]
-mod tests
-{
-  use super::*;
+    let mut r = 0;
+
+    let mut flags = vec![(FlagType::RepeatFlag(&mut r), vec!["-r"])];
+
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-r");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::ExtraValueProvided);
+  }
 
   #[test]
-  fn flags_macro()
+  fn parse_flags_extra_value_repeat_2()
   {
-    let mut color = String::new();
-    let mut show_help = false;
+    let args_vector = vec!["program".to_string(), "-rr=test".to_string()];
 
-    let flags = vec![(FlagType::StringFlag(&mut color), vec!["--color", "-c"]),
-                     (FlagType::BoolFlag(&mut show_help), vec!["--help"]),];
+    let mut r = 0;
 
-    let mut color_macro;
-    let mut show_help_macro;
+    let mut flags = vec![(FlagType::RepeatFlag(&mut r), vec!["-r"])];
 
-    let flags_macro = flags!(
-        color_macro: StringFlag,   ["--color", "-c"],
-        show_help_macro: BoolFlag, ["--help"]
-    );
+    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
 
-    assert_eq!(flags, flags_macro);
+    assert!(err.is_err());
+    assert!(err.as_ref().unwrap_err().flag == "-r");
+    assert!(err.as_ref().unwrap_err().error_type ==
+            FlagErrorType::ExtraValueProvided);
   }
 
   #[test]
-  fn flag_everything_after()
+  fn parse_flags_typed()
   {
-    let argv = vec!["program", "-v", "-rr", "--", "argument", "-file",
-                    "hello!", "-rrrr"];
+    let argv = ["program", "-n", "5", "--size=-12", "-p", "1", "--port=22"];
     let mut args = argv.iter().map(|x| x.to_string());
 
-    let mut v;
-    let mut r;
+    let mut n: u32;
+    let mut size: i64;
+    let mut ports: Vec<u16>;
 
     let mut flags = flags![
-        v: RepeatFlag, ["-v"],
-        r: RepeatFlag, ["-r"]
+        n: TypedFlag,         ["-n"],
+        size: TypedFlag,      ["--size"],
+        ports: TypedManyFlag, ["-p", "--port"]
     ];
 
-    let parsed_args = parse_flags(&mut args, &mut flags);
+    let parsed_args = parse_flags(&mut args, &mut flags).unwrap();
 
-    assert_eq!(parsed_args.unwrap(),
-               vec!["program", "argument", "-file", "hello!", "-rrrr"]);
-    assert_eq!(v, 1);
-    assert_eq!(r, 2);
+    assert_eq!(parsed_args, vec!["program"]);
+    assert_eq!(n, 5);
+    assert_eq!(size, -12);
+    assert_eq!(ports, vec![1, 22]);
   }
 
   #[test]
-  fn flag_everything_after_subcommand()
+  fn parse_flags_typed_invalid()
   {
-    let argv = vec!["-v", "-rr", "--", "argument"];
+    let argv = ["program", "--port", "80", "--port", "http"];
     let mut args = argv.iter().map(|x| x.to_string());
 
-    let mut v;
-    let mut r;
+    let mut ports: Vec<u16> = vec![];
 
-    let mut flags = flags![
-        v: RepeatFlag, ["-v"],
-        r: RepeatFlag, ["-r"]
-    ];
+    let mut flags =
+      vec![(FlagType::TypedManyFlag(&mut ports), vec!["-p", "--port"])];
 
-    let subcommand = parse_flags_until_subcommand(&mut args, &mut flags);
-    assert_eq!(&subcommand.unwrap(), "--");
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
 
-    let parsed_args = parse_flags(&mut args, &mut flags);
+    let value = "http".to_string();
+    let reason = "invalid digit found in string".to_string();
 
-    assert_eq!(parsed_args.unwrap(), vec!["argument"]);
-    assert_eq!(v, 1);
-    assert_eq!(r, 2);
+    assert_eq!(err.flag, "--port");
+    assert_eq!(err.error_type, FlagErrorType::InvalidValue { value, reason });
+    assert_eq!(err.to_string(),
+               "Invalid value 'http' for --port: invalid digit found in \
+                string");
   }
 
   #[test]
-  fn flag_repeat_flag()
+  fn parse_flags_env()
   {
-    let argv = vec!["program", "-vvvv", "-eee", "--test", "argument"];
+    std::env::set_var("TOILETCLI_TEST_ENV_COLOR", "red");
+    std::env::set_var("TOILETCLI_TEST_ENV_CACHE", "yes");
+    std::env::set_var("TOILETCLI_TEST_ENV_VERBOSE", "3");
+    std::env::set_var("TOILETCLI_TEST_ENV_PATH", "a:b,c");
+    std::env::set_var("TOILETCLI_TEST_ENV_JOBS", "8");
+
+    let argv = ["program", "--color", "blue", "-j", "2", "argument"];
     let mut args = argv.iter().map(|x| x.to_string());
 
-    let mut v;
-    let mut e;
-    let mut t;
-    let mut unused;
+    let mut color = String::new();
+    let mut cache = false;
+    let mut verbose = 0;
+    let mut path = vec![];
+    let mut jobs: u32 = 0;
 
-    let mut flags = flags![
-        v: RepeatFlag,      ["-v"],
-        e: RepeatFlag,      ["-e"],
-        unused: RepeatFlag, ["-u", "--unused"],
-        t: RepeatFlag,      ["-t", "--test"]
-    ];
+    let mut flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut color), vec!["--color"])
+             .env("TOILETCLI_TEST_ENV_COLOR"),
+           FlagSpec::new(FlagType::BoolFlag(&mut cache), vec!["--cache"])
+             .env("TOILETCLI_TEST_ENV_CACHE"),
+           FlagSpec::new(FlagType::RepeatFlag(&mut verbose), vec!["-v"])
+             .env("TOILETCLI_TEST_ENV_VERBOSE"),
+           FlagSpec::new(FlagType::ManyFlag(&mut path), vec!["--path"])
+             .env("TOILETCLI_TEST_ENV_PATH"),
+           FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["-j"])
+             .env("TOILETCLI_TEST_ENV_JOBS"),];
 
-    let parsed_args = parse_flags(&mut args, &mut flags);
+    let parsed_args = parse_flags(&mut args, &mut flags).unwrap();
 
-    assert_eq!(v, 4);
-    assert_eq!(e, 3);
-    assert_eq!(unused, 0);
-    assert_eq!(t, 1);
-    assert_eq!(parsed_args.unwrap(), vec!["program", "argument"]);
+    assert_eq!(parsed_args, vec!["program", "argument"]);
+    assert_eq!(color, "blue");
+    assert!(cache);
+    assert_eq!(verbose, 3);
+    assert_eq!(path, vec!["a", "b", "c"]);
+    assert_eq!(jobs, 2);
   }
+
   #[test]
-  fn parse_flags_equals()
+  fn parse_flags_env_invalid()
   {
-    let argv = vec!["program",
-                    "arg_one",
-                    "-s=test1",
-                    "arg_two",
-                    "--long=test2",
-                    "--many=first",
-                    "arg_three",
-                    "--many",
-                    "second",
-                    "arg_four",];
+    std::env::set_var("TOILETCLI_TEST_ENV_INVALID", "maybe");
+
+    let mut args = ["program"].iter().map(|x| x.to_string());
+
+    let mut cache = false;
+
+    let mut flags =
+      vec![FlagSpec::new(FlagType::BoolFlag(&mut cache), vec!["--cache"])
+             .env("TOILETCLI_TEST_ENV_INVALID"),];
+
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
+
+    let value = "maybe".to_string();
+    let reason = "expected a boolean".to_string();
+
+    assert_eq!(err.flag, "TOILETCLI_TEST_ENV_INVALID");
+    assert_eq!(err.error_type, FlagErrorType::InvalidValue { value, reason });
+  }
+
+  #[test]
+  fn parse_flags_unknown_suggestions()
+  {
+    let argv = ["program", "--colour", "always"];
     let mut args = argv.iter().map(|x| x.to_string());
 
-    let mut s;
-    let mut s2;
-    let mut m;
+    let mut color;
+    let mut cold;
+    let mut verbose;
 
     let mut flags = flags![
-        s: StringFlag,  ["-s"],
-        s2: StringFlag, ["--long"],
-        m: ManyFlag,    ["--many"]
+        color: StringFlag, ["-c", "--color"],
+        cold: BoolFlag,    ["--cold"],
+        verbose: BoolFlag, ["--verbose"]
     ];
 
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
+
+    assert_eq!(err.error_type, FlagErrorType::Unknown);
+    assert_eq!(err.suggestions, vec!["--color"]);
+    assert_eq!(err.to_string(), "Unknown flag --colour, did you mean --color?");
+
+    let mut args = ["--verb"].iter().map(|x| x.to_string());
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
+    assert_eq!(err.suggestions, vec!["--verbose"]);
+
+    let mut args = ["--nothing-like-it"].iter().map(|x| x.to_string());
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
+    assert!(err.suggestions.is_empty());
+    assert_eq!(err.to_string(), "Unknown flag --nothing-like-it");
+  }
+
+  #[test]
+  fn check_subcommand_suggestions()
+  {
+    let subcommands = ["add", "remove", "rename"];
+
+    assert!(check_subcommand("add", &subcommands).is_ok());
+    assert!(check_subcommand("", &subcommands).is_ok());
+
+    let err = check_subcommand("remve", &subcommands).unwrap_err();
+    assert_eq!(err.error_type,
+               FlagErrorType::UnknownSubcommand { subcommands:
+                                                    to_strings(&subcommands) });
+    assert_eq!(err.flag, "remve");
+    assert_eq!(err.suggestions, vec!["remove"]);
+
+    let err = check_subcommand("re", &subcommands).unwrap_err();
+    assert_eq!(err.suggestions, vec!["remove", "rename"]);
+
+    let err = check_subcommand("list", &subcommands).unwrap_err();
+    assert_eq!(err.to_string(),
+               "Unknown subcommand list, expected one of add, remove, rename");
+  }
+
+  #[test]
+  fn parse_flags_negatable()
+  {
+    let argv = ["program", "--no-cache", "-c", "--no-color", "--color"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let mut cache = true;
+    let mut color = false;
+
+    let mut flags =
+      vec![(FlagType::NegatableFlag(&mut cache), vec!["--cache"]),
+           (FlagType::NegatableFlag(&mut color), vec!["-c", "--color"]),];
+
     let parsed_args = parse_flags(&mut args, &mut flags).unwrap();
 
-    assert_eq!(parsed_args,
-               vec!["program", "arg_one", "arg_two", "arg_three", "arg_four"]);
-    assert_eq!(s, "test1");
-    assert_eq!(s2, "test2");
-    assert_eq!(m, vec!["first", "second"]);
+    assert_eq!(parsed_args, vec!["program"]);
+    assert!(!cache);
+    assert!(color);
   }
 
   #[test]
-  fn parse_flags_default()
+  fn parse_flags_negatable_value()
   {
-    let argv = vec!["program",
-                    "argument_one",
-                    "-aVns",
-                    "--long-specific",
-                    "something",
-                    "-vvvvv",
-                    "--many",
-                    "first",
-                    "--many",
-                    "second",
-                    "argument_two",];
+    let argv = ["program", "--no-cache=yes"];
     let mut args = argv.iter().map(|x| x.to_string());
 
-    let mut a = false;
-    let mut big_v = false;
-    let mut n = false;
-    let mut v = 0;
-    let mut s = false;
+    let mut cache = false;
 
-    let mut long_specific = String::new();
+    let mut flags =
+      vec![(FlagType::NegatableFlag(&mut cache), vec!["--cache"])];
 
-    let mut z = false;
-    let mut not_used = String::new();
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
 
-    let mut many = vec![];
+    assert_eq!(err.flag, "--no-cache");
+    assert_eq!(err.error_type, FlagErrorType::ExtraValueProvided);
+
+    let mut args = ["--no-cahce"].iter().map(|x| x.to_string());
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
+
+    assert_eq!(err.suggestions, vec!["--no-cache"]);
+  }
+
+  #[test]
+  fn parse_flags_optional_value()
+  {
+    let argv = ["program", "--color", "file.txt", "-w", "-q"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let mut color = String::new();
+    let mut width = String::new();
+    let mut quiet = false;
 
     let mut flags =
-      vec![(FlagType::BoolFlag(&mut a), vec!["-a"]),
-           (FlagType::BoolFlag(&mut big_v), vec!["-V"]),
-           (FlagType::BoolFlag(&mut n), vec!["-n"]),
-           (FlagType::BoolFlag(&mut s), vec!["-s"]),
-           (FlagType::StringFlag(&mut long_specific), vec!["--long-specific"]),
-           (FlagType::StringFlag(&mut not_used), vec!["--not-used"]),
-           (FlagType::RepeatFlag(&mut v), vec!["-v"]),
-           (FlagType::BoolFlag(&mut z), vec!["-z"]),
-           (FlagType::ManyFlag(&mut many), vec!["--many"]),];
+      vec![(FlagType::OptionalFlag(&mut color, "always"), vec!["--color"]),
+           (FlagType::OptionalFlag(&mut width, "80"), vec!["-w", "--width"]),
+           (FlagType::BoolFlag(&mut quiet), vec!["-q"]),];
 
     let parsed_args = parse_flags(&mut args, &mut flags).unwrap();
 
-    assert_eq!(parsed_args, vec!["program", "argument_one", "argument_two"]);
-    assert_eq!(a && big_v && n && s, true);
-    assert_eq!(v, 5);
-    assert_eq!(z, false);
-    assert_eq!(long_specific, "something");
-    assert_eq!(not_used, "");
-    assert_eq!(many, vec!["first", "second"])
+    assert_eq!(parsed_args, vec!["program", "file.txt"]);
+    assert_eq!(color, "always");
+    assert_eq!(width, "80");
+    assert!(quiet);
   }
 
   #[test]
-  fn parse_flags_no_arguments()
+  fn parse_flags_optional_value_attached()
   {
-    let argv = vec!["program", "-v", "-d"];
+    fn parse_width(arg: &str) -> String
+    {
+      let mut width = String::new();
+      let mut quiet = false;
+
+      let mut flags =
+        vec![(FlagType::OptionalFlag(&mut width, "80"), vec!["-w", "--width"]),
+             (FlagType::BoolFlag(&mut quiet), vec!["-q"]),];
+
+      let mut args = [arg, "next"].into_iter().map(|x| x.to_string());
+      let parsed_args = parse_flags(&mut args, &mut flags).unwrap();
+
+      assert_eq!(parsed_args, vec!["next"]);
+      width
+    }
+
+    assert_eq!(parse_width("--width=120"), "120");
+    assert_eq!(parse_width("--width="), "");
+    assert_eq!(parse_width("-w=40"), "40");
+    assert_eq!(parse_width("-qw40"), "40");
+    assert_eq!(parse_width("-wq"), "q");
+    assert_eq!(parse_width("-qw"), "80");
+  }
+
+  #[test]
+  fn parse_flags_abbreviations()
+  {
+    let argv = ["program", "--verb", "--col=red", "--no-c", "--version"];
     let mut args = argv.iter().map(|x| x.to_string());
 
-    let program_name = args.next().unwrap();
+    let mut verbose;
+    let mut version;
+    let mut color;
+    let mut cache;
 
-    assert_eq!(program_name, "program".to_string());
+    let mut flags = flags![
+        verbose: BoolFlag,     ["--verbose", "--verbosity"],
+        version: BoolFlag,     ["--version"],
+        color: StringFlag,     ["--color"],
+        cache: NegatableFlag,  ["--cache"]
+    ];
 
-    let mut v;
-    let mut d;
+    let mut options = ParseOptions::new();
+    options.abbreviations(true);
+    let parsed_args = parse_flags_with(&mut args, &mut flags, &options);
+
+    assert_eq!(parsed_args.unwrap(), vec!["program"]);
+    assert!(verbose);
+    assert!(version);
+    assert_eq!(color, "red");
+    assert!(!cache);
+  }
+
+  #[test]
+  fn parse_flags_abbreviations_ambiguous()
+  {
+    let mut verbose;
+    let mut version;
+    let mut ver;
 
     let mut flags = flags![
-        v: BoolFlag, ["-v"],
-        d: BoolFlag, ["-d"]
+        verbose: BoolFlag, ["--verbose"],
+        version: BoolFlag, ["--version"]
     ];
 
-    let parsed_args = parse_flags(&mut args, &mut flags);
+    let mut options = ParseOptions::new();
+    options.abbreviations(true);
 
-    assert_eq!(d, true);
-    assert_eq!(v, true);
-    assert!(parsed_args.unwrap().is_empty());
+    let mut args = ["--ver"].iter().map(|x| x.to_string());
+    let err = parse_flags_with(&mut args, &mut flags, &options).unwrap_err();
+
+    let candidates = vec!["--verbose".to_string(), "--version".to_string()];
+
+    assert_eq!(err.flag, "--ver");
+    assert_eq!(err.error_type, FlagErrorType::AmbiguousPrefix { candidates });
+    assert_eq!(err.to_string(),
+               "Flag --ver is ambiguous, could be --verbose or --version");
+
+    // Without abbreviations, prefixes are unknown.
+    let mut args = ["--verb"].iter().map(|x| x.to_string());
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
+    assert_eq!(err.error_type, FlagErrorType::Unknown);
+
+    // Exact match wins.
+    let mut flags = flags![
+        verbose: BoolFlag, ["--verbose"],
+        ver: BoolFlag,     ["--ver"]
+    ];
+
+    let mut args = ["--ver"].iter().map(|x| x.to_string());
+    parse_flags_with(&mut args, &mut flags, &options).unwrap();
+
+    assert!(ver);
+    assert!(!verbose);
   }
 
   #[test]
-  fn parse_flags_subcommand()
+  fn parse_flags_attached_values()
   {
-    let argv = vec!["program", "-v", "dump", "-d", "argument"];
+    let argv = ["program", "-n5", "-vofile", "-vo", "out", "-vm=a=b", "-m="];
     let mut args = argv.iter().map(|x| x.to_string());
 
-    let program_name = args.next().unwrap();
+    let mut n: u32;
+    let mut o;
+    let mut v;
+    let mut m;
+
+    let mut flags = flags![
+        n: TypedFlag,  ["-n"],
+        o: ManyFlag,   ["-o"],
+        v: RepeatFlag, ["-v"],
+        m: ManyFlag,   ["-m"]
+    ];
+
+    let mut options = ParseOptions::new();
+    options.attached_values(true);
+
+    let parsed_args = parse_flags_with(&mut args, &mut flags, &options);
 
-    assert_eq!(program_name, "program".to_string());
+    assert_eq!(parsed_args.unwrap(), vec!["program"]);
+    assert_eq!(n, 5);
+    assert_eq!(o, vec!["file", "out"]);
+    assert_eq!(v, 3);
+    assert_eq!(m, vec!["a=b", ""]);
+  }
 
+  #[test]
+  fn parse_flags_attached_values_errors()
+  {
+    let mut o;
     let mut v;
 
-    let mut main_flags = flags![
+    let mut flags = flags![
+        o: ManyFlag, ["-o"],
         v: BoolFlag, ["-v"]
     ];
 
-    let subcommand = parse_flags_until_subcommand(&mut args, &mut main_flags);
+    let mut options = ParseOptions::new();
+    options.attached_values(true);
 
-    assert_eq!(v, true);
-    assert_eq!(subcommand.unwrap(), "dump".to_string());
+    let mut args = ["-ov"].iter().map(|x| x.to_string());
+    parse_flags_with(&mut args, &mut flags, &options).unwrap();
 
-    let mut d;
+    let mut args = ["-vo"].iter().map(|x| x.to_string());
+    let err = parse_flags_with(&mut args, &mut flags, &options).unwrap_err();
+    assert_eq!(err.flag, "-o");
+    assert_eq!(err.error_type, FlagErrorType::NoValueProvided);
 
-    let mut sub_flags = flags![
-        d: BoolFlag, ["-d"]
-    ];
+    let mut args = ["-ov=x"].iter().map(|x| x.to_string());
+    parse_flags_with(&mut args, &mut flags, &options).unwrap();
 
-    let parsed_args = parse_flags(&mut args, &mut sub_flags);
+    let mut args = ["-v=x"].iter().map(|x| x.to_string());
+    let err = parse_flags_with(&mut args, &mut flags, &options).unwrap_err();
+    assert_eq!(err.flag, "-v");
+    assert_eq!(err.error_type, FlagErrorType::ExtraValueProvided);
 
-    assert_eq!(d, true);
-    assert_eq!(parsed_args.unwrap(), vec!["argument"]);
+    assert_eq!(o, vec!["v", "v=x"]);
+    assert!(v);
   }
 
   #[test]
-  fn parse_flags_subcommand_no_argument()
+  fn parse_flags_map()
   {
-    let argv = vec!["program", "-v", "-d"];
+    std::env::set_var("TOILETCLI_TEST_MAP_UNDEFINE", "A=1,URL=http://a");
+
+    let mut defines = HashMap::new();
+    let mut first = HashMap::new();
+    let mut undefines = HashMap::new();
+
+    let mut flags =
+      vec![FlagSpec::new(FlagType::MapFlag(&mut defines, DuplicateKeys::Error),
+                         vec!["-D", "--define"]),
+           FlagSpec::new(FlagType::MapFlag(&mut first, DuplicateKeys::First),
+                         vec!["-F"]),
+           FlagSpec::new(FlagType::MapFlag(&mut undefines,
+                                           DuplicateKeys::Last),
+                         vec!["-U"]).env("TOILETCLI_TEST_MAP_UNDEFINE"),];
+
+    let mut options = ParseOptions::new();
+    options.attached_values(true);
+
+    let argv = ["-DA=1", "-D", "B", "--define=C=x=y", "-D=D=", "-F", "k=1",
+                "-Fk=2"];
     let mut args = argv.iter().map(|x| x.to_string());
+    parse_flags_with(&mut args, &mut flags, &options).unwrap();
 
-    let program_name = args.next().unwrap();
+    let mut args = ["-DA=2"].iter().map(|x| x.to_string());
+    let err = parse_flags_with(&mut args, &mut flags, &options).unwrap_err();
 
-    assert_eq!(program_name, "program".to_string());
+    assert_eq!(err.to_string(),
+               "Invalid value 'A=2' for -D: duplicate key A");
 
-    let mut v;
-    let mut d;
+    let mut args = ["-D", "=1"].iter().map(|x| x.to_string());
+    let err = parse_flags_with(&mut args, &mut flags, &options).unwrap_err();
 
-    let mut main_flags = flags![
-        v: BoolFlag, ["-v"],
-        d: BoolFlag, ["-d"]
-    ];
+    assert_eq!(err.position.as_deref(),
+               Some(&ArgPosition { index: 1, span: 0..2 }));
 
-    let subcommand = parse_flags_until_subcommand(&mut args, &mut main_flags);
+    drop(flags);
 
-    assert_eq!(v && d, true);
-    assert!(subcommand.unwrap().is_empty());
+    let pairs = |pairs: &[(&str, &str)]| {
+      pairs.iter()
+           .map(|(key, value)| (key.to_string(), value.to_string()))
+           .collect::<HashMap<_, _>>()
+    };
+
+    assert_eq!(defines,
+               pairs(&[("A", "1"), ("B", ""), ("C", "x=y"), ("D", "")]));
+    assert_eq!(first, pairs(&[("k", "1")]));
+    assert_eq!(undefines, pairs(&[("A", "1"), ("URL", "http://a")]));
   }
 
   #[test]
-  fn parse_flags_no_value_to_short_combined()
+  fn parse_flags_list()
   {
-    let argv = vec!["program", "-sb"];
+    std::env::set_var("TOILETCLI_TEST_LIST_PORTS", "8080;1;8080;22");
+
+    let mut tags: Vec<String> = vec![];
+    let mut ports: Vec<u16> = vec![];
+
+    let mut flags =
+      vec![(FlagType::ListFlag(&mut tags, Separator::new(',')), vec!["-t"]),
+           (FlagType::ListFlag(&mut ports, Separator::new(';').dedup(true)),
+            vec!["--ports"]),];
+
+    let argv = ["-t", r"a,,b\,c,d\\,e\f,g\", "-t=a", "--ports", "80;22;80"];
     let mut args = argv.iter().map(|x| x.to_string());
+    parse_flags(&mut args, &mut flags).unwrap();
+    drop(flags);
 
-    let program_name = args.next().unwrap();
+    assert_eq!(tags, vec!["a", "b,c", r"d\", r"e\f", r"g\", "a"]);
+    assert_eq!(ports, vec![80, 22]);
 
-    assert_eq!(program_name, "program".to_string());
+    // Values that were present before parsing are kept as they are.
+    let mut ports: Vec<u16> = vec![1, 1];
+    let mut flags = vec![FlagSpec::new(FlagType::ListFlag(&mut ports,
+                                                          Separator::new(';')
+                                                            .dedup(true)),
+                                       vec!["--ports"])
+                           .env("TOILETCLI_TEST_LIST_PORTS")];
 
-    let mut v;
-    let mut d;
+    parse_flags(&mut std::iter::empty(), &mut flags).unwrap();
 
-    let mut main_flags = flags![
-        v: StringFlag, ["-s"],
-        d: BoolFlag,   ["-b"]
-    ];
+    let mut args = ["--ports", "80;22;x"].iter().map(|x| x.to_string());
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
 
-    let err = parse_flags(&mut args, &mut main_flags);
+    assert_eq!(err.to_string(),
+               "Invalid value '80;22;x' for --ports: 'x': invalid digit found \
+                in string");
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-s");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::CannotCombine);
+    drop(flags);
+
+    assert_eq!(ports, vec![1, 1, 8080, 22]);
   }
 
   #[test]
-  #[should_panic]
-  #[cfg(debug_assertions)]
-  fn parse_flags_malformed()
+  fn parse_flags_response_files()
   {
-    let args_vector = vec!["program".to_string()];
+    // Every run has its own directory, so runs don't overwrite each other.
+    let dir = std::env::temp_dir()
+      .join(format!("toiletcli_test_response_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let outer = dir.join("outer");
+    let inner = dir.join("inner");
+    let cycle = dir.join("cycle");
+
+    std::fs::write(&outer,
+                   format!("-v --name 'hello world'\n@{} @@literal",
+                           inner.display())).unwrap();
+    std::fs::write(&inner, "-v \"inner argument\"").unwrap();
+    std::fs::write(&cycle, format!("-v @{}", cycle.display())).unwrap();
 
-    let mut malformed = false;
+    let mut v;
+    let mut name;
 
-    let mut flags = vec![(FlagType::BoolFlag(&mut malformed), vec!["m"])];
+    let mut flags = flags![
+        v: RepeatFlag,    ["-v"],
+        name: StringFlag, ["--name"]
+    ];
 
-    parse_flags(&mut args_vector.into_iter(), &mut flags).unwrap();
+    let mut options = ParseOptions::new();
+    options.response_files(true);
+
+    let argv = ["program".to_string(),
+                format!("@{}", outer.display()),
+                "--".to_string(),
+                format!("@{}", inner.display())];
+    let mut args = argv.into_iter();
+
+    let parsed_args = parse_flags_with(&mut args, &mut flags, &options);
+
+    assert_eq!(parsed_args.unwrap(),
+               vec!["program".to_string(),
+                    "inner argument".to_string(),
+                    "@literal".to_string(),
+                    format!("@{}", inner.display())]);
+
+    let mut args = [format!("@{}", cycle.display())].into_iter();
+    let err = parse_flags_with(&mut args, &mut flags, &options).unwrap_err();
+
+    let reason = "file includes itself".to_string();
+
+    assert_eq!(err.flag, format!("@{}", cycle.display()));
+    assert_eq!(err.error_type, FlagErrorType::ResponseFile { reason });
+
+    let argv = ["--name", "@toiletcli_no_such_file"];
+    let mut args = argv.iter().map(|x| x.to_string());
+    let err = parse_flags_with(&mut args, &mut flags, &options).unwrap_err();
+
+    assert_eq!(err.flag, "@toiletcli_no_such_file");
+    assert!(matches!(err.error_type, FlagErrorType::ResponseFile { .. }));
+    assert_eq!(err.position.as_deref(),
+               Some(&ArgPosition { index: 1, span: 0..23 }));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(v, 3);
+    assert_eq!(name, "hello world");
   }
 
   #[test]
-  #[should_panic]
-  #[cfg(debug_assertions)]
-  fn parse_flags_malformed_long()
+  fn parse_flags_response_files_collect()
   {
-    let args_vector = vec!["program".to_string()];
+    let mut v;
+    let mut name;
 
-    let mut malformed = false;
+    let mut flags = flags![
+        v: RepeatFlag,    ["-v"],
+        name: StringFlag, ["--name"]
+    ];
 
-    let mut flags =
-      vec![(FlagType::BoolFlag(&mut malformed), vec!["-onedash"])];
+    let mut options = ParseOptions::new();
+    options.response_files(true);
 
-    parse_flags(&mut args_vector.into_iter(), &mut flags).unwrap();
+    let argv = ["@toiletcli_no_such_file", "-v", "a.txt", "--bogus",
+                "@toiletcli_no_such_file", "b.txt"];
+    let mut args = argv.iter().map(|x| x.to_string());
+    let (arguments, errors) =
+      parse_flags_collect_errors(&mut args, &mut flags, &options);
+    let errors = errors.errors;
+
+    assert_eq!(arguments, vec!["a.txt", "b.txt"]);
+    assert_eq!(errors.len(), 3);
+    assert!(matches!(errors[0].error_type, FlagErrorType::ResponseFile { .. }));
+    assert_eq!(errors[0].position.as_deref(),
+               Some(&ArgPosition { index: 0, span: 0..23 }));
+    assert_eq!(errors[1].to_string(), "Unknown flag --bogus");
+    assert!(matches!(errors[2].error_type, FlagErrorType::ResponseFile { .. }));
+    assert_eq!(errors[2].position.as_deref(),
+               Some(&ArgPosition { index: 4, span: 0..23 }));
+
+    drop(flags);
+
+    assert_eq!(v, 1);
   }
 
   #[test]
-  #[should_panic]
-  #[cfg(debug_assertions)]
-  fn parse_flags_malformed_space()
+  fn parse_flags_required()
   {
-    let args_vector = vec!["program".to_string()];
+    std::env::set_var("TOILETCLI_TEST_REQUIRED_INPUT", "input.txt");
 
-    let mut malformed = false;
+    let mut output = String::new();
+    let mut input = String::new();
+    let mut jobs: u32 = 0;
+    let mut verbose = false;
 
     let mut flags =
-      vec![(FlagType::BoolFlag(&mut malformed), vec!["--space bar"])];
+      vec![FlagSpec::new(FlagType::StringFlag(&mut output),
+                         vec!["-o", "--output"]).required(true),
+           FlagSpec::new(FlagType::StringFlag(&mut input), vec!["--input"])
+             .env("TOILETCLI_TEST_REQUIRED_INPUT")
+             .required(true),
+           FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["-j"])
+             .required(true),
+           FlagSpec::new(FlagType::BoolFlag(&mut verbose), vec!["-v"]),];
 
-    parse_flags(&mut args_vector.into_iter(), &mut flags).unwrap();
-  }
+    let mut args = ["program", "-v"].iter().map(|x| x.to_string());
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
 
-  #[test]
-  fn parse_flags_string_no_value()
-  {
-    let args_vector = vec!["program".to_string(), "-s".to_string()];
+    let missing = vec!["--output".to_string(), "-j".to_string()];
 
-    let mut s = String::new();
+    assert_eq!(err.flag, "--output");
+    assert_eq!(err.error_type,
+               FlagErrorType::MissingRequired { flags: missing });
+    assert_eq!(err.to_string(), "Missing required flags --output, -j");
 
-    let mut flags = vec![(FlagType::StringFlag(&mut s), vec!["-s"])];
+    let mut args = ["program", "-o", "out.txt"].iter().map(|x| x.to_string());
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+    assert_eq!(err.to_string(), "Missing required flag -j");
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-s");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::NoValueProvided);
+    // Configuration file satisfies required flags too.
+    let config = Config::parse("tool.conf", "output = config.txt").unwrap();
+    let mut options = ParseOptions::new();
+    options.config(&config, "");
+
+    let mut args = ["program", "-j", "2"].iter().map(|x| x.to_string());
+    let (_, sources) =
+      parse_flags_with_sources(&mut args, &mut flags, &options).unwrap();
+
+    assert_eq!(sources[0],
+               Source::Config { path: "tool.conf".to_string(), line: 1 });
+
+    let argv = ["program", "-j", "2", "-o", "out.txt"];
+    let mut args = argv.iter().map(|x| x.to_string());
+    parse_flags(&mut args, &mut flags).unwrap();
+
+    assert_eq!(output, "out.txt");
+    assert_eq!(input, "input.txt");
+    assert_eq!(jobs, 2);
   }
 
-  #[test]
-  fn parse_flags_string_no_value_combine_order()
+  fn parse_groups(argv: &[&str]) -> Result<Vec<String>, FlagError>
   {
-    let args_vector = vec!["program".to_string(), "-bs".to_string()];
-
-    let mut s;
-    let mut b;
+    let mut json;
+    let mut table;
+    let mut file;
+    let mut stdin;
+    let mut key;
+    let mut cert;
 
     let mut flags = flags![
-        s: StringFlag, ["-s"],
-        b: BoolFlag,   ["-b"]
+        json: BoolFlag,    ["-j", "--json"],
+        table: BoolFlag,   ["--table"],
+        file: StringFlag,  ["-f", "--file"],
+        stdin: BoolFlag,   ["--stdin"],
+        key: StringFlag,   ["--key"],
+        cert: StringFlag,  ["--cert"]
     ];
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+    let mut options = ParseOptions::new();
+    options.abbreviations(true)
+           .conflicts(&["--json", "--table"])
+           .exactly_one(&["--file", "--stdin"])
+           .requires("--key", &["--cert"]);
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-s");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::CannotCombine);
+    let mut args = argv.iter().map(|x| x.to_string());
+    parse_flags_with(&mut args, &mut flags, &options)
   }
 
   #[test]
-  fn parse_flags_string_no_value_combine_order_2()
+  fn parse_flags_groups()
   {
-    let args_vector = vec!["program".to_string(), "-sb".to_string()];
+    assert!(parse_groups(&["--json", "-f", "x"]).is_ok());
+    assert!(parse_groups(&["--stdin", "--key", "k", "--cert", "c"]).is_ok());
 
-    let mut s;
-    let mut b;
+    let err = parse_groups(&["-j", "--tab", "--stdin"]).unwrap_err();
+    let flags = vec!["-j".to_string(), "--tab".to_string()];
 
-    let mut flags = flags![
-        s: StringFlag, ["-s"],
-        b: BoolFlag,   ["-b"]
-    ];
+    assert_eq!(err.flag, "-j");
+    assert_eq!(err.error_type, FlagErrorType::Conflicts { flags });
+    assert_eq!(err.to_string(), "Flags -j and --tab can't be used together");
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+    let err = parse_groups(&["--stdin", "-f", "x"]).unwrap_err();
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-s");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::CannotCombine);
+    assert_eq!(err.to_string(), "Flags -f and --stdin can't be used together");
+
+    let err = parse_groups(&["--json"]).unwrap_err();
+    let flags = vec!["--file".to_string(), "--stdin".to_string()];
+
+    assert_eq!(err.error_type, FlagErrorType::MissingOneOf { flags });
+    assert_eq!(err.to_string(), "One of --file or --stdin is required");
+
+    let err = parse_groups(&["--stdin", "--ke=k"]).unwrap_err();
+    let required = vec!["--cert".to_string()];
+
+    assert_eq!(err.flag, "--ke");
+    assert_eq!(err.error_type, FlagErrorType::Requires { required });
+    assert_eq!(err.to_string(), "Flag --ke requires --cert");
   }
 
   #[test]
-  fn parse_flags_string_no_value_combine_order_many_3()
+  fn parse_flags_groups_config()
   {
-    let args_vector = vec!["program".to_string(), "-cbm".to_string()];
+    let mut json = false;
+    let mut table = false;
 
-    let mut s;
-    let mut b;
-    let mut c;
+    let mut flags =
+      vec![FlagSpec::new(FlagType::BoolFlag(&mut json), vec!["--json"]),
+           FlagSpec::new(FlagType::BoolFlag(&mut table), vec!["--table"]),];
 
-    let mut flags = flags![
-        s: ManyFlag, ["-m"],
-        b: BoolFlag, ["-b"],
-        c: BoolFlag, ["-c"]
-    ];
+    let config = Config::parse("tool.conf", "json").unwrap();
+    let mut options = ParseOptions::new();
+    options.exactly_one(&["--json", "--table"]).config(&config, "");
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+    let mut args = std::iter::empty();
+    parse_flags_with(&mut args, &mut flags, &options).unwrap();
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-m");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::CannotCombine);
+    drop(flags);
+
+    assert!(json);
+    assert!(!table);
+
+    json = false;
+    let mut flags =
+      vec![FlagSpec::new(FlagType::BoolFlag(&mut json), vec!["--json"]),
+           FlagSpec::new(FlagType::BoolFlag(&mut table), vec!["--table"]),];
+
+    let mut args = ["--table"].iter().map(|x| x.to_string());
+    parse_flags_with(&mut args, &mut flags, &options).unwrap();
+
+    drop(flags);
+
+    assert!(!json);
+    assert!(table);
   }
 
   #[test]
-  fn parse_flags_string_no_value_combine_order_many_4()
+  #[cfg(debug_assertions)]
+  #[should_panic(expected = "Unknown flag '--tabel' in a flag group.")]
+  fn parse_flags_groups_unknown()
   {
-    let args_vector = vec!["program".to_string(), "-cmb".to_string()];
+    let mut json = false;
+    let mut flags = vec![(FlagType::BoolFlag(&mut json), vec!["--json"])];
 
-    let mut s;
-    let mut b;
-    let mut c;
+    let mut options = ParseOptions::new();
+    options.conflicts(&["--json", "--tabel"]);
 
-    let mut flags = flags![
-        s: ManyFlag, ["-m"],
-        b: BoolFlag, ["-b"],
-        c: BoolFlag, ["-c"]
-    ];
+    let _ = parse_flags_with(&mut std::iter::empty(), &mut flags, &options);
+  }
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+  #[test]
+  fn parse_flags_collect_all_errors()
+  {
+    std::env::set_var("TOILETCLI_TEST_COLLECT_JOBS", "many");
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-m");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::CannotCombine);
+    let mut verbose = false;
+    let mut name = String::new();
+    let mut jobs: u32 = 0;
+    let mut json = false;
+    let mut table = false;
+
+    let mut flags =
+      vec![FlagSpec::new(FlagType::BoolFlag(&mut verbose), vec!["-v"]),
+           FlagSpec::new(FlagType::StringFlag(&mut name), vec!["-n"])
+             .required(true),
+           FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["--jobs"])
+             .env("TOILETCLI_TEST_COLLECT_JOBS"),
+           FlagSpec::new(FlagType::BoolFlag(&mut json), vec!["--json"]),
+           FlagSpec::new(FlagType::BoolFlag(&mut table), vec!["--table"]),];
+
+    let mut options = ParseOptions::new();
+    options.conflicts(&["--json", "--table"]);
+
+    let argv = ["-xv", "a", "--jsn", "--json", "--table", "-v=1", "b", "-n"];
+    let mut args = argv.iter().map(|x| x.to_string());
+    let (args, errors) =
+      parse_flags_collect_errors(&mut args, &mut flags, &options);
+
+    let types = errors.errors
+                      .iter()
+                      .map(|error| &error.error_type)
+                      .collect::<Vec<_>>();
+
+    assert_eq!(args, vec!["a", "b"]);
+    assert_eq!(types.len(), 6);
+    assert_eq!(types[0], &FlagErrorType::Unknown);
+    assert_eq!(errors.to_string(),
+               "Unknown flag -x\n\
+                Unknown flag --jsn, did you mean --json?\n\
+                Flag -v does not take a value\n\
+                No value provided for -n\n\
+                Invalid value 'many' for TOILETCLI_TEST_COLLECT_JOBS: \
+                invalid digit found in string\n\
+                Flags --json and --table can't be used together");
+
+    let mut args = ["-n", "x", "--jobs", "2"].iter().map(|x| x.to_string());
+    let (_, errors) =
+      parse_flags_collect_errors(&mut args, &mut flags, &options);
+
+    assert!(errors.is_empty());
+
+    drop(flags);
+
+    assert!(!verbose);
+    assert!(json);
+    assert_eq!(name, "x");
+    assert_eq!(jobs, 2);
   }
 
   #[test]
-  fn parse_flags_extra_value()
+  fn parse_flags_error_position()
   {
-    let args_vector = vec!["program".to_string(), "-s=test".to_string()];
+    let mut verbose;
+    let mut name;
+    let mut jobs: u32;
+
+    let mut flags = flags!(
+        verbose: BoolFlag, ["-v", "--verbose"],
+        name: StringFlag,  ["-n"],
+        jobs: TypedFlag,   ["--jobs"]
+    );
 
-    let mut s = false;
+    let mut position = |argv: &[&str]| {
+      let mut args = argv.iter().map(|x| x.to_string());
+      let err = parse_flags(&mut args, &mut flags).unwrap_err();
+      let position = err.position.expect("error should have a position");
+      (position.index, position.span)
+    };
 
-    let mut flags = vec![(FlagType::BoolFlag(&mut s), vec!["-s"])];
+    assert_eq!(position(&["a", "-vxq"]), (1, 2..3));
+    assert_eq!(position(&["-nv", "x"]), (0, 1..2));
+    assert_eq!(position(&["--verbose=yes"]), (0, 10..13));
+    assert_eq!(position(&["--jobs", "x"]), (1, 0..1));
+    assert_eq!(position(&["--jobs=x"]), (0, 7..8));
+    assert_eq!(position(&["a", "--verbsoe"]), (1, 0..9));
+    assert_eq!(position(&["a", "-n"]), (1, 1..2));
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+    let argv = ["a b", "--jobs", "ten"];
+    let mut args = argv.iter().map(|x| x.to_string());
+    let err = parse_flags(&mut args, &mut flags).unwrap_err();
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-s");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::ExtraValueProvided);
+    assert_eq!(err.render(&argv, false),
+               "Invalid value 'ten' for --jobs: invalid digit found in \
+                string\n  'a b' --jobs ten\n               ^^^");
+    assert_eq!(err.render(&argv[..1], false), err.to_string());
+
+    let mut flags = vec![FlagSpec::new(FlagType::StringFlag(&mut name),
+                                       vec!["--name"]).required(true)];
+    let err = parse_flags(&mut std::iter::empty(), &mut flags).unwrap_err();
+
+    assert!(err.position.is_none());
   }
 
   #[test]
-  fn parse_flags_extra_value_combine()
+  fn parse_flags_config()
   {
-    let args_vector = vec!["program".to_string(), "-rs=test".to_string()];
+    std::env::set_var("TOILETCLI_TEST_CONFIG_NAME", "env");
 
-    let mut s = false;
-    let mut r = 0;
+    let mut name = String::new();
+    let mut color = String::new();
+    let mut includes: Vec<String> = vec![];
+    let mut verbose = 0;
+    let mut jobs: u32 = 0;
+    let mut cache = true;
+    let mut color_cache = true;
+
+    let contents = "name = config\n\
+                    color = never\n\
+                    include = C:\\src\n\
+                    include = http://a,b\n\
+                    verbose\n\
+                    no-cache\n\
+                    no-color-cache =\n\
+                    [build]\n\
+                    jobs = 4\n";
+    let config = Config::parse("tool.conf", contents).unwrap();
 
-    let mut flags = vec![(FlagType::RepeatFlag(&mut r), vec!["-r"]),
-                         (FlagType::BoolFlag(&mut s), vec!["-s"])];
+    let mut flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut name), vec!["--name"])
+             .env("TOILETCLI_TEST_CONFIG_NAME"),
+           FlagSpec::new(FlagType::StringFlag(&mut color), vec!["--color"]),
+           FlagSpec::new(FlagType::ManyFlag(&mut includes), vec!["--include"]),
+           FlagSpec::new(FlagType::RepeatFlag(&mut verbose), vec!["--verbose"]),
+           FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["--jobs"]),
+           FlagSpec::new(FlagType::NegatableFlag(&mut cache), vec!["--cache"]),
+           FlagSpec::new(FlagType::NegatableFlag(&mut color_cache),
+                         vec!["--color-cache"]),];
+
+    let mut options = ParseOptions::new();
+    options.config(&config, "");
+
+    let mut args = ["--color", "always"].iter().map(|x| x.to_string());
+    parse_flags_with(&mut args, &mut flags, &options).unwrap();
+
+    let mut options = ParseOptions::new();
+    let contents = "jobs = x\ncolr = y\nno-cache = false";
+    options.config(&Config::parse("tool.conf", contents).unwrap(), "");
+
+    let mut args = std::iter::empty();
+    let (_, errors) =
+      parse_flags_collect_errors(&mut args, &mut flags, &options);
+
+    assert_eq!(errors.to_string(),
+               "tool.conf:1: Invalid value 'x' for --jobs: invalid digit \
+                found in string\n\
+                tool.conf:2: Unknown flag --colr, did you mean --color?\n\
+                tool.conf:3: Flag --no-cache does not take a value");
+
+    drop(flags);
+
+    assert_eq!(name, "env");
+    assert_eq!(color, "always");
+    assert_eq!(includes, vec!["C:\\src", "http://a,b"]);
+    assert_eq!(verbose, 1);
+    assert!(!cache);
+    assert!(!color_cache);
+  }
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+  #[test]
+  fn parse_flags_config_layers()
+  {
+    let mut color = String::new();
+    let mut jobs: u32 = 0;
+    let mut verbose = false;
+    let mut includes: Vec<String> = vec![];
+
+    let system = "color = never
+jobs = 2
+include = /usr/src
+";
+    let user = "color = auto
+verbose
+include = ~/src
+include = ~/lib
+";
+    let system = Config::parse("/etc/tool.conf", system).unwrap();
+    let user = Config::parse("tool.conf", user).unwrap();
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-r");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::ExtraValueProvided);
+    let mut flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut color), vec!["--color"]),
+           FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["--jobs"]),
+           FlagSpec::new(FlagType::BoolFlag(&mut verbose), vec!["--verbose"]),
+           FlagSpec::new(FlagType::ManyFlag(&mut includes), vec!["--include"])];
+
+    let mut options = ParseOptions::new();
+    options.config(&system, "").config(&user, "");
+
+    let mut args = std::iter::empty();
+    parse_flags_with(&mut args, &mut flags, &options).unwrap();
+
+    drop(flags);
+
+    assert_eq!(color, "auto");
+    assert_eq!(jobs, 2);
+    assert!(verbose);
+    assert_eq!(includes, vec!["~/src", "~/lib"]);
   }
 
   #[test]
-  fn parse_flags_extra_value_repeat()
+  fn parse_flags_os_strings()
   {
-    let args_vector = vec!["program".to_string(), "-r=test".to_string()];
+    let argv = ["program", "-o", "out.txt", "--name=hello", "argument"];
+    let mut args = argv.iter().map(OsString::from);
 
-    let mut r = 0;
+    let mut output: PathBuf;
+    let mut name;
 
-    let mut flags = vec![(FlagType::RepeatFlag(&mut r), vec!["-r"])];
+    let mut flags = flags![
+        output: PathFlag, ["-o"],
+        name: StringFlag, ["--name"]
+    ];
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+    let parsed_args = parse_flags_os(&mut args, &mut flags).unwrap();
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-r");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::ExtraValueProvided);
+    assert_eq!(parsed_args, vec!["program", "argument"]);
+    assert_eq!(output, PathBuf::from("out.txt"));
+    assert_eq!(name, "hello");
   }
 
+  #[cfg(unix)]
   #[test]
-  fn parse_flags_extra_value_repeat_2()
+  fn parse_flags_os_not_unicode()
   {
-    let args_vector = vec!["program".to_string(), "-rr=test".to_string()];
+    use std::os::unix::ffi::OsStringExt;
 
-    let mut r = 0;
+    let not_unicode = |bytes: &[u8]| OsString::from_vec(bytes.to_vec());
 
-    let mut flags = vec![(FlagType::RepeatFlag(&mut r), vec!["-r"])];
+    let mut output: PathBuf;
+    let mut name;
 
-    let err = parse_flags(&mut args_vector.into_iter(), &mut flags);
+    let mut flags = flags![
+        output: PathFlag, ["-o", "--output"],
+        name: StringFlag, ["-n", "--name"]
+    ];
 
-    assert!(err.is_err());
-    assert!(err.as_ref().unwrap_err().flag == "-r");
-    assert!(err.as_ref().unwrap_err().error_type ==
-            FlagErrorType::ExtraValueProvided);
+    let mut options = ParseOptions::new();
+    options.attached_values(true);
+
+    let argv = [not_unicode(b"file\xff"),
+                not_unicode(b"--output=out\xff"),
+                not_unicode(b"-oout\xfe")];
+    let mut args = argv.into_iter();
+    let parsed_args =
+      parse_flags_os_with(&mut args, &mut flags, &options).unwrap();
+
+    assert_eq!(parsed_args, vec![not_unicode(b"file\xff")]);
+
+    let argv = [OsString::from("-n"), not_unicode(b"name\xff")];
+    let mut args = argv.into_iter();
+    let err = parse_flags_os(&mut args, &mut flags).unwrap_err();
+
+    let value = not_unicode(b"name\xff");
+
+    assert_eq!(err.flag, "-n");
+    assert_eq!(err.error_type, FlagErrorType::InvalidUnicode { value });
+    assert_eq!(err.to_string(),
+               "Invalid value 'name\u{FFFD}' for -n: not valid unicode");
+
+    let mut args = [not_unicode(b"--out\xff")].into_iter();
+    let err = parse_flags_os(&mut args, &mut flags).unwrap_err();
+
+    assert_eq!(err.flag, "--out\u{FFFD}");
+    assert_eq!(err.error_type, FlagErrorType::Unknown);
+
+    assert_eq!(output, PathBuf::from(not_unicode(b"out\xfe")));
   }
 }
@@ -0,0 +1,588 @@
+//! Tree of subcommands with their own flags.
+
+use std::any::{type_name, Any};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::Path;
+
+use super::{check_subcommand, into_string,
+            parse_flags_until_subcommand_os_with, parse_flags_os_with,
+            ArgPosition, FlagEntry, FlagError, FlagErrorType, FlagSpec,
+            FlagType, ParseOptions};
+
+/// Function of a command that is called with [`Command::run`]. It gets the
+/// resolved command and values of flags of its own command.
+pub type Handler<'a, A = String> =
+  Box<dyn FnMut(&Dispatch<A>, &CommandFlags<'_, 'a>) + 'a>;
+
+/// A command with its own flags, handler and subcommands. Commands are parsed
+/// level by level: flags of a command come before its subcommand, and
+/// arguments are collected only by commands without subcommands.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags;
+/// use toiletcli::flags::{Command, FlagType};
+///
+/// let mut verbose;
+/// let mut name;
+/// let mut added = vec![];
+///
+/// let tool_flags = flags!(verbose: BoolFlag, ["-v"]);
+/// let add_flags = flags!(name: StringFlag, ["--name"]);
+///
+/// let add = Command::new("add").flags(add_flags).handler(|add, flags| {
+///   added.push(format!("{} {}", flags.get_str("--name"), add.arguments[0]));
+/// });
+/// let remote = Command::new("remote").subcommand(add);
+/// let mut tool = Command::new("tool").flags(tool_flags).subcommand(remote);
+///
+/// let argv = ["-v", "remote", "add", "--name", "x", "url"];
+/// let mut args = argv.iter().map(|x| x.to_string());
+///
+/// let dispatch = tool.parse(&mut args).unwrap();
+///
+/// assert_eq!(dispatch.path, vec!["tool", "remote", "add"]);
+/// assert_eq!(dispatch.arguments, vec!["url"]);
+/// assert!(tool.run(&dispatch));
+///
+/// drop(tool);
+///
+/// assert!(verbose);
+/// assert_eq!(name, "x");
+/// assert_eq!(added, vec!["x url"]);
+/// ```
+pub struct Command<'a, A = String>
+{
+  name: &'a str,
+  flags: Vec<FlagSpec<'a>>,
+  options: ParseOptions,
+  handler: Option<Handler<'a, A>>,
+  subcommands: Vec<Command<'a, A>>,
+  subcommand_required: bool,
+}
+
+impl<'a, A> Command<'a, A>
+{
+  /// `name` is how the command is typed. For the root command, it's the name
+  /// of the program, and it's not expected to be in arguments.
+  pub fn new(name: &'a str) -> Self
+  {
+    Self { name,
+           flags: vec![],
+           options: ParseOptions::default(),
+           handler: None,
+           subcommands: vec![],
+           subcommand_required: false }
+  }
+
+  /// Flags of this command.
+  pub fn flags<F>(mut self, flags: Vec<F>) -> Self
+    where F: Into<FlagSpec<'a>>
+  {
+    self.flags = flags.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Options to parse flags of this command with.
+  pub fn options(mut self, options: ParseOptions) -> Self
+  {
+    self.options = options;
+    self
+  }
+
+  /// Function to call when this command is the resolved one.
+  pub fn handler<H>(mut self, handler: H) -> Self
+    where H: FnMut(&Dispatch<A>, &CommandFlags<'_, 'a>) + 'a
+  {
+    self.handler = Some(Box::new(handler));
+    self
+  }
+
+  /// Add a subcommand. Can be used multiple times.
+  pub fn subcommand(mut self, subcommand: Command<'a, A>) -> Self
+  {
+    self.subcommands.push(subcommand);
+    self
+  }
+
+  /// Whether one of subcommands has to be present, otherwise parsing fails
+  /// with a
+  /// [`MissingSubcommand`](type@super::FlagErrorType::MissingSubcommand)
+  /// error. Only matters for commands with subcommands.
+  pub fn subcommand_required(mut self, subcommand_required: bool) -> Self
+  {
+    self.subcommand_required = subcommand_required;
+    self
+  }
+
+  // Parses arguments the same way for `parse` and `parse_os`.
+  fn parse_args<Args>(&mut self,
+                      args: &mut Args)
+                      -> Result<Dispatch<OsString>, FlagError>
+    where Args: Iterator<Item = OsString>
+  {
+    let mut dispatch = Dispatch { path: vec![], arguments: vec![] };
+    let mut command = self;
+    // Arguments consumed by previous commands, so errors point to the right
+    // argument.
+    let mut consumed = 0;
+
+    loop {
+      dispatch.path.push(command.name.to_string());
+
+      let offset = consumed;
+      let mut args = args.by_ref().inspect(|_| consumed += 1);
+
+      if command.subcommands.is_empty() {
+        dispatch.arguments =
+          parse_flags_os_with(&mut args, &mut command.flags, &command.options)
+            .map_err(|error| error.after(offset))?;
+        return Ok(dispatch);
+      }
+
+      let subcommand =
+        parse_flags_until_subcommand_os_with(&mut args,
+                                             &mut command.flags,
+                                             &command.options)
+          .map_err(|error| error.after(offset))?;
+
+      let names = command.subcommands
+                         .iter()
+                         .map(|command| command.name)
+                         .collect::<Vec<_>>();
+
+      // Everything after `--` is an argument, so there is no subcommand.
+      if subcommand == "--" {
+        dispatch.arguments = args.collect();
+      }
+      if subcommand.is_empty() || subcommand == "--" {
+        if command.subcommand_required {
+          let subcommands = names.iter().map(|name| name.to_string()).collect();
+          let error_type = FlagErrorType::MissingSubcommand { subcommands };
+          return Err(FlagError::new(error_type, String::new()));
+        }
+        return Ok(dispatch);
+      }
+
+      // Names that are not valid unicode are never known subcommands.
+      check_subcommand(&subcommand.to_string_lossy(), &names).map_err(|error| {
+        let span = 0..subcommand.len();
+        error.at(ArgPosition { index: consumed - 1, span })
+      })?;
+
+      command = command.subcommands
+                       .iter_mut()
+                       .find(|command| subcommand == command.name)
+                       .expect("unreachable");
+    }
+  }
+
+  /// Calls the handler of the command that was resolved by
+  /// [`parse`](fn@Command::parse) with flags of that command. Returns
+  /// `false` if it does not have one.
+  pub fn run(&mut self, dispatch: &Dispatch<A>) -> bool
+  {
+    let mut command = self;
+    for name in dispatch.path.iter().skip(1) {
+      match command.subcommands
+                   .iter_mut()
+                   .find(|command| command.name == name)
+      {
+        Some(subcommand) => command = subcommand,
+        None => return false,
+      }
+    }
+
+    match &mut command.handler {
+      Some(handler) => {
+        handler(dispatch, &CommandFlags { flags: &command.flags });
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+impl Command<'_>
+{
+  /// Consumes arguments, setting flags of every command on the way, until
+  /// the command without subcommands or the end of arguments.
+  ///
+  /// When a command with subcommands gets no subcommand, it becomes the
+  /// resolved command, unless
+  /// [`subcommand_required`](fn@Command::subcommand_required) is set. `--`
+  /// in place of a subcommand means there is none, and arguments after it
+  /// are not parsed.
+  ///
+  /// # Returns
+  /// ## Ok
+  /// Names of resolved commands and the remaining arguments. The last name is
+  /// the command that was resolved, so it can be a command with subcommands.
+  /// Its handler can be called with [`run`](fn@Command::run).
+  ///
+  /// ## Err
+  /// Any error of [`parse_flags`](fn@super::parse_flags), an unknown
+  /// subcommand, or a missing subcommand when it's required.
+  pub fn parse<Args>(&mut self, args: &mut Args) -> Result<Dispatch, FlagError>
+    where Args: Iterator<Item = String>
+  {
+    let mut args = args.by_ref().map(OsString::from);
+    let dispatch = self.parse_args(&mut args)?;
+    let arguments = dispatch.arguments.into_iter().map(into_string).collect();
+    Ok(Dispatch { path: dispatch.path, arguments })
+  }
+}
+
+impl Command<'_, OsString>
+{
+  /// Works the same way as [`parse`](fn@Command::parse), but takes
+  /// [`Iterator<OsString>`](type@Iterator<OsString>), see
+  /// [`parse_flags_os`](fn@super::parse_flags_os).
+  ///
+  /// # Example
+  /// ```rust
+  /// use std::ffi::OsString;
+  /// use toiletcli::flags::Command;
+  ///
+  /// let mut tool = Command::new("tool").subcommand(Command::new("add"));
+  ///
+  /// let mut args = ["add", "file.txt"].iter().map(OsString::from);
+  /// let dispatch = tool.parse_os(&mut args).unwrap();
+  ///
+  /// assert_eq!(dispatch.path, vec!["tool", "add"]);
+  /// assert_eq!(dispatch.arguments, vec![OsString::from("file.txt")]);
+  /// ```
+  pub fn parse_os<Args>(&mut self,
+                        args: &mut Args)
+                        -> Result<Dispatch<OsString>, FlagError>
+    where Args: Iterator<Item = OsString>
+  {
+    self.parse_args(args)
+  }
+}
+
+impl<A> fmt::Debug for Command<'_, A>
+{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+  {
+    f.debug_struct("Command")
+     .field("name", &self.name)
+     .field("flags", &self.flags)
+     .field("options", &self.options)
+     .field("handler", &self.handler.is_some())
+     .field("subcommands", &self.subcommands)
+     .field("subcommand_required", &self.subcommand_required)
+     .finish()
+  }
+}
+
+/// Command that was resolved by [`Command::parse`]. Arguments are
+/// [`String`](struct@String), or [`OsString`](struct@OsString) when parsed
+/// with [`Command::parse_os`].
+#[derive(Debug, Clone)]
+pub struct Dispatch<A = String>
+{
+  /// Names of commands from the root one, like `["tool", "remote", "add"]`.
+  pub path: Vec<String>,
+  /// Arguments that are not flags.
+  pub arguments: Vec<A>,
+}
+
+/// Values of flags of a command, which are passed to its
+/// [`Handler`](type@Handler), queried by any alias of the flag.
+///
+/// # Panics
+/// Getters panic when there is no flag with that alias, or when flag is of the
+/// wrong type.
+#[derive(Debug)]
+pub struct CommandFlags<'f, 'a>
+{
+  flags: &'f [FlagSpec<'a>],
+}
+
+impl<'a> CommandFlags<'_, 'a>
+{
+  fn flag_type(&self, alias: &str) -> &FlagType<'a>
+  {
+    self.flags
+        .iter()
+        .find(|flag| flag.aliases().contains(&alias))
+        .map(|flag| flag.flag_type())
+        .unwrap_or_else(|| panic!("There is no flag with alias '{}'.", alias))
+  }
+
+  /// Value of [`BoolFlag`](type@FlagType::BoolFlag) or
+  /// [`NegatableFlag`](type@FlagType::NegatableFlag).
+  pub fn get_bool(&self, alias: &str) -> bool
+  {
+    match self.flag_type(alias) {
+      FlagType::BoolFlag(value) | FlagType::NegatableFlag(value) => **value,
+      _ => panic!("Flag '{}' is not a boolean.", alias),
+    }
+  }
+
+  /// Value of [`StringFlag`](type@FlagType::StringFlag) or
+  /// [`OptionalFlag`](type@FlagType::OptionalFlag).
+  pub fn get_str(&self, alias: &str) -> &str
+  {
+    match self.flag_type(alias) {
+      FlagType::StringFlag(value) | FlagType::OptionalFlag(value, _) => value,
+      _ => panic!("Flag '{}' is not a string.", alias),
+    }
+  }
+
+  /// Value of [`PathFlag`](type@FlagType::PathFlag).
+  pub fn get_path(&self, alias: &str) -> &Path
+  {
+    match self.flag_type(alias) {
+      FlagType::PathFlag(path) => path,
+      _ => panic!("Flag '{}' is not a path.", alias),
+    }
+  }
+
+  /// Value of [`TypedFlag`](type@FlagType::TypedFlag).
+  ///
+  /// # Panics
+  /// Also panics when `T` is not the type of the variable of the flag.
+  pub fn get<T>(&self, alias: &str) -> &T
+    where T: Any
+  {
+    let value = match self.flag_type(alias) {
+      FlagType::TypedFlag(value) => value.as_any(),
+      _ => panic!("Flag '{}' is not typed.", alias),
+    };
+    value.downcast_ref().unwrap_or_else(|| {
+                          panic!("Flag '{}' is not a {}.",
+                                 alias,
+                                 type_name::<T>())
+                        })
+  }
+
+  /// Values of [`ManyFlag`](type@FlagType::ManyFlag).
+  pub fn get_many(&self, alias: &str) -> &[String]
+  {
+    match self.flag_type(alias) {
+      FlagType::ManyFlag(values) => values,
+      _ => panic!("Flag '{}' does not take many values.", alias),
+    }
+  }
+
+  /// Values of [`TypedManyFlag`](type@FlagType::TypedManyFlag) or
+  /// [`ListFlag`](type@FlagType::ListFlag), see
+  /// [`get`](fn@CommandFlags::get).
+  pub fn get_all<T>(&self, alias: &str) -> &[T]
+    where T: Any
+  {
+    let values = match self.flag_type(alias) {
+      FlagType::TypedManyFlag(values) | FlagType::ListFlag(values, _) => {
+        values.as_any()
+      }
+      _ => panic!("Flag '{}' does not take many typed values.", alias),
+    };
+    values.downcast_ref::<Vec<T>>().unwrap_or_else(|| {
+                                     panic!("Flag '{}' is not a list of {}.",
+                                            alias,
+                                            type_name::<T>())
+                                   })
+  }
+
+  /// Values of [`MapFlag`](type@FlagType::MapFlag).
+  pub fn get_map(&self, alias: &str) -> &HashMap<String, String>
+  {
+    match self.flag_type(alias) {
+      FlagType::MapFlag(map, _) => map,
+      _ => panic!("Flag '{}' is not a map.", alias),
+    }
+  }
+
+  /// Count of [`RepeatFlag`](type@FlagType::RepeatFlag), or the number of
+  /// values of [`ManyFlag`](type@FlagType::ManyFlag) or
+  /// [`MapFlag`](type@FlagType::MapFlag).
+  pub fn count(&self, alias: &str) -> usize
+  {
+    match self.flag_type(alias) {
+      FlagType::RepeatFlag(count) => **count,
+      FlagType::ManyFlag(values) => values.len(),
+      FlagType::MapFlag(map, _) => map.len(),
+      _ => panic!("Flag '{}' can't be counted.", alias),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use std::path::PathBuf;
+
+  use super::*;
+  use crate::flags;
+  use crate::flags::*;
+
+  fn parse(argv: &[&str]) -> Result<(Dispatch, bool, String), FlagError>
+  {
+    let mut verbose;
+    let mut name;
+    let mut force;
+
+    let tool_flags = flags!(verbose: BoolFlag, ["-v"]);
+    let add_flags = flags!(name: StringFlag, ["--name"]);
+    let remove_flags = flags!(force: BoolFlag, ["-f"]);
+
+    let add = Command::new("add").flags(add_flags);
+    let remove = Command::new("remove").flags(remove_flags);
+    let remote = Command::new("remote").subcommand(add).subcommand(remove);
+    let mut tool = Command::new("tool").flags(tool_flags).subcommand(remote);
+
+    let mut args = argv.iter().map(|x| x.to_string());
+    let dispatch = tool.parse(&mut args)?;
+    drop(tool);
+
+    Ok((dispatch, verbose, name))
+  }
+
+  #[test]
+  fn command_dispatch()
+  {
+    let (dispatch, verbose, name) =
+      parse(&["-v", "remote", "add", "--name", "x", "url", "-"]).unwrap();
+
+    assert_eq!(dispatch.path, vec!["tool", "remote", "add"]);
+    assert_eq!(dispatch.arguments, vec!["url", "-"]);
+    assert!(verbose);
+    assert_eq!(name, "x");
+
+    let (dispatch, verbose, _) = parse(&["remote"]).unwrap();
+
+    assert_eq!(dispatch.path, vec!["tool", "remote"]);
+    assert!(dispatch.arguments.is_empty());
+    assert!(!verbose);
+  }
+
+  #[test]
+  fn command_unknown_subcommand()
+  {
+    let err = parse(&["remote", "list"]).unwrap_err();
+    let subcommands = vec!["add".to_string(), "remove".to_string()];
+
+    assert_eq!(err.flag, "list");
+    assert_eq!(err.error_type,
+               FlagErrorType::UnknownSubcommand { subcommands });
+    assert_eq!(err.to_string(),
+               "Unknown subcommand list, expected one of add, remove");
+
+    let err = parse(&["remote", "remvoe"]).unwrap_err();
+
+    assert_eq!(err.to_string(),
+               "Unknown subcommand remvoe, did you mean remove?");
+    assert_eq!(err.position.as_deref(),
+               Some(&ArgPosition { index: 1, span: 0..6 }));
+
+    let err = parse(&["-v", "remote", "add", "x", "-f"]).unwrap_err();
+
+    assert_eq!(err.to_string(), "Unknown flag -f");
+    assert_eq!(err.position.as_deref(),
+               Some(&ArgPosition { index: 4, span: 1..2 }));
+  }
+
+  #[test]
+  fn command_missing_subcommand()
+  {
+    let (dispatch, ..) = parse(&["remote", "--", "add", "-v"]).unwrap();
+
+    assert_eq!(dispatch.path, vec!["tool", "remote"]);
+    assert_eq!(dispatch.arguments, vec!["add", "-v"]);
+
+    let mut tool = Command::new("tool").subcommand(Command::new("add"))
+                                       .subcommand(Command::new("list"))
+                                       .subcommand_required(true);
+
+    for argv in [&[][..], &["--"][..]] {
+      let mut args = argv.iter().map(|x| x.to_string());
+      let err = tool.parse(&mut args).unwrap_err();
+      let subcommands = vec!["add".to_string(), "list".to_string()];
+
+      assert_eq!(err.error_type,
+                 FlagErrorType::MissingSubcommand { subcommands });
+      assert_eq!(err.to_string(),
+                 "Missing subcommand, expected one of add, list");
+    }
+  }
+
+  #[test]
+  fn command_handler()
+  {
+    let mut name = String::new();
+    let mut seen = vec![];
+
+    let add_flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut name), vec!["--name"])];
+    let add = Command::new("add").flags(add_flags).handler(|add, flags| {
+      seen.push(format!("{} {}", add.path.join(" "), flags.get_str("--name")));
+    });
+    let mut tool = Command::new("tool").subcommand(add);
+
+    let mut args = ["add", "--name", "x"].iter().map(|x| x.to_string());
+    let dispatch = tool.parse(&mut args).unwrap();
+
+    assert!(tool.run(&dispatch));
+
+    let mut args = std::iter::empty();
+    let dispatch = tool.parse(&mut args).unwrap();
+
+    assert!(!tool.run(&dispatch));
+    drop(tool);
+
+    assert_eq!(seen, vec!["tool add x"]);
+    assert_eq!(name, "x");
+  }
+
+  #[test]
+  fn command_flags()
+  {
+    let mut verbose = 0;
+    let mut jobs: u32 = 0;
+    let mut ports: Vec<u16> = vec![];
+    let mut output = PathBuf::new();
+    let mut seen = vec![];
+
+    let flags =
+      vec![FlagSpec::new(FlagType::RepeatFlag(&mut verbose), vec!["-v"]),
+           FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["-j"]),
+           FlagSpec::new(FlagType::TypedManyFlag(&mut ports), vec!["-p"]),
+           FlagSpec::new(FlagType::PathFlag(&mut output), vec!["-o"])];
+    let mut tool = Command::new("tool").flags(flags).handler(|tool, flags| {
+      seen.push(format!("{} {} {:?} {:?} {:?}",
+                        tool.path[0],
+                        flags.count("-v"),
+                        flags.get::<u32>("-j"),
+                        flags.get_all::<u16>("-p"),
+                        flags.get_path("-o")));
+    });
+
+    let argv = ["-vv", "-j", "4", "-p", "80", "-p", "443", "-o", "out"];
+    let mut args = argv.iter().map(OsString::from);
+    let dispatch = tool.parse_os(&mut args).unwrap();
+
+    assert!(tool.run(&dispatch));
+    drop(tool);
+
+    assert_eq!(seen, vec!["tool 2 4 [80, 443] \"out\""]);
+  }
+
+  #[test]
+  #[should_panic(expected = "Flag '-j' is not a u64.")]
+  fn command_flags_wrong_type()
+  {
+    let mut jobs: u32 = 0;
+
+    let flags = vec![FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["-j"])];
+    let mut tool = Command::new("tool").flags(flags).handler(|_, flags| {
+      flags.get::<u64>("-j");
+    });
+
+    let dispatch = tool.parse(&mut std::iter::empty()).unwrap();
+    tool.run(&dispatch);
+  }
+}
@@ -0,0 +1,584 @@
+//! Shell completion scripts from the flag table.
+
+use std::fmt::{self, Display};
+use std::io::Error;
+use std::str::FromStr;
+
+use super::{long_names, FlagEntry, FlagType};
+
+/// Kind of values a flag takes, used to complete them.
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValueHint<'a>
+{
+  /// Anything, nothing will be completed.
+  #[default]
+  Any,
+  /// Path to a file.
+  File,
+  /// Path to a directory.
+  Directory,
+  /// One of the fixed values.
+  Choices(&'a [&'a str]),
+}
+
+/// Shells that can use completion scripts.
+///
+/// Can be parsed from string with [`from_str`](trait@FromStr), so it can be
+/// used with [`TypedFlag`](type@FlagType::TypedFlag).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Shell
+{
+  Bash,
+  Zsh,
+  Fish,
+}
+
+impl Display for Shell
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+  {
+    match self {
+      Shell::Bash => write!(f, "bash"),
+      Shell::Zsh => write!(f, "zsh"),
+      Shell::Fish => write!(f, "fish"),
+    }
+  }
+}
+
+impl FromStr for Shell
+{
+  type Err = Error;
+
+  fn from_str(string: &str) -> Result<Self, Self::Err>
+  {
+    match string.to_lowercase().as_str() {
+      "bash" => Ok(Shell::Bash),
+      "zsh" => Ok(Shell::Zsh),
+      "fish" => Ok(Shell::Fish),
+      _ => Err(Error::other(format!("Unknown shell '{}'", string))),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+enum Hint
+{
+  Any,
+  File,
+  Directory,
+  Choices(Vec<String>),
+}
+
+// Flag information that is needed for completion, copied out of the flag
+// table.
+#[derive(Debug, Clone)]
+struct CompletionFlag
+{
+  shorts: Vec<String>,
+  longs: Vec<String>,
+  description: String,
+  takes_value: bool,
+  repeats: bool,
+  hint: Hint,
+}
+
+impl CompletionFlag
+{
+  fn aliases(&self) -> impl Iterator<Item = &String>
+  {
+    self.shorts.iter().chain(self.longs.iter())
+  }
+}
+
+// Value hint of a flag, where paths are files unless said otherwise.
+pub(super) fn flag_value_hint<'f, 'a, F>(flag: &'f F) -> ValueHint<'f>
+  where F: FlagEntry<'a>
+{
+  match flag.value_hint() {
+    ValueHint::Any if matches!(flag.flag_type(), FlagType::PathFlag(_)) => {
+      ValueHint::File
+    }
+    value_hint => value_hint,
+  }
+}
+
+fn completion_flags<'a, F>(flags: &[F]) -> Vec<CompletionFlag>
+  where F: FlagEntry<'a>
+{
+  flags.iter()
+       .map(|flag| {
+         let aliases = flag.aliases();
+         let flag_type = flag.flag_type();
+         let hint = match flag_value_hint(flag) {
+           ValueHint::Any => Hint::Any,
+           ValueHint::File => Hint::File,
+           ValueHint::Directory => Hint::Directory,
+           ValueHint::Choices(choices) => {
+             Hint::Choices(choices.iter().map(|c| c.to_string()).collect())
+           }
+         };
+         CompletionFlag { shorts: aliases.iter()
+                                         .filter(|a| a.len() == 2)
+                                         .map(|a| a.to_string())
+                                         .collect(),
+                          longs: long_names(aliases,
+                                            flag_type.is_negatable()),
+                          description: flag.description().to_string(),
+                          takes_value: flag_type.takes_value(),
+                          repeats: flag_type.takes_many() ||
+                                   matches!(flag_type,
+                                            FlagType::RepeatFlag(_)),
+                          hint }
+       })
+       .collect()
+}
+
+/// Generates completion scripts for bash, zsh and fish from the flag table.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{Completion, FlagSpec, FlagType, Shell, ValueHint};
+///
+/// let mut color = String::new();
+/// let mut file = String::new();
+///
+/// let main_flags = vec![
+///     FlagSpec::new(FlagType::StringFlag(&mut color), vec!["-c", "--color"])
+///       .value_hint(ValueHint::Choices(&["always", "never"])),
+/// ];
+/// let add_flags = vec![
+///     FlagSpec::new(FlagType::StringFlag(&mut file), vec!["--file"])
+///       .value_hint(ValueHint::File),
+/// ];
+///
+/// let script = Completion::new("tool").flags(&main_flags)
+///                                     .subcommand("add", &add_flags)
+///                                     .render(Shell::Bash);
+///
+/// assert!(script.contains("complete -F _tool tool"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Completion<'c>
+{
+  name: &'c str,
+  flags: Vec<CompletionFlag>,
+  subcommands: Vec<(&'c str, Vec<CompletionFlag>)>,
+}
+
+impl<'c> Completion<'c>
+{
+  /// `name` is the name of the program to complete.
+  pub fn new(name: &'c str) -> Self
+  {
+    Self { name, flags: vec![], subcommands: vec![] }
+  }
+
+  /// Flags of the program itself, or flags that come before a subcommand.
+  pub fn flags<'a, F>(&mut self, flags: &[F]) -> &mut Self
+    where F: FlagEntry<'a>
+  {
+    self.flags = completion_flags(flags);
+    self
+  }
+
+  /// Add a subcommand with its flags. Can be used multiple times.
+  pub fn subcommand<'a, F>(&mut self, name: &'c str, flags: &[F]) -> &mut Self
+    where F: FlagEntry<'a>
+  {
+    self.subcommands.push((name, completion_flags(flags)));
+    self
+  }
+
+  /// Returns completion script for the shell.
+  pub fn render(&self, shell: Shell) -> String
+  {
+    match shell {
+      Shell::Bash => self.bash(),
+      Shell::Zsh => self.zsh(),
+      Shell::Fish => self.fish(),
+    }
+  }
+
+  fn function_name(&self) -> String
+  {
+    let name = self.name
+                   .chars()
+                   .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+                   .collect::<String>();
+    format!("_{}", name)
+  }
+
+  fn subcommand_names(&self) -> Vec<&str>
+  {
+    self.subcommands.iter().map(|(name, _)| *name).collect()
+  }
+
+  fn bash(&self) -> String
+  {
+    // Quotes a word for the word list of `compgen -W`, which is itself in
+    // double quotes.
+    fn bash_word(word: &str) -> String
+    {
+      format!("'{}'", word.replace('\'', "'\\''")).replace('\\', "\\\\")
+                                                .replace('"', "\\\"")
+                                                .replace('$', "\\$")
+                                                .replace('`', "\\`")
+    }
+
+    fn bash_case(flags: &[CompletionFlag], indent: &str) -> String
+    {
+      let mut case = String::new();
+
+      case += &format!("{}case \"$prev\" in\n", indent);
+      for flag in flags.iter().filter(|flag| flag.takes_value) {
+        let aliases = flag.aliases().cloned().collect::<Vec<_>>().join("|");
+        let action = match &flag.hint {
+          Hint::Any => "COMPREPLY=()".to_string(),
+          Hint::File => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+          Hint::Directory => {
+            "COMPREPLY=($(compgen -d -- \"$cur\"))".to_string()
+          }
+          // Matches are read one per line, so choices can have spaces.
+          Hint::Choices(choices) => {
+            let words =
+              choices.iter().map(|c| bash_word(c)).collect::<Vec<_>>();
+            format!("mapfile -t COMPREPLY < <(compgen -W \"{}\" -- \
+                     \"$cur\")",
+                    words.join(" "))
+          }
+        };
+        case += &format!("{}  {}) {}; return ;;\n", indent, aliases, action);
+      }
+      case += &format!("{}esac\n", indent);
+
+      let opts = flags.iter()
+                      .flat_map(|flag| flag.aliases().cloned())
+                      .collect::<Vec<_>>()
+                      .join(" ");
+      case += &format!("{}opts=\"{}\"\n", indent, opts);
+
+      case
+    }
+
+    let function = self.function_name();
+    let subcommands = self.subcommand_names();
+
+    let mut script = String::new();
+
+    script += &format!("{}()\n{{\n", function);
+    script += "  local cur prev cmd opts i\n";
+    script += "  cur=\"${COMP_WORDS[COMP_CWORD]}\"\n";
+    script += "  prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n";
+    script += "  cmd=\"\"\n";
+
+    if !subcommands.is_empty() {
+      // Values of flags before the subcommand should not be mistaken for it.
+      let value_flags = self.flags
+                            .iter()
+                            .filter(|flag| flag.takes_value)
+                            .flat_map(|flag| flag.aliases().cloned())
+                            .collect::<Vec<_>>();
+
+      script += "  for ((i = 1; i < COMP_CWORD; i++)); do\n";
+      script += "    case \"${COMP_WORDS[i]}\" in\n";
+      if !value_flags.is_empty() {
+        script += &format!("      {}) ((i++)) ;;\n", value_flags.join("|"));
+      }
+      script += &format!("      {}) cmd=\"${{COMP_WORDS[i]}}\"; break ;;\n",
+                         subcommands.join("|"));
+      script += "    esac\n";
+      script += "  done\n";
+    }
+
+    script += "  case \"$cmd\" in\n";
+    for (name, flags) in &self.subcommands {
+      script += &format!("    {})\n", name);
+      script += &bash_case(flags, "      ");
+      script += "      ;;\n";
+    }
+    script += "    *)\n";
+    script += &bash_case(&self.flags, "      ");
+    script += "      ;;\n";
+    script += "  esac\n";
+
+    script += "  if [[ \"$cur\" == -* ]]; then\n";
+    script += "    COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))\n";
+    if !subcommands.is_empty() {
+      script += "  elif [[ -z \"$cmd\" ]]; then\n";
+      script += &format!("    COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n",
+                         subcommands.join(" "));
+    }
+    script += "  else\n";
+    script += "    COMPREPLY=($(compgen -f -- \"$cur\"))\n";
+    script += "  fi\n";
+    script += "}\n\n";
+
+    script += &format!("complete -F {} {}\n", function, self.name);
+
+    script
+  }
+
+  fn zsh(&self) -> String
+  {
+    fn zsh_escape(text: &str) -> String
+    {
+      text.replace('\'', "'\\''")
+          .replace('[', "\\[")
+          .replace(']', "\\]")
+          .replace(':', "\\:")
+    }
+
+    // Escapes a choice for the list of matches, which is split into words
+    // like in the shell.
+    fn zsh_choice(choice: &str) -> String
+    {
+      zsh_escape(&escape_word(choice, "\\ $`()"))
+    }
+
+    fn zsh_arguments(flags: &[CompletionFlag], indent: &str) -> String
+    {
+      let mut arguments = String::new();
+
+      for flag in flags {
+        let aliases = flag.aliases().cloned().collect::<Vec<_>>();
+        let names = if aliases.len() == 1 {
+          aliases[0].clone()
+        } else {
+          format!("{{{}}}", aliases.join(","))
+        };
+        let exclusion = if flag.repeats {
+          "'*'".to_string()
+        } else if aliases.len() > 1 {
+          format!("'({})'", aliases.join(" "))
+        } else {
+          "".to_string()
+        };
+        let value = if flag.takes_value {
+          let action = match &flag.hint {
+            Hint::Any => " ".to_string(),
+            Hint::File => "_files".to_string(),
+            Hint::Directory => "_files -/".to_string(),
+            Hint::Choices(choices) => {
+              let choices =
+                choices.iter().map(|c| zsh_choice(c)).collect::<Vec<_>>();
+              format!("({})", choices.join(" "))
+            }
+          };
+          format!(":value:{}", action)
+        } else {
+          "".to_string()
+        };
+        let mut spec = String::new();
+        if !flag.description.is_empty() {
+          spec += &format!("[{}]", zsh_escape(&flag.description));
+        }
+        spec += &value;
+        if !spec.is_empty() {
+          spec = format!("'{}'", spec);
+        }
+        arguments +=
+          &format!("{}{}{}{} \\\n", indent, exclusion, names, spec);
+      }
+
+      arguments
+    }
+
+    let function = self.function_name();
+    let subcommands = self.subcommand_names();
+
+    let mut script = String::new();
+
+    script += &format!("#compdef {}\n\n", self.name);
+    script += &format!("{}()\n{{\n", function);
+    script += "  local context state state_descr line\n";
+    script += "  typeset -A opt_args\n\n";
+    script += "  _arguments -C \\\n";
+    script += &zsh_arguments(&self.flags, "    ");
+
+    if subcommands.is_empty() {
+      script += "    '*: :_files'\n";
+    } else {
+      script += &format!("    '1: :({})' \\\n", subcommands.join(" "));
+      script += "    '*:: :->args'\n\n";
+      script += "  case $state in\n";
+      script += "    args)\n";
+      script += "      case $words[1] in\n";
+      for (name, flags) in &self.subcommands {
+        script += &format!("        {})\n", name);
+        script += "          _arguments \\\n";
+        script += &zsh_arguments(flags, "            ");
+        script += "            '*: :_files'\n";
+        script += "          ;;\n";
+      }
+      script += "      esac\n";
+      script += "      ;;\n";
+      script += "  esac\n";
+    }
+
+    script += "}\n\n";
+    script += &format!("if [ \"$funcstack[1]\" = \"{}\" ]; then\n", function);
+    script += &format!("  {} \"$@\"\n", function);
+    script += "else\n";
+    script += &format!("  compdef {} {}\n", function, self.name);
+    script += "fi\n";
+
+    script
+  }
+
+  fn fish(&self) -> String
+  {
+    fn fish_escape(text: &str) -> String
+    {
+      text.replace('\\', "\\\\")
+          .replace('"', "\\\"")
+          .replace('$', "\\$")
+    }
+
+    // Escapes a choice for `-a`, which is split into words like in the shell.
+    fn fish_choice(choice: &str) -> String
+    {
+      escape_word(choice, "\\'\" $")
+    }
+
+    fn fish_complete(name: &str,
+                     condition: &str,
+                     flags: &[CompletionFlag])
+                     -> String
+    {
+      let mut complete = String::new();
+
+      for flag in flags {
+        let mut line = format!("complete -c {}", name);
+        if !condition.is_empty() {
+          line += &format!(" -n \"{}\"", condition);
+        }
+        for short in &flag.shorts {
+          line += &format!(" -s {}", &short[1..]);
+        }
+        for long in &flag.longs {
+          line += &format!(" -l {}", &long[2..]);
+        }
+        if flag.takes_value {
+          line += &match &flag.hint {
+            Hint::Any => " -x".to_string(),
+            Hint::File => " -r -F".to_string(),
+            Hint::Directory => {
+              " -x -a \"(__fish_complete_directories)\"".to_string()
+            }
+            Hint::Choices(choices) => {
+              let choices =
+                choices.iter().map(|c| fish_choice(c)).collect::<Vec<_>>();
+              format!(" -x -a \"{}\"", fish_escape(&choices.join(" ")))
+            }
+          };
+        }
+        if !flag.description.is_empty() {
+          line += &format!(" -d \"{}\"", fish_escape(&flag.description));
+        }
+        complete += &line;
+        complete.push('\n');
+      }
+
+      complete
+    }
+
+    let subcommands = self.subcommand_names();
+
+    let mut script = String::new();
+
+    if subcommands.is_empty() {
+      script += &fish_complete(self.name, "", &self.flags);
+    } else {
+      script += &fish_complete(self.name, "__fish_use_subcommand", &self.flags);
+      for name in &subcommands {
+        script += &format!("complete -c {} -n \"__fish_use_subcommand\" -f -a \
+                            \"{}\"\n",
+                           self.name, name);
+      }
+      for (name, flags) in &self.subcommands {
+        let condition = format!("__fish_seen_subcommand_from {}", name);
+        script += &fish_complete(self.name, &condition, flags);
+      }
+    }
+
+    script
+  }
+}
+
+// Puts a backslash before every character of `word` that is in `special`.
+fn escape_word(word: &str, special: &str) -> String
+{
+  let mut escaped = String::new();
+  for ch in word.chars() {
+    if special.contains(ch) {
+      escaped.push('\\');
+    }
+    escaped.push(ch);
+  }
+  escaped
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+  use crate::flags::*;
+
+  #[test]
+  fn completion_scripts()
+  {
+    let mut color = String::new();
+    let mut verbose = 0;
+    let mut dir = String::new();
+
+    const COLORS: &[&str] = &["always", "never", "it's $HOME"];
+
+    let main_flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut color), vec!["-c", "--color"])
+             .description("Color [output].")
+             .value_hint(ValueHint::Choices(COLORS)),
+           FlagSpec::new(FlagType::RepeatFlag(&mut verbose), vec!["-v"]),];
+    let add_flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut dir), vec!["--dir"])
+             .value_hint(ValueHint::Directory)];
+
+    let mut completion = Completion::new("tool");
+    completion.flags(&main_flags).subcommand("add", &add_flags);
+
+    let bash = completion.render(Shell::Bash);
+    assert!(bash.contains("      -c|--color) ((i++)) ;;\n"));
+    assert!(bash.contains("      add) cmd=\"${COMP_WORDS[i]}\"; break ;;\n"));
+    assert!(bash.contains("  -c|--color) mapfile -t COMPREPLY < <(compgen -W \
+                           \"'always' 'never' 'it'\\\\''s \\$HOME'\" -- \
+                           \"$cur\"); return ;;\n"));
+    assert!(bash.contains("  --dir) COMPREPLY=($(compgen -d -- \"$cur\")); \
+                           return ;;\n"));
+    assert!(bash.contains("      opts=\"-c --color -v\"\n"));
+    assert!(bash.ends_with("complete -F _tool tool\n"));
+
+    let zsh = completion.render(Shell::Zsh);
+    assert!(zsh.starts_with("#compdef tool\n"));
+    assert!(zsh.contains("    '(-c --color)'{-c,--color}'[Color \\[output\\].]\
+                          :value:(always never it'\\''s\\ \\$HOME)' \\\n"));
+    assert!(zsh.contains("    '*'-v \\\n"));
+    assert!(zsh.contains("            --dir':value:_files -/' \\\n"));
+
+    let fish = completion.render(Shell::Fish);
+    assert_eq!(fish,
+               "complete -c tool -n \"__fish_use_subcommand\" -s c -l color \
+                -x -a \"always never it\\\\'s\\\\ \\\\\\$HOME\" \
+                -d \"Color [output].\"\n\
+                complete -c tool -n \"__fish_use_subcommand\" -s v\n\
+                complete -c tool -n \"__fish_use_subcommand\" -f -a \"add\"\n\
+                complete -c tool -n \"__fish_seen_subcommand_from add\" \
+                -l dir -x -a \"(__fish_complete_directories)\"\n");
+  }
+
+  #[test]
+  fn completion_shell_from_str()
+  {
+    assert_eq!("Bash".parse::<Shell>().unwrap(), Shell::Bash);
+    assert_eq!("fish".parse::<Shell>().unwrap(), Shell::Fish);
+    assert!("powershell".parse::<Shell>().is_err());
+  }
+}
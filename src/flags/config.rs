@@ -0,0 +1,272 @@
+//! Configuration files with values of flags.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use super::{long_flag_suggestions, negated, overridden, FlagError,
+            FlagErrorType, FlagGroup, FlagKind, ParserFlag, Seen, Source,
+            ValueStore};
+
+#[derive(Debug, Clone)]
+struct ConfigEntry
+{
+  section: String,
+  key: String,
+  value: Option<String>,
+  line: usize,
+}
+
+/// Values of flags from an INI-style file, which can be used beneath
+/// arguments with [`ParseOptions::config`](fn@super::ParseOptions::config).
+///
+/// Keys are long flag names without dashes. Values are set the same way as
+/// values in arguments, one value per line, so flags that take many values
+/// are set by repeating the key. Boolean flags take `true` or `false`, and
+/// [`RepeatFlag`](type@super::FlagType::RepeatFlag) takes a count. A key
+/// without a value sets the flag as if it was present in arguments, so
+/// [`NegatableFlag`](type@super::FlagType::NegatableFlag) can also be unset
+/// with its `no-` name, like `no-cache`. Keys before the first section belong
+/// to the program itself, and each `[section]` belongs to a subcommand. Lines
+/// that start with `#` or `;` are comments.
+///
+/// ```ini
+/// color = always
+/// verbose
+/// no-cache
+///
+/// [add]
+/// include = src
+/// include = tests
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Config
+{
+  path: String,
+  entries: Vec<ConfigEntry>,
+}
+
+impl Config
+{
+  /// Reads the file at `path`. A file that does not exist is read as an
+  /// empty one, since configuration files are usually optional.
+  pub fn load<P>(path: P) -> Result<Self, FlagError>
+    where P: AsRef<Path>
+  {
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    match fs::read_to_string(path) {
+      Ok(contents) => Self::parse(&name, &contents),
+      Err(err) if err.kind() == ErrorKind::NotFound => {
+        Ok(Self { path: name, entries: vec![] })
+      }
+      Err(err) => Err(config_error(&name, 0, err.to_string(), name.clone())),
+    }
+  }
+
+  /// Parses `contents` of a file. `path` is only used in errors.
+  pub fn parse(path: &str, contents: &str) -> Result<Self, FlagError>
+  {
+    let mut config = Self { path: path.to_string(), entries: vec![] };
+    let mut section = String::new();
+
+    for (i, line) in contents.lines().enumerate() {
+      let line_number = i + 1;
+      let line = line.trim();
+
+      if line.is_empty() || line.starts_with(['#', ';']) {
+        continue;
+      }
+
+      if let Some(name) = line.strip_prefix('[') {
+        let Some(name) = name.strip_suffix(']') else {
+          let reason = "expected ']' at the end of section".to_string();
+          return Err(config_error(path, line_number, reason, line.into()));
+        };
+        section = name.trim().to_string();
+        continue;
+      }
+
+      let (key, value) = match line.split_once('=') {
+        Some((key, value)) => (key.trim(), Some(unquote(value.trim()))),
+        None => (line, None),
+      };
+      if key.is_empty() || key.contains(char::is_whitespace) {
+        let reason = "expected 'key = value'".to_string();
+        return Err(config_error(path, line_number, reason, line.into()));
+      }
+
+      config.entries.push(ConfigEntry { section: section.clone(),
+                                        key: key.to_string(),
+                                        value,
+                                        line: line_number });
+    }
+
+    Ok(config)
+  }
+
+  // Returns only keys of the section.
+  pub(super) fn section(&self, section: &str) -> Self
+  {
+    let entries = self.entries
+                      .iter()
+                      .filter(|entry| entry.section == section)
+                      .cloned()
+                      .collect();
+    Self { path: self.path.clone(), entries }
+  }
+}
+
+// Removes double quotes around a value, so it can have spaces at the ends.
+fn unquote(value: &str) -> String
+{
+  match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+    Some(value) => value.to_string(),
+    None => value.to_string(),
+  }
+}
+
+fn config_error(path: &str,
+                line: usize,
+                reason: String,
+                flag: String)
+                -> FlagError
+{
+  let error_type =
+    FlagErrorType::ConfigFile { path: path.to_string(), line, reason };
+  FlagError::new(error_type, flag)
+}
+
+// Sets flags that were not seen from the configuration file. Flags that were
+// set are seen as their long name.
+pub(super) fn apply_config<V>(flags: &[ParserFlag],
+                              values: &mut V,
+                              seen: &mut [Option<Seen>],
+                              groups: &[FlagGroup],
+                              config: &Config,
+                              errors: &mut Vec<FlagError>)
+  where V: ValueStore + ?Sized
+{
+  // Only flags that were not set by arguments or environment are set, but
+  // keys can be repeated for flags that take many values.
+  let was_seen = (0..flags.len()).map(|index| {
+                                   seen[index].is_some() ||
+                                   overridden(flags, seen, groups, index)
+                                 })
+                                 .collect::<Vec<_>>();
+
+  for entry in &config.entries {
+    let name = format!("--{}", entry.key);
+    // Errors are shown with their location in the file.
+    let error = |error: FlagError| {
+      config_error(&config.path, entry.line, error.to_string(), error.flag)
+    };
+
+    let index = flags.iter().position(|flag| flag.aliases.contains(&name));
+    // Negatable flags also match their long aliases with `no-` prefix.
+    let negated_index = || {
+      flags.iter().position(|flag| {
+                    flag.kind.is_negatable() &&
+                    flag.aliases
+                        .iter()
+                        .any(|alias| negated(alias).is_some_and(|n| n == name))
+                  })
+    };
+    let (index, is_negated) = match index {
+      Some(index) => (Some(index), false),
+      None => (negated_index(), true),
+    };
+    let Some(index) = index else {
+      let mut unknown = FlagError::new(FlagErrorType::Unknown, name.clone());
+      unknown.suggestions = long_flag_suggestions(flags, &name);
+      errors.push(error(unknown));
+      continue;
+    };
+    if was_seen[index] {
+      continue;
+    }
+    let source = Source::Config { path: config.path.clone(),
+                                  line: entry.line };
+    seen[index] = Some(Seen { name: name.clone(), source });
+
+    let result = match (&entry.value, &flags[index].kind) {
+      // Negated names can't have a value, the same as in arguments.
+      (Some(value), _) if is_negated && !value.is_empty() => {
+        Err(FlagErrorType::ExtraValueProvided)
+      }
+      _ if is_negated => {
+        values.set_bool(index, false);
+        Ok(())
+      }
+      // Flags without a value in arguments take one like from environment.
+      (Some(value), kind) => {
+        let result = match kind {
+          FlagKind::Bool | FlagKind::Negatable | FlagKind::Repeat => {
+            values.set_from_str(index, value)
+          }
+          _ => values.set_value(index, value.clone()),
+        };
+        result.map_err(|reason| {
+                let value = value.clone();
+                FlagErrorType::InvalidValue { value, reason }
+              })
+      }
+      (None, FlagKind::Bool | FlagKind::Negatable) => {
+        values.set_bool(index, true);
+        Ok(())
+      }
+      (None, FlagKind::Repeat) => {
+        values.increment(index);
+        Ok(())
+      }
+      (None, FlagKind::Optional(implicit_value)) => {
+        let value = implicit_value.clone();
+        values.set_value(index, value.clone())
+              .map_err(|reason| FlagErrorType::InvalidValue { value, reason })
+      }
+      (None, _) => Err(FlagErrorType::NoValueProvided),
+    };
+
+    if let Err(error_type) = result {
+      errors.push(error(FlagError::new(error_type, name)));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+
+  #[test]
+  fn config_parse()
+  {
+    let contents = "# comment\n\
+                    color = \" always \"\n\
+                    verbose\n\
+                    \n\
+                    [add]\n\
+                    ; comment\n\
+                    include=src\n";
+    let config = Config::parse("tool.conf", contents).unwrap();
+
+    let root = config.section("");
+    let add = config.section("add");
+
+    assert_eq!(root.entries.len(), 2);
+    assert_eq!(root.entries[0].key, "color");
+    assert_eq!(root.entries[0].value.as_deref(), Some(" always "));
+    assert_eq!(root.entries[1].value, None);
+    assert_eq!(add.entries.len(), 1);
+    assert_eq!(add.entries[0].line, 7);
+
+    let err = Config::parse("tool.conf", "a = b\n[add\n").unwrap_err();
+
+    assert_eq!(err.to_string(),
+               "tool.conf:2: expected ']' at the end of section");
+
+    let err = Config::parse("tool.conf", "a b = c").unwrap_err();
+
+    assert_eq!(err.to_string(), "tool.conf:1: expected 'key = value'");
+  }
+}
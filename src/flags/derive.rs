@@ -0,0 +1,453 @@
+//! Traits for derive macros of `toiletcli-derive`.
+
+use std::any::Any;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{FlagError, FlagErrorType, FlagSpec, FlagValue, ParseOptions};
+
+pub use toiletcli_derive::{Flags, Subcommand};
+
+/// A struct that is made from parsed flags. Can be derived with
+/// `#[derive(Flags)]`, see documentation of the macro for attributes.
+///
+/// # Example
+/// ```rust
+/// use std::path::PathBuf;
+/// use toiletcli::flags::{Flags, Subcommand};
+///
+/// #[derive(Flags)]
+/// struct Tool
+/// {
+///   #[flag("-v", "--verbose", count, description = "Print more.")]
+///   verbose: usize,
+///   #[flag("-C", env = "TOOL_DIR", default = ".")]
+///   dir: PathBuf,
+///   #[subcommand]
+///   command: Option<Command>,
+/// }
+///
+/// #[derive(Subcommand)]
+/// enum Command
+/// {
+///   Add(Add),
+///   #[subcommand(name = "ls")]
+///   List,
+/// }
+///
+/// #[derive(Flags)]
+/// struct Add
+/// {
+///   #[flag("-n", required)]
+///   name: String,
+///   dry_run: bool,
+///   #[arguments]
+///   files: Vec<PathBuf>,
+/// }
+///
+/// let argv = ["-vv", "add", "-n", "x", "--dry-run", "a.txt"];
+/// let mut args = argv.iter().map(|x| x.to_string());
+///
+/// let tool = Tool::from_args(&mut args).unwrap();
+///
+/// assert_eq!(tool.verbose, 2);
+/// assert_eq!(tool.dir, PathBuf::from("."));
+///
+/// let Some(Command::Add(add)) = tool.command else {
+///   panic!("Add should be parsed");
+/// };
+///
+/// assert_eq!(add.name, "x");
+/// assert!(add.dry_run);
+/// assert_eq!(add.files, vec![PathBuf::from("a.txt")]);
+/// ```
+///
+/// Mistakes in attributes are reported at compile time, like `count` on a
+/// field that is not `usize`, a default value that is not a number for a
+/// number field, or two `#[flag(...)]` attributes on one field.
+///
+/// ```compile_fail
+/// use toiletcli::flags::Flags;
+///
+/// #[derive(Flags)]
+/// struct Tool
+/// {
+///   #[flag("-v", count)]
+///   verbose: u32,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use toiletcli::flags::Flags;
+///
+/// #[derive(Flags)]
+/// struct Tool
+/// {
+///   #[flag("-j", default = "many")]
+///   jobs: u32,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use toiletcli::flags::Flags;
+///
+/// #[derive(Flags)]
+/// struct Tool
+/// {
+///   #[flag("-j")]
+///   #[flag("--jobs")]
+///   jobs: u32,
+/// }
+/// ```
+pub trait Flags: Sized
+{
+  /// Consumes and parses flags and arguments from
+  /// [`Iterator<String>`](type@Iterator<String>), which should not include
+  /// the name of the program.
+  fn from_args<Args>(args: &mut Args) -> Result<Self, FlagError>
+    where Args: Iterator<Item = String>
+  {
+    Self::from_args_with(args, &ParseOptions::default())
+  }
+
+  /// Works the same way as [`from_args`](fn@Flags::from_args), but with
+  /// [`ParseOptions`](struct@ParseOptions). Options apply to flags of this
+  /// struct, and flags of a subcommand are parsed with default options.
+  ///
+  /// # Example
+  /// ```rust
+  /// use toiletcli::flags::{Config, Flags, ParseOptions};
+  ///
+  /// #[derive(Flags)]
+  /// struct Tool
+  /// {
+  ///   verbose: bool,
+  ///   color: Option<String>,
+  /// }
+  ///
+  /// let config = Config::parse("tool.conf", "verbose").unwrap();
+  ///
+  /// let mut options = ParseOptions::new();
+  /// options.abbreviations(true).config(&config, "");
+  ///
+  /// let mut args = ["--col", "never"].iter().map(|x| x.to_string());
+  /// let tool = Tool::from_args_with(&mut args, &options).unwrap();
+  ///
+  /// assert!(tool.verbose);
+  /// assert_eq!(tool.color.as_deref(), Some("never"));
+  /// ```
+  fn from_args_with<Args>(args: &mut Args,
+                          options: &ParseOptions)
+                          -> Result<Self, FlagError>
+    where Args: Iterator<Item = String>;
+
+  /// Parses arguments of the program from
+  /// [`std::env::args`](fn@std::env::args).
+  fn from_env() -> Result<Self, FlagError>
+  {
+    let mut args = std::env::args();
+    args.next();
+    Self::from_args(&mut args)
+  }
+
+  /// Calls `f` with the flag table of this struct, so it can be used to
+  /// render help, completion scripts, man pages or JSON. Flags refer to
+  /// temporary variables, which are dropped after `f` returns.
+  ///
+  /// # Example
+  /// ```rust
+  /// use toiletcli::flags::{Flags, Help};
+  ///
+  /// #[derive(Flags)]
+  /// struct Tool
+  /// {
+  ///   #[flag("-o", description = "Where to write.", value_name = "FILE")]
+  ///   output: String,
+  /// }
+  ///
+  /// let help =
+  ///   Tool::with_flag_specs(|flags| Help::new("tool").colored(false)
+  ///                                                  .render(flags));
+  ///
+  /// assert_eq!(help,
+  ///            "Usage: tool [OPTIONS]\n\
+  ///             \n\
+  ///             Options:\n  \
+  ///               -o <FILE>  Where to write.\n");
+  /// ```
+  fn with_flag_specs<R, F>(f: F) -> R
+    where F: FnOnce(&[FlagSpec]) -> R;
+}
+
+/// An enum of subcommands, each with its own flags. Can be derived with
+/// `#[derive(Subcommand)]`, see [`Flags`](trait@Flags).
+pub trait Subcommand: Sized
+{
+  /// Names of all subcommands.
+  const SUBCOMMANDS: &'static [&'static str];
+
+  /// Parses flags and arguments of the subcommand called `name`.
+  fn from_subcommand<Args>(name: &str,
+                           args: &mut Args)
+                           -> Result<Self, FlagError>
+    where Args: Iterator<Item = String>;
+}
+
+// Error for derived flags with a default value that could not be parsed.
+#[doc(hidden)]
+pub fn invalid_default(flag: &str, value: &str, reason: String) -> FlagError
+{
+  let reason = format!("invalid default value: {}", reason);
+  let error_type = FlagErrorType::InvalidValue { value: value.to_string(),
+                                                 reason };
+  FlagError::new(error_type, flag.to_string())
+}
+
+/// Value of a derived flag of type `Option<T>`, which is `None` until the flag
+/// is set.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct OptionValue<T>(pub Option<T>);
+
+impl<T> Default for OptionValue<T>
+{
+  fn default() -> Self
+  {
+    Self(None)
+  }
+}
+
+impl<T> FlagValue for OptionValue<T>
+  where T: FromStr + fmt::Debug + 'static,
+        T::Err: fmt::Display
+{
+  fn set_from_str(&mut self, value: &str) -> Result<(), String>
+  {
+    self.0 = Some(value.parse().map_err(|err: T::Err| err.to_string())?);
+    Ok(())
+  }
+
+  fn as_any(&self) -> &dyn Any
+  {
+    &self.0
+  }
+}
+
+// Error for derived flags without `#[arguments]` when arguments are left.
+#[doc(hidden)]
+pub fn check_no_arguments(arguments: &[String]) -> Result<(), FlagError>
+{
+  match arguments.first() {
+    Some(extra) => {
+      let error_type = FlagErrorType::UnexpectedArgument;
+      Err(FlagError::new(error_type, extra.clone()))
+    }
+    None => Ok(()),
+  }
+}
+
+// Error for derived subcommands when there are no arguments left.
+#[doc(hidden)]
+pub fn missing_subcommand(subcommands: &[&str]) -> FlagError
+{
+  let subcommands = subcommands.iter().map(|s| s.to_string()).collect();
+  let error_type = FlagErrorType::MissingSubcommand { subcommands };
+  FlagError::new(error_type, String::new())
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+  use crate::flags::{FlagEntry, Help};
+
+  #[derive(Flags, Debug)]
+  struct Tool
+  {
+    #[flag("-p", "--port", env = "TOILETCLI_TEST_DERIVE_PORT")]
+    ports: Vec<u16>,
+    #[flag("-j", default = "4", value_name = "N")]
+    jobs: u32,
+    #[flag(negatable)]
+    cache: bool,
+    #[subcommand]
+    command: Command,
+  }
+
+  #[derive(Subcommand, Debug, PartialEq)]
+  enum Command
+  {
+    RemoteAdd(RemoteAdd),
+    List,
+  }
+
+  #[derive(Flags, Debug, PartialEq)]
+  struct RemoteAdd
+  {
+    #[flag("--name", required)]
+    name: String,
+    #[flag("--url")]
+    url: Option<String>,
+  }
+
+  fn parse(argv: &[&str]) -> Result<Tool, FlagError>
+  {
+    let mut args = argv.iter().map(|x| x.to_string());
+    Tool::from_args(&mut args)
+  }
+
+  #[test]
+  fn derive_flags()
+  {
+    std::env::set_var("TOILETCLI_TEST_DERIVE_PORT", "80,443");
+
+    let tool = parse(&["--no-cache", "remote-add", "--name", "x"]).unwrap();
+
+    assert_eq!(tool.ports, vec![80, 443]);
+    assert_eq!(tool.jobs, 4);
+    assert!(!tool.cache);
+    assert_eq!(tool.command,
+               Command::RemoteAdd(RemoteAdd { name: "x".to_string(),
+                                              url: None }));
+
+    let argv = ["remote-add", "--name", "x", "--url", "a.git"];
+    let tool = parse(&argv).unwrap();
+
+    let url = Some("a.git".to_string());
+    assert_eq!(tool.command,
+               Command::RemoteAdd(RemoteAdd { name: "x".to_string(), url }));
+
+    let tool = parse(&["-p", "1", "-j", "8", "--cache", "list"]).unwrap();
+
+    assert_eq!(tool.ports, vec![1]);
+    assert_eq!(tool.jobs, 8);
+    assert!(tool.cache);
+    assert_eq!(tool.command, Command::List);
+  }
+
+  #[test]
+  fn derive_flags_errors()
+  {
+    let err = parse(&[]).unwrap_err();
+    assert_eq!(err.to_string(),
+               "Missing subcommand, expected one of remote-add, list");
+
+    let err = parse(&["lst"]).unwrap_err();
+    assert_eq!(err.to_string(), "Unknown subcommand lst, did you mean list?");
+
+    let err = parse(&["remote-add"]).unwrap_err();
+    assert_eq!(err.to_string(), "Missing required flag --name");
+
+    let err = parse(&["list", "--name"]).unwrap_err();
+    assert_eq!(err.to_string(), "Unknown flag --name");
+
+    let err = parse(&["list", "garbage"]).unwrap_err();
+    assert_eq!(err.error_type, FlagErrorType::UnexpectedArgument);
+    assert_eq!(err.to_string(), "Unexpected argument garbage");
+
+    let err = parse(&["remote-add", "--name", "x", "origin"]).unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected argument origin");
+  }
+
+  // Fields are named like variables that are generated by the macro.
+  #[derive(Flags, Debug)]
+  struct Clash
+  {
+    #[flag("-a", description = "Enable a.")]
+    args: bool,
+    #[flag("-f", value_name = "FLAG")]
+    flags: Vec<String>,
+    subcommand: String,
+    #[arguments]
+    arguments: Vec<String>,
+  }
+
+  #[test]
+  fn derive_flags_field_names()
+  {
+    let argv = ["-a", "-f", "x", "--subcommand", "y", "z"];
+    let mut args = argv.iter().map(|x| x.to_string());
+
+    let clash = Clash::from_args(&mut args).unwrap();
+
+    assert!(clash.args);
+    assert_eq!(clash.flags, vec!["x"]);
+    assert_eq!(clash.subcommand, "y");
+    assert_eq!(clash.arguments, vec!["z"]);
+  }
+
+  #[test]
+  fn derive_flags_options()
+  {
+    let mut options = ParseOptions::new();
+    options.abbreviations(true);
+
+    let mut args = ["--sub", "y", "z"].iter().map(|x| x.to_string());
+    let clash = Clash::from_args_with(&mut args, &options).unwrap();
+
+    assert_eq!(clash.subcommand, "y");
+    assert_eq!(clash.arguments, vec!["z"]);
+
+    let mut args = ["--sub", "y"].iter().map(|x| x.to_string());
+    let err = Clash::from_args(&mut args).unwrap_err();
+
+    assert_eq!(err.to_string(),
+               "Unknown flag --sub, did you mean --subcommand?");
+  }
+
+  #[test]
+  fn derive_flags_specs()
+  {
+    let help = Clash::with_flag_specs(|flags| {
+                 Help::new("clash").colored(false).render(flags)
+               });
+
+    assert_eq!(help,
+               "Usage: clash [OPTIONS]\n\
+                \n\
+                Options:\n  \
+                  -a                        Enable a.\n  \
+                  -f <FLAG>...\n      \
+                      --subcommand <VALUE>\n");
+
+    let aliases = Tool::with_flag_specs(|flags| {
+                    flags.iter()
+                         .map(|flag| flag.aliases().join(" "))
+                         .collect::<Vec<_>>()
+                  });
+    assert_eq!(aliases, vec!["-p --port", "-j", "--cache"]);
+  }
+
+  #[derive(Debug, Default)]
+  struct Level;
+
+  impl std::str::FromStr for Level
+  {
+    type Err = String;
+
+    fn from_str(_: &str) -> Result<Self, Self::Err>
+    {
+      Err("expected a level".to_string())
+    }
+  }
+
+  // Never parsed successfully, since the default value is invalid.
+  #[allow(dead_code)]
+  #[derive(Flags, Debug)]
+  struct Logger
+  {
+    #[flag("-l", "--level", default = "loud")]
+    level: Level,
+  }
+
+  #[test]
+  fn derive_flags_invalid_default()
+  {
+    let err = Logger::from_args(&mut std::iter::empty()).unwrap_err();
+
+    assert_eq!(err.flag, "--level");
+    assert_eq!(err.to_string(),
+               "Invalid value 'loud' for --level: invalid default value: \
+                expected a level");
+  }
+}
@@ -0,0 +1,524 @@
+//! Help rendering from the flag table.
+
+#[cfg(feature = "colors")]
+use crate::colors::Style;
+
+use super::{FlagEntry, FlagType, Positional};
+
+const INDENT: &str = "  ";
+const GAP: &str = "  ";
+// Options that are wider than this will have their description on the next
+// line.
+const MAX_OPTION_WIDTH: usize = 28;
+const DEFAULT_WIDTH: usize = 80;
+const DEFAULT_VALUE_NAME: &str = "VALUE";
+
+/// Renders usage and help message from the flag table, so it never gets out
+/// of sync with flags that are actually parsed.
+///
+/// Since the flag table holds references to variables, help should be rendered
+/// before these variables are read.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{FlagSpec, FlagType, Help};
+///
+/// let mut color = String::new();
+/// let mut show_help = false;
+///
+/// let flags = vec![
+///     FlagSpec::new(FlagType::StringFlag(&mut color), vec!["-c", "--color"])
+///       .description("Color output.")
+///       .value_name("COLOR"),
+///     FlagSpec::new(FlagType::BoolFlag(&mut show_help), vec!["--help"])
+///       .description("Display this message."),
+/// ];
+///
+/// let help = Help::new("cat").args("<file>...")
+///                            .about("Output a file to standard output.")
+///                            .colored(false)
+///                            .render(&flags);
+///
+/// assert_eq!(help,
+///            "Usage: cat [OPTIONS] <file>...\n\
+///             \n\
+///             Output a file to standard output.\n\
+///             \n\
+///             Options:\n  \
+///               -c, --color <COLOR>  Color output.\n      \
+///                   --help           Display this message.\n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Help<'h>
+{
+  name: &'h str,
+  args: &'h str,
+  about: &'h str,
+  positionals: Vec<(String, &'h str)>,
+  subcommands: Vec<(&'h str, &'h str)>,
+  width: usize,
+  colored: bool,
+}
+
+impl<'h> Help<'h>
+{
+  /// `name` is the program name, or a program name followed by a subcommand
+  /// name, like `"tool remote"`.
+  pub fn new(name: &'h str) -> Self
+  {
+    Self { name,
+           args: "",
+           about: "",
+           positionals: vec![],
+           subcommands: vec![],
+           width: DEFAULT_WIDTH,
+           colored: true }
+  }
+
+  /// Synopsis of arguments that come after options, like `"<file>..."`.
+  pub fn args(&mut self, args: &'h str) -> &mut Self
+  {
+    self.args = args;
+    self
+  }
+
+  /// Arguments that come after options. Their synopsis is used in usage,
+  /// unless [`args`](fn@Help::args) is set, and they are listed with their
+  /// descriptions.
+  ///
+  /// # Example
+  /// ```rust
+  /// use toiletcli::flags::{FlagSpec, Help, Positional};
+  ///
+  /// let mut source = String::new();
+  /// let mut files: Vec<String> = vec![];
+  ///
+  /// let positionals = vec![
+  ///     Positional::new("SOURCE", &mut source)
+  ///       .description("Where to copy from."),
+  ///     Positional::many("FILE", &mut files),
+  /// ];
+  ///
+  /// let help = Help::new("copy").positionals(&positionals)
+  ///                             .colored(false)
+  ///                             .render::<FlagSpec>(&[]);
+  ///
+  /// assert_eq!(help,
+  ///            "Usage: copy <SOURCE> [FILE]...\n\
+  ///             \n\
+  ///             Arguments:\n  \
+  ///               <SOURCE>   Where to copy from.\n  \
+  ///               [FILE]...\n");
+  /// ```
+  pub fn positionals<'a: 'h>(&mut self,
+                             positionals: &[Positional<'a>])
+                             -> &mut Self
+  {
+    self.positionals = positionals.iter()
+                                  .map(|p| (p.synopsis(), p.description))
+                                  .collect();
+    self
+  }
+
+  /// Text that is shown after usage line.
+  pub fn about(&mut self, about: &'h str) -> &mut Self
+  {
+    self.about = about;
+    self
+  }
+
+  /// Add a subcommand to the list of subcommands. Can be used multiple times.
+  pub fn subcommand(&mut self,
+                    name: &'h str,
+                    description: &'h str)
+                    -> &mut Self
+  {
+    self.subcommands.push((name, description));
+    self
+  }
+
+  /// Maximum width of the help message. Defaults to 80.
+  pub fn width(&mut self, width: usize) -> &mut Self
+  {
+    self.width = width;
+    self
+  }
+
+  /// Whether headings and flags should be styled. Styles are never used when
+  /// [`should_use_colors`](fn@crate::common::should_use_colors) returns
+  /// `false`, or when `colors` feature is disabled. Defaults to `true`.
+  pub fn colored(&mut self, colored: bool) -> &mut Self
+  {
+    self.colored = colored;
+    self
+  }
+
+  /// Returns help message for the provided flags.
+  pub fn render<'a, F>(&self, flags: &[F]) -> String
+    where F: FlagEntry<'a>
+  {
+    let mut help = String::new();
+
+    let mut usage = vec![self.name];
+    if !flags.is_empty() {
+      usage.push("[OPTIONS]");
+    }
+    let synopsis = self.positionals
+                       .iter()
+                       .map(|(synopsis, _)| synopsis.as_str())
+                       .collect::<Vec<_>>()
+                       .join(" ");
+    if !self.args.is_empty() {
+      usage.push(self.args);
+    } else if !synopsis.is_empty() {
+      usage.push(&synopsis);
+    } else if !self.subcommands.is_empty() {
+      usage.push("<COMMAND>");
+    }
+    help += &format!("{} {}\n", self.heading("Usage:"), usage.join(" "));
+
+    if !self.about.is_empty() {
+      help.push('\n');
+      for line in wrap(self.about, self.width) {
+        help += &line;
+        help.push('\n');
+      }
+    }
+
+    if !self.positionals.is_empty() {
+      let rows = self.positionals
+                     .iter()
+                     .map(|(left, text)| (left.clone(), text.to_string()))
+                     .collect::<Vec<_>>();
+      help += &format!("\n{}\n", self.heading("Arguments:"));
+      help += &self.table(&rows);
+    }
+
+    if !flags.is_empty() {
+      let rows = flags.iter()
+                      .map(|flag| (option_synopsis(flag), option_text(flag)))
+                      .collect::<Vec<_>>();
+      help += &format!("\n{}\n", self.heading("Options:"));
+      help += &self.table(&rows);
+    }
+
+    if !self.subcommands.is_empty() {
+      let rows = self.subcommands
+                     .iter()
+                     .map(|(name, text)| (name.to_string(), text.to_string()))
+                     .collect::<Vec<_>>();
+      help += &format!("\n{}\n", self.heading("Commands:"));
+      help += &self.table(&rows);
+    }
+
+    help
+  }
+
+  // Renders two aligned columns, wrapping the second one.
+  fn table(&self, rows: &[(String, String)]) -> String
+  {
+    let mut table = String::new();
+
+    let column = rows.iter()
+                     .map(|(left, _)| left.chars().count())
+                     .filter(|len| *len <= MAX_OPTION_WIDTH)
+                     .max()
+                     .unwrap_or(MAX_OPTION_WIDTH);
+    let text_offset = INDENT.len() + column + GAP.len();
+    let text_width = self.width.saturating_sub(text_offset);
+
+    for (left, text) in rows {
+      let left_width = left.chars().count();
+      table += INDENT;
+      table += &self.highlight(left);
+
+      let lines = wrap(text, text_width);
+      let mut lines = lines.iter();
+
+      if left_width > column {
+        table.push('\n');
+      } else if let Some(first) = lines.next() {
+        table += &" ".repeat(column - left_width);
+        table += GAP;
+        table += first;
+        table.push('\n');
+      } else {
+        table.push('\n');
+      }
+
+      for line in lines {
+        table += &" ".repeat(text_offset);
+        table += line;
+        table.push('\n');
+      }
+    }
+
+    table
+  }
+
+  fn heading(&self, text: &str) -> String
+  {
+    self.style(text, true)
+  }
+
+  fn highlight(&self, text: &str) -> String
+  {
+    self.style(text, false)
+  }
+
+  #[cfg(feature = "colors")]
+  fn style(&self, text: &str, is_heading: bool) -> String
+  {
+    if !self.colored {
+      return text.to_string();
+    }
+    if is_heading {
+      format!("{}{}{}{}", Style::Bold, Style::Underlined, text, Style::Reset)
+    } else {
+      format!("{}{}{}", Style::Bold, text, Style::Reset)
+    }
+  }
+
+  #[cfg(not(feature = "colors"))]
+  fn style(&self, text: &str, _is_heading: bool) -> String
+  {
+    text.to_string()
+  }
+}
+
+// How parts of a synopsis are written, so it can be rendered as help or in
+// other formats, like man pages.
+pub(super) trait Markup
+{
+  // Text that is typed as is, like a flag name.
+  fn literal(&self, text: &str) -> String;
+
+  // Placeholder for a value.
+  fn placeholder(&self, text: &str) -> String;
+}
+
+struct PlainText;
+
+impl Markup for PlainText
+{
+  fn literal(&self, text: &str) -> String
+  {
+    text.to_string()
+  }
+
+  fn placeholder(&self, text: &str) -> String
+  {
+    format!("<{}>", text)
+  }
+}
+
+/// Returns flag aliases and its value placeholder, like
+/// `-c, --color <COLOR>`. Short aliases come first. When flag does not have
+/// short aliases, the result is indented to align with flags that do.
+pub fn option_synopsis<'a, F>(flag: &F) -> String
+  where F: FlagEntry<'a>
+{
+  let has_shorts = flag.aliases().iter().any(|alias| alias.len() == 2);
+
+  let mut synopsis = String::new();
+  if !has_shorts {
+    synopsis += "    ";
+  }
+  synopsis += &flag_synopsis(flag, &PlainText);
+  synopsis
+}
+
+// Returns flag aliases and its value placeholder written with `markup`.
+pub(super) fn flag_synopsis<'a, F, M>(flag: &F, markup: &M) -> String
+  where F: FlagEntry<'a>,
+        M: Markup
+{
+  let aliases = flag.aliases();
+  let is_negatable = flag.flag_type().is_negatable();
+
+  let shorts = aliases.iter().filter(|alias| alias.len() == 2);
+  let longs = aliases.iter().filter(|alias| alias.len() > 2);
+  let names = shorts.chain(longs)
+                    .map(|alias| match alias.strip_prefix("--") {
+                      Some(name) if is_negatable => {
+                        format!("{}[{}]{}",
+                                markup.literal("--"),
+                                markup.literal("no-"),
+                                markup.literal(name))
+                      }
+                      _ => markup.literal(alias),
+                    })
+                    .collect::<Vec<_>>();
+
+  let mut synopsis = names.join(", ");
+  let value_name =
+    markup.placeholder(flag.value_name().unwrap_or(DEFAULT_VALUE_NAME));
+
+  if let FlagType::OptionalFlag(..) = flag.flag_type() {
+    synopsis += &format!("[={}]", value_name);
+  } else if flag.flag_type().takes_value() {
+    synopsis += &format!(" {}", value_name);
+    if flag.flag_type().takes_many() {
+      synopsis += "...";
+    }
+  }
+
+  synopsis
+}
+
+pub(super) fn option_text<'a, F>(flag: &F) -> String
+  where F: FlagEntry<'a>
+{
+  let mut text = flag.description().to_string();
+  if flag.is_required() {
+    if !text.is_empty() {
+      text.push(' ');
+    }
+    text += "[required]";
+  }
+  if let Some(default_value) = flag.default_value() {
+    if !text.is_empty() {
+      text.push(' ');
+    }
+    text += &format!("[default: {}]", default_value);
+  }
+  if let Some(env) = flag.env() {
+    if !text.is_empty() {
+      text.push(' ');
+    }
+    text += &format!("[env: {}]", env);
+  }
+  text
+}
+
+// Greedily wraps text by words. Words that are longer than width are kept
+// intact. Width is counted in characters, not bytes.
+fn wrap(text: &str, width: usize) -> Vec<String>
+{
+  let mut lines = vec![];
+
+  for paragraph in text.lines() {
+    let mut line = String::new();
+    for word in paragraph.split_whitespace() {
+      let line_width = line.chars().count();
+      if !line.is_empty() && line_width + 1 + word.chars().count() > width {
+        lines.push(std::mem::take(&mut line));
+      }
+      if !line.is_empty() {
+        line.push(' ');
+      }
+      line += word;
+    }
+    lines.push(line);
+  }
+
+  lines
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+  use crate::flags::*;
+
+  #[test]
+  fn help_synopsis()
+  {
+    let mut color = String::new();
+    let mut files = vec![];
+    let mut verbose = 0;
+    let mut cache = false;
+    let mut when = String::new();
+
+    let flags =
+      [FlagSpec::new(FlagType::StringFlag(&mut color), vec!["--color", "-c"])
+         .value_name("COLOR"),
+       FlagSpec::new(FlagType::ManyFlag(&mut files), vec!["--file"]),
+       FlagSpec::new(FlagType::RepeatFlag(&mut verbose), vec!["-v"]),
+       FlagSpec::new(FlagType::NegatableFlag(&mut cache),
+                     vec!["-C", "--cache"]),
+       FlagSpec::new(FlagType::OptionalFlag(&mut when, "auto"), vec!["--when"])
+         .value_name("WHEN"),];
+
+    assert_eq!(option_synopsis(&flags[0]), "-c, --color <COLOR>");
+    assert_eq!(option_synopsis(&flags[1]), "    --file <VALUE>...");
+    assert_eq!(option_synopsis(&flags[2]), "-v");
+    assert_eq!(option_synopsis(&flags[3]), "-C, --[no-]cache");
+    assert_eq!(option_synopsis(&flags[4]), "    --when[=<WHEN>]");
+  }
+
+  #[test]
+  fn help_wrap_and_subcommands()
+  {
+    let mut show_help = false;
+    let mut very_long_name = String::new();
+
+    let flags =
+      vec![FlagSpec::new(FlagType::BoolFlag(&mut show_help),
+                         vec!["-h", "--help"]).description("Display this \
+                                                           message and exit \
+                                                           right away."),
+           FlagSpec::new(FlagType::StringFlag(&mut very_long_name),
+                         vec!["--a-very-long-flag-name"]).value_name("NAME")
+                                                         .default_value("x")
+                                                         .env("X"),];
+
+    let help = Help::new("tool").about("Does things.")
+                                .subcommand("add", "Add a thing.")
+                                .subcommand("remove", "Remove a thing.")
+                                .width(40)
+                                .colored(false)
+                                .render(&flags);
+
+    assert_eq!(help,
+               "Usage: tool [OPTIONS] <COMMAND>\n\
+                \n\
+                Does things.\n\
+                \n\
+                Options:\n  \
+                  -h, --help  Display this message and\n              \
+                              exit right away.\n      \
+                      --a-very-long-flag-name <NAME>\n              \
+                              [default: x] [env: X]\n\
+                \n\
+                Commands:\n  \
+                  add     Add a thing.\n  \
+                  remove  Remove a thing.\n");
+  }
+
+  #[test]
+  fn help_non_ascii()
+  {
+    let mut name = String::new();
+    let mut verbose = false;
+
+    let flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut name), vec!["-n"])
+             .value_name("ИМЯ")
+             .description("Имя для приветствия"),
+           FlagSpec::new(FlagType::BoolFlag(&mut verbose), vec!["-v"])
+             .description("Печатать больше"),];
+
+    let help = Help::new("tool").width(30).colored(false).render(&flags);
+
+    assert_eq!(help,
+               "Usage: tool [OPTIONS]\n\
+                \n\
+                Options:\n  \
+                  -n <ИМЯ>  Имя для\n            \
+                            приветствия\n  \
+                  -v        Печатать больше\n");
+  }
+
+  #[test]
+  fn help_plain_flags()
+  {
+    let mut v = false;
+
+    let flags = vec![(FlagType::BoolFlag(&mut v), vec!["-v"])];
+
+    let help = Help::new("tool").colored(false).render(&flags);
+
+    assert_eq!(help, "Usage: tool [OPTIONS]\n\nOptions:\n  -v\n");
+  }
+}
@@ -0,0 +1,578 @@
+//! JSON description of the command line interface.
+
+use super::{flag_value_hint, DuplicateKeys, FlagEntry, FlagType, Positional,
+            ValueHint};
+
+/// Version of the document format, written as `format_version` at the top of
+/// it. It's changed when keys are removed or change their meaning.
+pub const JSON_FORMAT_VERSION: u32 = 1;
+
+// Flag information that is exported, copied out of the flag table.
+#[derive(Debug, Clone)]
+struct JsonFlag
+{
+  aliases: Vec<String>,
+  kind: &'static str,
+  value_name: Option<String>,
+  value_hint: Option<&'static str>,
+  choices: Vec<String>,
+  implicit_value: Option<String>,
+  separator: Option<String>,
+  dedup: bool,
+  duplicate_keys: Option<&'static str>,
+  description: String,
+  env: Option<String>,
+  default_value: Option<String>,
+  required: bool,
+}
+
+fn json_flags<'a, F>(flags: &[F]) -> Vec<JsonFlag>
+  where F: FlagEntry<'a>
+{
+  flags.iter()
+       .map(|flag| {
+         let flag_type = flag.flag_type();
+         let has_value = flag_type.takes_value() ||
+                         matches!(flag_type, FlagType::OptionalFlag(..));
+         let (value_hint, mut choices) = value_hint(flag_value_hint(flag));
+         if !has_value {
+           choices.clear();
+         }
+         let (separator, dedup) = match flag_type {
+           FlagType::ListFlag(_, separator) => {
+             (Some(separator.separator.to_string()), separator.dedup)
+           }
+           _ => (None, false),
+         };
+         JsonFlag { aliases: flag.aliases()
+                                 .iter()
+                                 .map(|alias| alias.to_string())
+                                 .collect(),
+                    kind: kind(flag_type),
+                    value_name: flag.value_name()
+                                    .filter(|_| has_value)
+                                    .map(|name| name.to_string()),
+                    value_hint: Some(value_hint).filter(|_| has_value),
+                    choices,
+                    implicit_value: match flag_type {
+                      FlagType::OptionalFlag(_, implicit) => {
+                        Some(implicit.to_string())
+                      }
+                      _ => None,
+                    },
+                    separator,
+                    dedup,
+                    duplicate_keys: match flag_type {
+                      FlagType::MapFlag(_, duplicate_keys) => {
+                        Some(duplicate_keys_name(*duplicate_keys))
+                      }
+                      _ => None,
+                    },
+                    description: flag.description().to_string(),
+                    env: flag.env().map(|env| env.to_string()),
+                    default_value: flag.default_value()
+                                       .map(|value| value.to_string()),
+                    required: flag.is_required() }
+       })
+       .collect()
+}
+
+impl JsonFlag
+{
+  fn fields(&self) -> Vec<(&'static str, String)>
+  {
+    let aliases = self.aliases.iter().map(|alias| string(alias));
+    let choices = self.choices.iter().map(|choice| string(choice));
+    vec![("aliases", list(aliases)),
+         ("kind", string(self.kind)),
+         ("value_name", optional(self.value_name.as_deref())),
+         ("value_hint", optional(self.value_hint)),
+         ("choices", list(choices)),
+         ("implicit_value", optional(self.implicit_value.as_deref())),
+         ("separator", optional(self.separator.as_deref())),
+         ("dedup", self.dedup.to_string()),
+         ("duplicate_keys", optional(self.duplicate_keys)),
+         ("description", string(&self.description)),
+         ("env", optional(self.env.as_deref())),
+         ("default", optional(self.default_value.as_deref())),
+         ("required", self.required.to_string()),]
+  }
+}
+
+// Name of the flag type, which is a part of the format.
+fn kind(flag_type: &FlagType) -> &'static str
+{
+  match flag_type {
+    FlagType::BoolFlag(_) => "bool",
+    FlagType::NegatableFlag(_) => "negatable",
+    FlagType::StringFlag(_) => "string",
+    FlagType::PathFlag(_) => "path",
+    FlagType::OptionalFlag(..) => "optional",
+    FlagType::ManyFlag(_) => "many",
+    FlagType::RepeatFlag(_) => "repeat",
+    FlagType::TypedFlag(_) => "typed",
+    FlagType::TypedManyFlag(_) => "typed_many",
+    FlagType::MapFlag(..) => "map",
+    FlagType::ListFlag(..) => "list",
+  }
+}
+
+// Name of the value hint and its choices, which are a part of the format.
+fn value_hint(value_hint: ValueHint) -> (&'static str, Vec<String>)
+{
+  match value_hint {
+    ValueHint::Any => ("any", vec![]),
+    ValueHint::File => ("file", vec![]),
+    ValueHint::Directory => ("directory", vec![]),
+    ValueHint::Choices(choices) => {
+      ("choices", choices.iter().map(|choice| choice.to_string()).collect())
+    }
+  }
+}
+
+fn duplicate_keys_name(duplicate_keys: DuplicateKeys) -> &'static str
+{
+  match duplicate_keys {
+    DuplicateKeys::Error => "error",
+    DuplicateKeys::First => "first",
+    DuplicateKeys::Last => "last",
+  }
+}
+
+#[derive(Debug, Clone)]
+struct JsonPositional
+{
+  name: String,
+  description: String,
+  required: bool,
+  many: bool,
+}
+
+impl JsonPositional
+{
+  fn fields(&self) -> Vec<(&'static str, String)>
+  {
+    vec![("name", string(&self.name)),
+         ("description", string(&self.description)),
+         ("required", self.required.to_string()),
+         ("many", self.many.to_string()),]
+  }
+}
+
+/// Describes flags, positional arguments and subcommands of a program as a
+/// JSON document, so other tools can be generated from it.
+///
+/// Keys are always present and always in the same order. Optional values are
+/// `null` when they are not set. The document starts with `format_version`,
+/// see [`JSON_FORMAT_VERSION`](constant@JSON_FORMAT_VERSION); subcommands
+/// don't have it.
+///
+/// Flag `kind` is the name of its [`FlagType`](enum@FlagType) in snake case
+/// without `_flag`, like `bool`, `string` or `typed_many`. `value_name` and
+/// `value_hint` are `null` for flags that can't have a value. `value_hint` is
+/// `any`, `file`, `directory` or `choices`, with values listed in `choices`,
+/// and is `file` for [`PathFlag`](type@FlagType::PathFlag) unless it's set.
+/// `implicit_value` is set for [`OptionalFlag`](type@FlagType::OptionalFlag),
+/// `separator` and `dedup` for [`ListFlag`](type@FlagType::ListFlag), and
+/// `duplicate_keys` (`error`, `first` or `last`) for
+/// [`MapFlag`](type@FlagType::MapFlag).
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{FlagSpec, FlagType, JsonSpec};
+///
+/// let mut color = String::new();
+///
+/// let flags = vec![
+///     FlagSpec::new(FlagType::StringFlag(&mut color), vec!["-c", "--color"])
+///       .description("Color output.")
+///       .env("TOOL_COLOR"),
+/// ];
+///
+/// let json = JsonSpec::new("tool").flags(&flags).render();
+///
+/// assert_eq!(json,
+///            r#"{
+///   "format_version": 1,
+///   "name": "tool",
+///   "about": "",
+///   "version": null,
+///   "flags": [
+///     {
+///       "aliases": ["-c", "--color"],
+///       "kind": "string",
+///       "value_name": null,
+///       "value_hint": "any",
+///       "choices": [],
+///       "implicit_value": null,
+///       "separator": null,
+///       "dedup": false,
+///       "duplicate_keys": null,
+///       "description": "Color output.",
+///       "env": "TOOL_COLOR",
+///       "default": null,
+///       "required": false
+///     }
+///   ],
+///   "positionals": [],
+///   "subcommands": []
+/// }
+/// "#);
+/// ```
+#[derive(Debug, Clone)]
+pub struct JsonSpec<'j>
+{
+  name: &'j str,
+  about: &'j str,
+  version: Option<&'j str>,
+  flags: Vec<JsonFlag>,
+  positionals: Vec<JsonPositional>,
+  subcommands: Vec<JsonSpec<'j>>,
+}
+
+impl<'j> JsonSpec<'j>
+{
+  /// `name` is the program name, or the subcommand name.
+  pub fn new(name: &'j str) -> Self
+  {
+    Self { name,
+           about: "",
+           version: None,
+           flags: vec![],
+           positionals: vec![],
+           subcommands: vec![] }
+  }
+
+  /// Short description of the program or subcommand.
+  pub fn about(&mut self, about: &'j str) -> &mut Self
+  {
+    self.about = about;
+    self
+  }
+
+  /// Version of the program.
+  pub fn version(&mut self, version: &'j str) -> &mut Self
+  {
+    self.version = Some(version);
+    self
+  }
+
+  /// Flags of the program itself, or flags that come before a subcommand.
+  pub fn flags<'a, F>(&mut self, flags: &[F]) -> &mut Self
+    where F: FlagEntry<'a>
+  {
+    self.flags = json_flags(flags);
+    self
+  }
+
+  /// Positional arguments that come after flags.
+  pub fn positionals(&mut self, positionals: &[Positional]) -> &mut Self
+  {
+    let positional = |p: &Positional| {
+      JsonPositional { name: p.name.to_string(),
+                       description: p.description.to_string(),
+                       required: p.required,
+                       many: p.is_many() }
+    };
+    self.positionals = positionals.iter().map(positional).collect();
+    self
+  }
+
+  /// Add a subcommand, which can have its own subcommands. Can be used
+  /// multiple times.
+  pub fn subcommand(&mut self, subcommand: &JsonSpec<'j>) -> &mut Self
+  {
+    self.subcommands.push(subcommand.clone());
+    self
+  }
+
+  /// Returns the JSON document.
+  pub fn render(&self) -> String
+  {
+    let mut json = String::new();
+    self.write(&mut json, 0);
+    json.push('\n');
+    json
+  }
+
+  fn write(&self, json: &mut String, depth: usize)
+  {
+    let indent = "  ".repeat(depth + 1);
+    let version = optional(self.version);
+
+    *json += "{\n";
+    if depth == 0 {
+      *json += &format!("{}\"format_version\": {},\n",
+                        indent, JSON_FORMAT_VERSION);
+    }
+    *json += &format!("{}\"name\": {},\n", indent, string(self.name));
+    *json += &format!("{}\"about\": {},\n", indent, string(self.about));
+    *json += &format!("{}\"version\": {},\n", indent, version);
+
+    *json += &format!("{}\"flags\": ", indent);
+    write_objects(json, self.flags.iter().map(JsonFlag::fields), depth + 1);
+    *json += ",\n";
+
+    *json += &format!("{}\"positionals\": ", indent);
+    let positionals = self.positionals.iter().map(JsonPositional::fields);
+    write_objects(json, positionals, depth + 1);
+    *json += ",\n";
+
+    *json += &format!("{}\"subcommands\": ", indent);
+    if self.subcommands.is_empty() {
+      *json += "[]";
+    } else {
+      *json += "[\n";
+      for (i, subcommand) in self.subcommands.iter().enumerate() {
+        *json += &"  ".repeat(depth + 2);
+        subcommand.write(json, depth + 2);
+        if i + 1 < self.subcommands.len() {
+          json.push(',');
+        }
+        json.push('\n');
+      }
+      *json += &indent;
+      json.push(']');
+    }
+    json.push('\n');
+
+    *json += &"  ".repeat(depth);
+    json.push('}');
+  }
+}
+
+// Writes an array of objects, with each key on its own line.
+fn write_objects<Objects>(json: &mut String, objects: Objects, depth: usize)
+  where Objects: Iterator<Item = Vec<(&'static str, String)>>
+{
+  let objects = objects.collect::<Vec<_>>();
+  if objects.is_empty() {
+    *json += "[]";
+    return;
+  }
+
+  let indent = "  ".repeat(depth + 1);
+  let key_indent = "  ".repeat(depth + 2);
+
+  *json += "[\n";
+  for (i, object) in objects.iter().enumerate() {
+    *json += &indent;
+    *json += "{\n";
+    for (j, (key, value)) in object.iter().enumerate() {
+      *json += &format!("{}\"{}\": {}", key_indent, key, value);
+      if j + 1 < object.len() {
+        json.push(',');
+      }
+      json.push('\n');
+    }
+    *json += &indent;
+    json.push('}');
+    if i + 1 < objects.len() {
+      json.push(',');
+    }
+    json.push('\n');
+  }
+  *json += &"  ".repeat(depth);
+  json.push(']');
+}
+
+// Returns a JSON string literal.
+fn string(text: &str) -> String
+{
+  let mut string = String::from('"');
+  for ch in text.chars() {
+    match ch {
+      '"' => string += "\\\"",
+      '\\' => string += "\\\\",
+      '\n' => string += "\\n",
+      '\r' => string += "\\r",
+      '\t' => string += "\\t",
+      ch if ch.is_control() => string += &format!("\\u{:04x}", ch as u32),
+      ch => string.push(ch),
+    }
+  }
+  string.push('"');
+  string
+}
+
+fn optional(text: Option<&str>) -> String
+{
+  text.map(string).unwrap_or("null".to_string())
+}
+
+fn list<Items>(items: Items) -> String
+  where Items: Iterator<Item = String>
+{
+  format!("[{}]", items.collect::<Vec<_>>().join(", "))
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+  use crate::flags::*;
+  use std::collections::HashMap;
+  use std::path::PathBuf;
+
+  #[test]
+  fn json_spec()
+  {
+    let mut verbose = 0;
+    let mut jobs: u32 = 0;
+    let mut output = PathBuf::new();
+    let mut file = String::new();
+    let mut color = String::new();
+    let mut features: Vec<String> = vec![];
+    let mut defines = HashMap::new();
+
+    let color_type = FlagType::OptionalFlag(&mut color, "always");
+    let color_hint = ValueHint::Choices(&["always", "never"]);
+    let features_type =
+      FlagType::ListFlag(&mut features, Separator::new(',').dedup(true));
+    let defines_type = FlagType::MapFlag(&mut defines, DuplicateKeys::First);
+    let flags =
+      vec![FlagSpec::new(FlagType::RepeatFlag(&mut verbose), vec!["-v"])
+             .value_name("IGNORED"),
+           FlagSpec::new(color_type, vec!["--color"]).value_name("WHEN")
+                                                     .value_hint(color_hint),
+           FlagSpec::new(features_type, vec!["--features"]),
+           FlagSpec::new(defines_type, vec!["-D"]),];
+    let build_flags =
+      vec![FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["-j"])
+             .value_name("N")
+             .default_value("1")
+             .required(true),
+           FlagSpec::new(FlagType::PathFlag(&mut output), vec!["-o"])];
+    let positionals =
+      vec![Positional::new("FILE", &mut file).description("A \"file\".\n")];
+
+    let mut build = JsonSpec::new("build");
+    build.flags(&build_flags).positionals(&positionals);
+
+    let json = JsonSpec::new("tool").about("Tab\there.")
+                                    .version("1.0")
+                                    .flags(&flags)
+                                    .subcommand(&build)
+                                    .render();
+
+    assert_eq!(json,
+               r#"{
+  "format_version": 1,
+  "name": "tool",
+  "about": "Tab\there.",
+  "version": "1.0",
+  "flags": [
+    {
+      "aliases": ["-v"],
+      "kind": "repeat",
+      "value_name": null,
+      "value_hint": null,
+      "choices": [],
+      "implicit_value": null,
+      "separator": null,
+      "dedup": false,
+      "duplicate_keys": null,
+      "description": "",
+      "env": null,
+      "default": null,
+      "required": false
+    },
+    {
+      "aliases": ["--color"],
+      "kind": "optional",
+      "value_name": "WHEN",
+      "value_hint": "choices",
+      "choices": ["always", "never"],
+      "implicit_value": "always",
+      "separator": null,
+      "dedup": false,
+      "duplicate_keys": null,
+      "description": "",
+      "env": null,
+      "default": null,
+      "required": false
+    },
+    {
+      "aliases": ["--features"],
+      "kind": "list",
+      "value_name": null,
+      "value_hint": "any",
+      "choices": [],
+      "implicit_value": null,
+      "separator": ",",
+      "dedup": true,
+      "duplicate_keys": null,
+      "description": "",
+      "env": null,
+      "default": null,
+      "required": false
+    },
+    {
+      "aliases": ["-D"],
+      "kind": "map",
+      "value_name": null,
+      "value_hint": "any",
+      "choices": [],
+      "implicit_value": null,
+      "separator": null,
+      "dedup": false,
+      "duplicate_keys": "first",
+      "description": "",
+      "env": null,
+      "default": null,
+      "required": false
+    }
+  ],
+  "positionals": [],
+  "subcommands": [
+    {
+      "name": "build",
+      "about": "",
+      "version": null,
+      "flags": [
+        {
+          "aliases": ["-j"],
+          "kind": "typed",
+          "value_name": "N",
+          "value_hint": "any",
+          "choices": [],
+          "implicit_value": null,
+          "separator": null,
+          "dedup": false,
+          "duplicate_keys": null,
+          "description": "",
+          "env": null,
+          "default": "1",
+          "required": true
+        },
+        {
+          "aliases": ["-o"],
+          "kind": "path",
+          "value_name": null,
+          "value_hint": "file",
+          "choices": [],
+          "implicit_value": null,
+          "separator": null,
+          "dedup": false,
+          "duplicate_keys": null,
+          "description": "",
+          "env": null,
+          "default": null,
+          "required": false
+        }
+      ],
+      "positionals": [
+        {
+          "name": "FILE",
+          "description": "A \"file\".\n",
+          "required": true,
+          "many": false
+        }
+      ],
+      "subcommands": []
+    }
+  ]
+}
+"#);
+  }
+}
@@ -0,0 +1,412 @@
+//! Man page rendering from the flag table.
+
+use super::help::{flag_synopsis, option_text, Markup};
+use super::{display_name, positionals_synopsis, FlagEntry, Positional};
+
+// Flag information that is needed for a man page, copied out of the flag
+// table.
+#[derive(Debug, Clone)]
+struct ManFlag
+{
+  synopsis: String,
+  text: String,
+  env: Option<String>,
+  name: String,
+}
+
+fn man_flags<'a, F>(flags: &[F]) -> Vec<ManFlag>
+  where F: FlagEntry<'a>
+{
+  flags.iter()
+       .map(|flag| {
+         ManFlag { synopsis: flag_synopsis(flag, &Roff),
+                   text: option_text(flag),
+                   env: flag.env().map(|env| env.to_string()),
+                   name: display_name(flag.aliases()) }
+       })
+       .collect()
+}
+
+// Writes synopsis with bold aliases and an italic value placeholder, like
+// `\fB\-c\fR, \fB\-\-color\fR \fICOLOR\fR`.
+struct Roff;
+
+impl Markup for Roff
+{
+  fn literal(&self, text: &str) -> String
+  {
+    format!("\\fB{}\\fR", escape(text))
+  }
+
+  fn placeholder(&self, text: &str) -> String
+  {
+    format!("\\fI{}\\fR", escape(text))
+  }
+}
+
+// Escapes text, so it's shown as is.
+fn escape(text: &str) -> String
+{
+  text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+// Escapes an argument of a request. A double quote would end a quoted
+// argument, so it's escaped too.
+fn escape_argument(text: &str) -> String
+{
+  escape(text).replace('"', "\\(dq")
+}
+
+// Escapes text that is put on its own lines. Lines that start with a dot or
+// an apostrophe would be treated as requests otherwise.
+fn escape_lines(text: &str) -> String
+{
+  let mut escaped = String::new();
+  for line in text.lines() {
+    let line = line.trim();
+    if line.is_empty() {
+      escaped += ".PP\n";
+      continue;
+    }
+    if line.starts_with(['.', '\'']) {
+      escaped += "\\&";
+    }
+    escaped += &escape(line);
+    escaped.push('\n');
+  }
+  escaped
+}
+
+#[derive(Debug, Clone)]
+struct ManSubcommand<'m>
+{
+  name: &'m str,
+  about: &'m str,
+  flags: Vec<ManFlag>,
+}
+
+/// Renders a `man(7)` page from the flag table, with NAME, SYNOPSIS,
+/// DESCRIPTION, OPTIONS, COMMANDS and ENVIRONMENT sections. Sections without
+/// contents are left out.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{FlagSpec, FlagType, ManPage};
+///
+/// let mut color = String::new();
+/// let mut force = false;
+///
+/// let main_flags = vec![
+///     FlagSpec::new(FlagType::StringFlag(&mut color), vec!["-c", "--color"])
+///       .description("Color output.")
+///       .value_name("WHEN")
+///       .env("TOOL_COLOR"),
+/// ];
+/// let add_flags = vec![
+///     FlagSpec::new(FlagType::BoolFlag(&mut force), vec!["-f", "--force"])
+///       .description("Overwrite existing files."),
+/// ];
+///
+/// let page =
+///   ManPage::new("tool", "do things").version("tool 1.0")
+///                                    .flags(&main_flags)
+///                                    .subcommand("add",
+///                                                "Add a file.",
+///                                                &add_flags)
+///                                    .render();
+///
+/// assert!(page.starts_with(".TH TOOL 1 \"\" \"tool 1.0\" \"User Commands\"\n\
+///                           .SH NAME\n\
+///                           tool \\- do things\n"));
+/// assert!(page.contains(".TP\n\
+///                        \\fB\\-c\\fR, \\fB\\-\\-color\\fR \\fIWHEN\\fR\n\
+///                        Color output. [env: TOOL_COLOR]\n"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManPage<'m>
+{
+  name: &'m str,
+  section: &'m str,
+  date: &'m str,
+  version: &'m str,
+  manual: &'m str,
+  about: &'m str,
+  description: &'m str,
+  args: String,
+  flags: Vec<ManFlag>,
+  subcommands: Vec<ManSubcommand<'m>>,
+}
+
+impl<'m> ManPage<'m>
+{
+  /// `name` is the program name, and `about` is its one line description,
+  /// which is shown in NAME section. Both are required, since tools like
+  /// `whatis` and `apropos` read them from there.
+  pub fn new(name: &'m str, about: &'m str) -> Self
+  {
+    Self { name,
+           section: "1",
+           date: "",
+           version: "",
+           manual: "User Commands",
+           about,
+           description: "",
+           args: String::new(),
+           flags: vec![],
+           subcommands: vec![] }
+  }
+
+  /// Section of the manual. Defaults to `1`.
+  pub fn section(&mut self, section: &'m str) -> &mut Self
+  {
+    self.section = section;
+    self
+  }
+
+  /// Date of the last change, shown in the footer.
+  pub fn date(&mut self, date: &'m str) -> &mut Self
+  {
+    self.date = date;
+    self
+  }
+
+  /// Program name with its version, like `"tool 1.0"`, shown in the footer.
+  pub fn version(&mut self, version: &'m str) -> &mut Self
+  {
+    self.version = version;
+    self
+  }
+
+  /// Title of the manual, shown in the header. Defaults to `User Commands`.
+  pub fn manual(&mut self, manual: &'m str) -> &mut Self
+  {
+    self.manual = manual;
+    self
+  }
+
+  /// Text of DESCRIPTION section. Empty lines separate paragraphs.
+  pub fn description(&mut self, description: &'m str) -> &mut Self
+  {
+    self.description = description;
+    self
+  }
+
+  /// Synopsis of arguments that come after options, like `"<file>..."`.
+  pub fn args(&mut self, args: &str) -> &mut Self
+  {
+    self.args = args.to_string();
+    self
+  }
+
+  /// Use synopsis of positional arguments as
+  /// [`args`](fn@ManPage::args).
+  pub fn positionals(&mut self, positionals: &[Positional]) -> &mut Self
+  {
+    self.args = positionals_synopsis(positionals);
+    self
+  }
+
+  /// Flags of the program itself, or flags that come before a subcommand.
+  pub fn flags<'a, F>(&mut self, flags: &[F]) -> &mut Self
+    where F: FlagEntry<'a>
+  {
+    self.flags = man_flags(flags);
+    self
+  }
+
+  /// Add a subcommand with its description and flags. Can be used multiple
+  /// times.
+  pub fn subcommand<'a, F>(&mut self,
+                           name: &'m str,
+                           about: &'m str,
+                           flags: &[F])
+                           -> &mut Self
+    where F: FlagEntry<'a>
+  {
+    let flags = man_flags(flags);
+    self.subcommands.push(ManSubcommand { name, about, flags });
+    self
+  }
+
+  /// Returns the man page.
+  pub fn render(&self) -> String
+  {
+    let mut page = String::new();
+
+    page += &format!(".TH {} {} \"{}\" \"{}\" \"{}\"\n",
+                     escape_argument(&self.name.to_uppercase()),
+                     escape_argument(self.section),
+                     escape_argument(self.date),
+                     escape_argument(self.version),
+                     escape_argument(self.manual));
+
+    page += ".SH NAME\n";
+    page += &format!("{} \\- {}\n", escape(self.name), escape(self.about));
+
+    page += ".SH SYNOPSIS\n";
+    page += &self.synopsis(None, !self.flags.is_empty());
+    for subcommand in &self.subcommands {
+      page += ".br\n";
+      page +=
+        &self.synopsis(Some(subcommand.name), !subcommand.flags.is_empty());
+    }
+
+    if !self.description.is_empty() {
+      page += ".SH DESCRIPTION\n";
+      page += &escape_lines(self.description);
+    }
+
+    if !self.flags.is_empty() {
+      page += ".SH OPTIONS\n";
+      page += &options(&self.flags);
+    }
+
+    if !self.subcommands.is_empty() {
+      page += ".SH COMMANDS\n";
+      for subcommand in &self.subcommands {
+        page += &format!(".SS {}\n", escape(subcommand.name));
+        if !subcommand.about.is_empty() {
+          page += &escape_lines(subcommand.about);
+        }
+        page += &options(&subcommand.flags);
+      }
+    }
+
+    let subcommand_flags =
+      self.subcommands.iter().flat_map(|subcommand| &subcommand.flags);
+    let env_flags = self.flags
+                        .iter()
+                        .chain(subcommand_flags)
+                        .filter(|flag| flag.env.is_some())
+                        .collect::<Vec<_>>();
+    if !env_flags.is_empty() {
+      page += ".SH ENVIRONMENT\n";
+      for flag in env_flags {
+        let env = flag.env.as_deref().unwrap_or_default();
+        page += &format!(".TP\n\\fB{}\\fR\nUsed when \\fB{}\\fR is not \
+                          present.\n",
+                         escape(env),
+                         escape(&flag.name));
+      }
+    }
+
+    page
+  }
+
+  fn synopsis(&self, subcommand: Option<&str>, has_flags: bool) -> String
+  {
+    let mut synopsis = format!(".B {}\n", escape(self.name));
+    if let Some(subcommand) = subcommand {
+      synopsis += &format!(".B {}\n", escape(subcommand));
+    }
+
+    let mut rest = vec![];
+    if has_flags {
+      rest.push("[\\fIOPTIONS\\fR]".to_string());
+    }
+    if subcommand.is_none() && !self.args.is_empty() {
+      rest.push(escape(&self.args));
+    } else if subcommand.is_none() && !self.subcommands.is_empty() {
+      rest.push("\\fICOMMAND\\fR".to_string());
+    }
+
+    if !rest.is_empty() {
+      synopsis += &rest.join(" ");
+      synopsis.push('\n');
+    }
+    synopsis
+  }
+}
+
+// Renders flags as a list of tagged paragraphs.
+fn options(flags: &[ManFlag]) -> String
+{
+  let mut options = String::new();
+  for flag in flags {
+    options += ".TP\n";
+    options += &flag.synopsis;
+    options.push('\n');
+    if !flag.text.is_empty() {
+      options += &escape_lines(&flag.text);
+    }
+  }
+  options
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+  use crate::flags::*;
+
+  #[test]
+  fn man_page()
+  {
+    let mut cache = false;
+    let mut color = String::new();
+    let mut includes: Vec<String> = vec![];
+    let mut jobs: u32 = 0;
+    let mut file = String::new();
+
+    let flags =
+      vec![FlagSpec::new(FlagType::NegatableFlag(&mut cache), vec!["--cache"])
+             .description(".hidden dots and back\\slashes"),
+           FlagSpec::new(FlagType::OptionalFlag(&mut color, "always"),
+                         vec!["--color"]).value_name("WHEN"),
+           FlagSpec::new(FlagType::ManyFlag(&mut includes), vec!["-I"])
+             .value_name("DIR"),];
+    let build_flags =
+      vec![FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["-j"])
+             .env("TOOL_JOBS")
+             .required(true),];
+    let positionals = vec![Positional::new("FILE", &mut file)];
+
+    let page =
+      ManPage::new("tool", "build things").section("8")
+                                          .date("2024-01-01")
+                                          .positionals(&positionals)
+                                          .description("First.\n\nSecond.")
+                                          .flags(&flags)
+                                          .subcommand("build", "", &build_flags)
+                                          .render();
+
+    assert_eq!(page,
+               ".TH TOOL 8 \"2024\\-01\\-01\" \"\" \"User Commands\"\n\
+                .SH NAME\n\
+                tool \\- build things\n\
+                .SH SYNOPSIS\n\
+                .B tool\n\
+                [\\fIOPTIONS\\fR] <FILE>\n\
+                .br\n\
+                .B tool\n\
+                .B build\n\
+                [\\fIOPTIONS\\fR]\n\
+                .SH DESCRIPTION\n\
+                First.\n\
+                .PP\n\
+                Second.\n\
+                .SH OPTIONS\n\
+                .TP\n\
+                \\fB\\-\\-\\fR[\\fBno\\-\\fR]\\fBcache\\fR\n\
+                \\&.hidden dots and back\\eslashes\n\
+                .TP\n\
+                \\fB\\-\\-color\\fR[=\\fIWHEN\\fR]\n\
+                .TP\n\
+                \\fB\\-I\\fR \\fIDIR\\fR...\n\
+                .SH COMMANDS\n\
+                .SS build\n\
+                .TP\n\
+                \\fB\\-j\\fR \\fIVALUE\\fR\n\
+                [required] [env: TOOL_JOBS]\n\
+                .SH ENVIRONMENT\n\
+                .TP\n\
+                \\fBTOOL_JOBS\\fR\n\
+                Used when \\fB\\-j\\fR is not present.\n");
+
+    let page =
+      ManPage::new("\"tool\"", "").version("tool \"1.0\"").render();
+
+    assert!(page.starts_with(".TH \\(dqTOOL\\(dq 1 \"\" \"tool \\(dq1.0\\(dq\" \
+                              \"User Commands\"\n"));
+  }
+}
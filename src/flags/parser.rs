@@ -0,0 +1,1081 @@
+//! Parser that owns its flags and values. It's the core of flag parsing:
+//! functions that take a table of [`FlagType`](enum@FlagType) references
+//! describe the table as a [`Parser`](struct@Parser) and let it set values
+//! through the references.
+
+use std::any::type_name;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::{bool_from_str, display_name, insert_pair, into_string,
+            long_names, parse_flags_seen, parse_until_subcommand_seen,
+            split_pairs, split_values, DuplicateKeys, FlagEntry, FlagError,
+            FlagErrorType, FlagSpec, FlagType, FlagValue, ParseOptions, Seen,
+            Separator, Source};
+
+/// Kind of a flag in [`Parser`](struct@Parser). Works the same way as the
+/// [`FlagType`](enum@FlagType) with the same name.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FlagKind
+{
+  Bool,
+  Negatable,
+  Repeat,
+  String,
+  Path,
+  /// Contains the value that is used when flag is present without a value.
+  Optional(String),
+  Many,
+  /// Contains the type of the value.
+  Typed(ValueType),
+  /// Contains the type of values.
+  TypedMany(ValueType),
+  /// Contains what to do with keys that are set more than once.
+  Map(DuplicateKeys),
+  /// Contains the type of values and how they are split.
+  List(ValueType, Separator),
+}
+
+impl FlagKind
+{
+  /// Returns `true` if this flag requires a value, see
+  /// [`FlagType::takes_value`](fn@FlagType::takes_value).
+  pub fn takes_value(&self) -> bool
+  {
+    !matches!(self,
+              FlagKind::Bool |
+              FlagKind::Negatable |
+              FlagKind::Repeat |
+              FlagKind::Optional(_))
+  }
+
+  /// Returns `true` if long aliases of this flag can be prefixed with `--no-`.
+  pub fn is_negatable(&self) -> bool
+  {
+    *self == FlagKind::Negatable
+  }
+
+  // Kind of a flag in a flag table.
+  fn of(flag_type: &FlagType) -> Self
+  {
+    match flag_type {
+      FlagType::BoolFlag(_) => FlagKind::Bool,
+      FlagType::NegatableFlag(_) => FlagKind::Negatable,
+      FlagType::RepeatFlag(_) => FlagKind::Repeat,
+      FlagType::StringFlag(_) => FlagKind::String,
+      FlagType::PathFlag(_) => FlagKind::Path,
+      FlagType::OptionalFlag(_, implicit_value) => {
+        FlagKind::Optional(implicit_value.to_string())
+      }
+      FlagType::ManyFlag(_) => FlagKind::Many,
+      FlagType::TypedFlag(_) => FlagKind::Typed(ValueType::referenced()),
+      FlagType::TypedManyFlag(_) => {
+        FlagKind::TypedMany(ValueType::referenced())
+      }
+      FlagType::MapFlag(_, duplicates) => FlagKind::Map(*duplicates),
+      FlagType::ListFlag(_, separator) => {
+        FlagKind::List(ValueType::referenced(), *separator)
+      }
+    }
+  }
+}
+
+/// Type of values of [`Typed`](type@FlagKind::Typed),
+/// [`TypedMany`](type@FlagKind::TypedMany) and
+/// [`List`](type@FlagKind::List) flags. Values are checked with
+/// [`FromStr`](trait@FromStr) when they are parsed, and parsed again when they
+/// are queried with [`ParsedArgs::get`](fn@ParsedArgs::get).
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{FlagKind, Parser, ValueType};
+///
+/// let mut parser = Parser::new();
+/// parser.flag("jobs", FlagKind::Typed(ValueType::of::<u32>()), &["-j"]);
+///
+/// let mut args = ["-j", "x"].iter().map(|x| x.to_string());
+/// assert!(parser.parse(&mut args).is_err());
+///
+/// let mut args = ["-j", "4"].iter().map(|x| x.to_string());
+/// assert_eq!(parser.parse(&mut args).unwrap().get::<u32>("jobs"), Some(4));
+/// ```
+#[derive(Clone, Copy)]
+pub struct ValueType
+{
+  name: &'static str,
+  // Returns the debug representation of the parsed value, which is used to
+  // compare values of lists.
+  parse: fn(&str) -> Result<String, String>,
+}
+
+impl ValueType
+{
+  /// Values are parsed as `T`.
+  pub fn of<T>() -> Self
+    where T: FromStr + fmt::Debug,
+          T::Err: fmt::Display
+  {
+    Self { name: type_name::<T>(),
+           parse: |value| {
+             let value = value.parse::<T>().map_err(|err| err.to_string())?;
+             Ok(format!("{:?}", value))
+           } }
+  }
+
+  // Values of flag tables are checked by the variable they are set to, so
+  // they are not checked here.
+  fn referenced() -> Self
+  {
+    Self { name: "_", parse: |value| Ok(value.to_string()) }
+  }
+}
+
+impl fmt::Debug for ValueType
+{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+  {
+    write!(f, "ValueType({})", self.name)
+  }
+}
+
+impl PartialEq for ValueType
+{
+  fn eq(&self, other: &Self) -> bool
+  {
+    self.name == other.name
+  }
+}
+
+impl Eq for ValueType {}
+
+/// A flag of [`Parser`](struct@Parser).
+#[derive(Debug, Clone)]
+pub struct ParserFlag
+{
+  id: String,
+  pub(super) kind: FlagKind,
+  pub(super) aliases: Vec<String>,
+  initial_value: Option<String>,
+  pub(super) env: Option<String>,
+  pub(super) required: bool,
+  description: String,
+  value_name: Option<String>,
+}
+
+impl ParserFlag
+{
+  /// Value of this flag before parsing, written the same way as in its
+  /// environment variable, like `true` for [`Bool`](type@FlagKind::Bool) or
+  /// `a,b` for [`Many`](type@FlagKind::Many) flags. Unlike
+  /// [`FlagSpec::default_value`](fn@super::FlagSpec::default_value), which is
+  /// only shown in help, this is the value
+  /// [`ParsedArgs`](struct@ParsedArgs) returns when flag is not set.
+  pub fn initial_value(&mut self, initial_value: &str) -> &mut Self
+  {
+    self.initial_value = Some(initial_value.to_string());
+    self
+  }
+
+  /// Environment variable to use when flag is not present in arguments, see
+  /// [`FlagSpec::env`](fn@super::FlagSpec::env).
+  pub fn env(&mut self, env: &str) -> &mut Self
+  {
+    self.env = Some(env.to_string());
+    self
+  }
+
+  /// Whether this flag has to be present, see
+  /// [`FlagSpec::required`](fn@super::FlagSpec::required).
+  pub fn required(&mut self, required: bool) -> &mut Self
+  {
+    self.required = required;
+    self
+  }
+
+  /// Text that describes this flag in help, see
+  /// [`FlagSpec::description`](fn@super::FlagSpec::description).
+  pub fn description(&mut self, description: &str) -> &mut Self
+  {
+    self.description = description.to_string();
+    self
+  }
+
+  /// Placeholder for the value in help, see
+  /// [`FlagSpec::value_name`](fn@super::FlagSpec::value_name).
+  pub fn value_name(&mut self, value_name: &str) -> &mut Self
+  {
+    self.value_name = Some(value_name.to_string());
+    self
+  }
+
+  pub(super) fn long_names(&self) -> Vec<String>
+  {
+    long_names(&self.aliases, self.kind.is_negatable())
+  }
+
+  // Value of this flag before parsing.
+  fn value(&self) -> Result<Value, FlagError>
+  {
+    let mut value = Value::new(&self.kind);
+    if let Some(initial_value) = &self.initial_value {
+      value.set_from_str(initial_value).map_err(|reason| {
+             let value = initial_value.clone();
+             let reason = format!("invalid initial value: {}", reason);
+             let error_type = FlagErrorType::InvalidValue { value, reason };
+             FlagError::new(error_type, display_name(&self.aliases))
+           })?;
+    }
+    Ok(value)
+  }
+}
+
+// Storage of values of flags, where flags are referred to by their index in
+// the parser.
+pub(super) trait ValueStore
+{
+  // Sets a boolean flag.
+  fn set_bool(&mut self, index: usize, value: bool);
+
+  // Counts one more repeat of a flag.
+  fn increment(&mut self, index: usize);
+
+  // Sets a value from arguments, which might not be valid unicode.
+  fn set_os_value(&mut self,
+                  index: usize,
+                  value: OsString)
+                  -> Result<(), FlagErrorType>;
+
+  // Sets a value the same way as from arguments. Returns the reason when value
+  // could not be parsed.
+  fn set_value(&mut self, index: usize, value: String) -> Result<(), String>;
+
+  // Sets a value that did not come from arguments, like from an environment
+  // variable. Unlike arguments, this can set any kind of flag.
+  fn set_from_str(&mut self, index: usize, value: &str) -> Result<(), String>;
+}
+
+// Value of a flag that is owned by the parser. Typed values are kept as they
+// were passed, since their type is only known when they are queried.
+#[derive(Debug, Clone)]
+enum Value
+{
+  Bool(bool),
+  Count(usize),
+  String(Option<String>),
+  Path(Option<PathBuf>),
+  Typed(ValueType, Option<String>),
+  Many(Option<ValueType>, Vec<String>),
+  Map(DuplicateKeys, HashMap<String, String>),
+  List(ValueType, Separator, Vec<String>),
+}
+
+impl Value
+{
+  fn new(kind: &FlagKind) -> Self
+  {
+    match kind {
+      FlagKind::Bool | FlagKind::Negatable => Value::Bool(false),
+      FlagKind::Repeat => Value::Count(0),
+      FlagKind::String | FlagKind::Optional(_) => Value::String(None),
+      FlagKind::Path => Value::Path(None),
+      FlagKind::Many => Value::Many(None, vec![]),
+      FlagKind::Typed(value_type) => Value::Typed(*value_type, None),
+      FlagKind::TypedMany(value_type) => Value::Many(Some(*value_type), vec![]),
+      FlagKind::Map(duplicates) => Value::Map(*duplicates, HashMap::new()),
+      FlagKind::List(value_type, separator) => {
+        Value::List(*value_type, *separator, vec![])
+      }
+    }
+  }
+
+  fn set_value(&mut self, value: String) -> Result<(), String>
+  {
+    match self {
+      Value::String(string) => *string = Some(value),
+      Value::Path(path) => *path = Some(PathBuf::from(value)),
+      Value::Typed(value_type, typed) => {
+        (value_type.parse)(&value)?;
+        *typed = Some(value);
+      }
+      Value::Many(value_type, values) => {
+        if let Some(value_type) = value_type {
+          (value_type.parse)(&value)?;
+        }
+        values.push(value);
+      }
+      Value::Map(duplicates, map) => insert_pair(map, *duplicates, value)?,
+      Value::List(value_type, separator, values) => {
+        // Values are compared the same way as
+        // `FlagValues::extend_from_strs` does.
+        let mut seen = values.iter()
+                             .filter_map(|value| (value_type.parse)(value).ok())
+                             .collect::<HashSet<_>>();
+        let mut items = vec![];
+        for item in separator.split(&value) {
+          let parsed = (value_type.parse)(&item)
+            .map_err(|reason| format!("'{}': {}", item, reason))?;
+          if !separator.dedup || seen.insert(parsed) {
+            items.push(item);
+          }
+        }
+        values.extend(items);
+      }
+      Value::Bool(_) | Value::Count(_) => {
+        unreachable!("flag does not take a value")
+      }
+    }
+    Ok(())
+  }
+
+  fn set_from_str(&mut self, value: &str) -> Result<(), String>
+  {
+    match self {
+      Value::Bool(boolean) => *boolean = bool_from_str(value)?,
+      Value::Count(count) => {
+        *count = value.parse().map_err(|err| format!("{}", err))?;
+      }
+      Value::Many(..) => {
+        for v in split_values(value) {
+          self.set_value(v.to_string())?;
+        }
+      }
+      Value::Map(..) => {
+        for pair in split_pairs(value) {
+          self.set_value(pair.to_string())?;
+        }
+      }
+      Value::String(_) |
+      Value::Path(_) |
+      Value::Typed(..) |
+      Value::List(..) => self.set_value(value.to_string())?,
+    }
+    Ok(())
+  }
+}
+
+impl ValueStore for [Value]
+{
+  fn set_bool(&mut self, index: usize, value: bool)
+  {
+    match &mut self[index] {
+      Value::Bool(boolean) => *boolean = value,
+      _ => unreachable!("flag is not a boolean"),
+    }
+  }
+
+  fn increment(&mut self, index: usize)
+  {
+    match &mut self[index] {
+      Value::Count(count) => *count += 1,
+      _ => unreachable!("flag is not a repeat flag"),
+    }
+  }
+
+  fn set_os_value(&mut self,
+                  index: usize,
+                  value: OsString)
+                  -> Result<(), FlagErrorType>
+  {
+    if let Value::Path(path) = &mut self[index] {
+      *path = Some(PathBuf::from(value));
+      return Ok(());
+    }
+
+    let value = value.into_string()
+                     .map_err(|value| FlagErrorType::InvalidUnicode { value })?;
+    self[index].set_value(value.clone())
+               .map_err(|reason| FlagErrorType::InvalidValue { value, reason })
+  }
+
+  fn set_value(&mut self, index: usize, value: String) -> Result<(), String>
+  {
+    self[index].set_value(value)
+  }
+
+  fn set_from_str(&mut self, index: usize, value: &str) -> Result<(), String>
+  {
+    self[index].set_from_str(value)
+  }
+}
+
+/// Parser that owns its flags, so the flag table can be built at runtime and
+/// stored anywhere. Flags are registered by id, and parsed values are queried
+/// by that id from [`ParsedArgs`](struct@ParsedArgs).
+///
+/// Functions like [`parse_flags`](fn@super::parse_flags) describe their flag
+/// table as a parser, so both ways parse the same.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{FlagKind, Parser};
+///
+/// let mut parser = Parser::new();
+/// parser.flag("verbose", FlagKind::Repeat, &["-v"]);
+/// parser.flag("output", FlagKind::String, &["-o", "--output"])
+///       .initial_value("out.txt");
+/// parser.flag("include", FlagKind::Many, &["-I"]);
+///
+/// let argv = ["-vv", "-I", "a", "file", "-I", "b"];
+/// let mut args = argv.iter().map(|x| x.to_string());
+///
+/// let parsed = parser.parse(&mut args).unwrap();
+///
+/// assert_eq!(parsed.count("verbose"), 2);
+/// assert_eq!(parsed.get_str("output"), Some("out.txt"));
+/// assert_eq!(parsed.get_many("include"), ["a", "b"]);
+/// assert_eq!(parsed.arguments(), ["file"]);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Parser
+{
+  pub(super) flags: Vec<ParserFlag>,
+}
+
+impl Parser
+{
+  pub fn new() -> Self
+  {
+    Self::default()
+  }
+
+  /// Registers a flag with `id`. Registering the same id again replaces the
+  /// flag.
+  pub fn flag(&mut self,
+              id: &str,
+              kind: FlagKind,
+              aliases: &[&str])
+              -> &mut ParserFlag
+  {
+    let flag = ParserFlag { id: id.to_string(),
+                            kind,
+                            aliases: aliases.iter()
+                                            .map(|a| a.to_string())
+                                            .collect(),
+                            initial_value: None,
+                            env: None,
+                            required: false,
+                            description: String::new(),
+                            value_name: None };
+
+    let index = match self.flags.iter().position(|f| f.id == id) {
+      Some(index) => {
+        self.flags[index] = flag;
+        index
+      }
+      None => {
+        self.flags.push(flag);
+        self.flags.len() - 1
+      }
+    };
+
+    &mut self.flags[index]
+  }
+
+  // Describes a flag table, which keeps values in its own variables.
+  pub(super) fn describe<'a, F>(flags: &[F]) -> Self
+    where F: FlagEntry<'a>
+  {
+    let flags = flags.iter()
+                     .map(|flag| {
+                       let aliases = flag.aliases();
+                       ParserFlag { id: display_name(aliases),
+                                    kind: FlagKind::of(flag.flag_type()),
+                                    aliases: aliases.iter()
+                                                    .map(|a| a.to_string())
+                                                    .collect(),
+                                    initial_value: None,
+                                    env: flag.env().map(str::to_string),
+                                    required: flag.is_required(),
+                                    description: String::new(),
+                                    value_name: None }
+                     })
+                     .collect();
+    Self { flags }
+  }
+
+  /// Consumes and parses flags and arguments, see
+  /// [`parse_flags`](fn@super::parse_flags).
+  pub fn parse<Args>(&self, args: &mut Args) -> Result<ParsedArgs, FlagError>
+    where Args: Iterator<Item = String>
+  {
+    self.parse_with(args, &ParseOptions::default())
+  }
+
+  /// Works the same way as [`parse`](fn@Parser::parse), but with
+  /// [`ParseOptions`](struct@ParseOptions).
+  pub fn parse_with<Args>(&self,
+                          args: &mut Args,
+                          options: &ParseOptions)
+                          -> Result<ParsedArgs, FlagError>
+    where Args: Iterator<Item = String>
+  {
+    let mut args = args.by_ref().map(OsString::from);
+    let parsed = self.parse_os_with(&mut args, options)?;
+    let arguments = parsed.arguments.into_iter().map(into_string).collect();
+    Ok(ParsedArgs { values: parsed.values, arguments })
+  }
+
+  /// Works the same way as [`parse`](fn@Parser::parse), but takes
+  /// [`Iterator<OsString>`](type@Iterator<OsString>), see
+  /// [`parse_flags_os`](fn@super::parse_flags_os).
+  pub fn parse_os<Args>(&self,
+                        args: &mut Args)
+                        -> Result<ParsedArgs<OsString>, FlagError>
+    where Args: Iterator<Item = OsString>
+  {
+    self.parse_os_with(args, &ParseOptions::default())
+  }
+
+  /// Works the same way as [`parse_os`](fn@Parser::parse_os), but with
+  /// [`ParseOptions`](struct@ParseOptions).
+  pub fn parse_os_with<Args>(&self,
+                             args: &mut Args,
+                             options: &ParseOptions)
+                             -> Result<ParsedArgs<OsString>, FlagError>
+    where Args: Iterator<Item = OsString>
+  {
+    let mut values = self.values()?;
+    let mut seen = vec![None; self.flags.len()];
+
+    let arguments = parse_flags_seen(args,
+                                     self,
+                                     values.as_mut_slice(),
+                                     &mut seen,
+                                     options)?;
+
+    Ok(self.parsed(values, seen, arguments))
+  }
+
+  /// Consumes and parses flags until the first argument that is not a flag,
+  /// see
+  /// [`parse_flags_until_subcommand`](fn@super::parse_flags_until_subcommand).
+  /// Returns parsed values without arguments, and the subcommand.
+  ///
+  /// # Example
+  /// ```rust
+  /// use toiletcli::flags::{FlagKind, Parser};
+  ///
+  /// let mut parser = Parser::new();
+  /// parser.flag("verbose", FlagKind::Bool, &["-v"]);
+  ///
+  /// let mut args = ["-v", "add", "-f"].iter().map(|x| x.to_string());
+  /// let (parsed, subcommand) = parser.parse_until_subcommand(&mut args)
+  ///                                  .unwrap();
+  ///
+  /// assert!(parsed.get_bool("verbose"));
+  /// assert_eq!(subcommand, "add");
+  /// assert_eq!(args.next().as_deref(), Some("-f"));
+  /// ```
+  pub fn parse_until_subcommand<Args>(&self,
+                                      args: &mut Args)
+                                      -> Result<(ParsedArgs, String),
+                                                FlagError>
+    where Args: Iterator<Item = String>
+  {
+    self.parse_until_subcommand_with(args, &ParseOptions::default())
+  }
+
+  /// Works the same way as
+  /// [`parse_until_subcommand`](fn@Parser::parse_until_subcommand), but with
+  /// [`ParseOptions`](struct@ParseOptions).
+  pub fn parse_until_subcommand_with<Args>(&self,
+                                           args: &mut Args,
+                                           options: &ParseOptions)
+                                           -> Result<(ParsedArgs, String),
+                                                     FlagError>
+    where Args: Iterator<Item = String>
+  {
+    let mut values = self.values()?;
+    let mut seen = vec![None; self.flags.len()];
+
+    let mut args = args.by_ref().map(OsString::from);
+    let subcommand = parse_until_subcommand_seen(&mut args,
+                                                 self,
+                                                 values.as_mut_slice(),
+                                                 &mut seen,
+                                                 options)?;
+
+    Ok((self.parsed(values, seen, vec![]), into_string(subcommand)))
+  }
+
+  /// Calls `f` with a flag table that describes flags of this parser, so it
+  /// can be used to render help, completion scripts, man pages or JSON.
+  /// Initial values are shown as defaults.
+  ///
+  /// # Example
+  /// ```rust
+  /// use toiletcli::flags::{FlagKind, Help, Parser};
+  ///
+  /// let mut parser = Parser::new();
+  /// parser.flag("output", FlagKind::String, &["-o"])
+  ///       .description("Where to write.")
+  ///       .value_name("FILE");
+  ///
+  /// let help =
+  ///   parser.with_flag_specs(|flags| Help::new("tool").colored(false)
+  ///                                                   .render(flags));
+  ///
+  /// assert_eq!(help,
+  ///            "Usage: tool [OPTIONS]\n\
+  ///             \n\
+  ///             Options:\n  \
+  ///               -o <FILE>  Where to write.\n");
+  /// ```
+  pub fn with_flag_specs<R, F>(&self, f: F) -> R
+    where F: FnOnce(&[FlagSpec]) -> R
+  {
+    let mut placeholders = self.flags
+                               .iter()
+                               .map(|flag| Placeholder::new(&flag.kind))
+                               .collect::<Vec<_>>();
+    let specs = self.flags
+                    .iter()
+                    .zip(placeholders.iter_mut())
+                    .map(|(flag, placeholder)| {
+                      let aliases =
+                        flag.aliases.iter().map(String::as_str).collect();
+                      let mut spec =
+                        FlagSpec::new(placeholder.flag_type(&flag.kind),
+                                      aliases).description(&flag.description)
+                                              .required(flag.required);
+                      if let Some(value_name) = &flag.value_name {
+                        spec = spec.value_name(value_name);
+                      }
+                      if let Some(initial_value) = &flag.initial_value {
+                        spec = spec.default_value(initial_value);
+                      }
+                      if let Some(env) = &flag.env {
+                        spec = spec.env(env);
+                      }
+                      spec
+                    })
+                    .collect::<Vec<_>>();
+    f(&specs)
+  }
+
+  // Values of flags before parsing.
+  fn values(&self) -> Result<Vec<Value>, FlagError>
+  {
+    self.flags.iter().map(ParserFlag::value).collect()
+  }
+
+  // Collects parsed values by id of their flag.
+  fn parsed<A>(&self,
+               values: Vec<Value>,
+               seen: Vec<Option<Seen>>,
+               arguments: Vec<A>)
+               -> ParsedArgs<A>
+  {
+    let values = self.flags
+                     .iter()
+                     .zip(values)
+                     .zip(seen)
+                     .map(|((flag, value), seen)| {
+                       let source =
+                         seen.map(|seen| seen.source).unwrap_or_default();
+                       (flag.id.clone(), ParsedValue { value, source })
+                     })
+                     .collect();
+    ParsedArgs { values, arguments }
+  }
+}
+
+// Variables of a flag table that describes a parser. They are only used for
+// display, so typed values are kept as strings.
+enum Placeholder
+{
+  Bool(bool),
+  Count(usize),
+  String(String),
+  Path(PathBuf),
+  Many(Vec<String>),
+  Map(HashMap<String, String>),
+}
+
+impl Placeholder
+{
+  fn new(kind: &FlagKind) -> Self
+  {
+    match kind {
+      FlagKind::Bool | FlagKind::Negatable => Placeholder::Bool(false),
+      FlagKind::Repeat => Placeholder::Count(0),
+      FlagKind::String | FlagKind::Optional(_) | FlagKind::Typed(_) => {
+        Placeholder::String(String::new())
+      }
+      FlagKind::Path => Placeholder::Path(PathBuf::new()),
+      FlagKind::Many | FlagKind::TypedMany(_) | FlagKind::List(..) => {
+        Placeholder::Many(vec![])
+      }
+      FlagKind::Map(_) => Placeholder::Map(HashMap::new()),
+    }
+  }
+
+  fn flag_type<'a>(&'a mut self, kind: &'a FlagKind) -> FlagType<'a>
+  {
+    match (kind, self) {
+      (FlagKind::Bool, Placeholder::Bool(value)) => FlagType::BoolFlag(value),
+      (FlagKind::Negatable, Placeholder::Bool(value)) => {
+        FlagType::NegatableFlag(value)
+      }
+      (FlagKind::Repeat, Placeholder::Count(count)) => {
+        FlagType::RepeatFlag(count)
+      }
+      (FlagKind::String, Placeholder::String(value)) => {
+        FlagType::StringFlag(value)
+      }
+      (FlagKind::Optional(implicit), Placeholder::String(value)) => {
+        FlagType::OptionalFlag(value, implicit)
+      }
+      (FlagKind::Typed(_), Placeholder::String(value)) => {
+        FlagType::TypedFlag(value as &mut dyn FlagValue)
+      }
+      (FlagKind::Path, Placeholder::Path(path)) => FlagType::PathFlag(path),
+      (FlagKind::Many, Placeholder::Many(values)) => FlagType::ManyFlag(values),
+      (FlagKind::TypedMany(_), Placeholder::Many(values)) => {
+        FlagType::TypedManyFlag(values)
+      }
+      (FlagKind::List(_, separator), Placeholder::Many(values)) => {
+        FlagType::ListFlag(values, *separator)
+      }
+      (FlagKind::Map(duplicates), Placeholder::Map(map)) => {
+        FlagType::MapFlag(map, *duplicates)
+      }
+      _ => unreachable!("placeholder is made for its kind"),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+struct ParsedValue
+{
+  value: Value,
+  source: Source,
+}
+
+/// Values of flags parsed by [`Parser`](struct@Parser), queried by id.
+/// Arguments are [`String`](struct@String), or
+/// [`OsString`](struct@OsString) when parsed with
+/// [`Parser::parse_os`](fn@Parser::parse_os).
+///
+/// # Panics
+/// Getters panic when there is no flag with that id, or when flag is of the
+/// wrong kind.
+#[derive(Debug, Default, Clone)]
+pub struct ParsedArgs<A = String>
+{
+  values: HashMap<String, ParsedValue>,
+  arguments: Vec<A>,
+}
+
+impl<A> ParsedArgs<A>
+{
+  fn value(&self, id: &str) -> &Value
+  {
+    self.values
+        .get(id)
+        .map(|parsed| &parsed.value)
+        .unwrap_or_else(|| panic!("There is no flag with id '{}'.", id))
+  }
+
+  /// Value of [`Bool`](type@FlagKind::Bool) or
+  /// [`Negatable`](type@FlagKind::Negatable) flag.
+  pub fn get_bool(&self, id: &str) -> bool
+  {
+    match self.value(id) {
+      Value::Bool(value) => *value,
+      _ => panic!("Flag '{}' is not a boolean.", id),
+    }
+  }
+
+  /// Value of [`String`](type@FlagKind::String),
+  /// [`Optional`](type@FlagKind::Optional) or
+  /// [`Typed`](type@FlagKind::Typed) flag as it was passed. `None` when flag
+  /// was not set and does not have an initial value.
+  pub fn get_str(&self, id: &str) -> Option<&str>
+  {
+    match self.value(id) {
+      Value::String(value) | Value::Typed(_, value) => value.as_deref(),
+      _ => panic!("Flag '{}' does not take a single value.", id),
+    }
+  }
+
+  /// Value of [`Path`](type@FlagKind::Path) flag. `None` when flag was not
+  /// set and does not have an initial value.
+  pub fn get_path(&self, id: &str) -> Option<&Path>
+  {
+    match self.value(id) {
+      Value::Path(path) => path.as_deref(),
+      _ => panic!("Flag '{}' is not a path.", id),
+    }
+  }
+
+  /// Value of a flag that takes a single value, like
+  /// [`Typed`](type@FlagKind::Typed) flag, parsed as `T`. `None` when flag was
+  /// not set and does not have an initial value.
+  ///
+  /// # Panics
+  /// Also panics when value is not a `T`, which happens when `T` is not the
+  /// type flag was registered with.
+  pub fn get<T>(&self, id: &str) -> Option<T>
+    where T: FromStr
+  {
+    self.get_str(id).map(|value| parse_as(id, value))
+  }
+
+  /// Values of [`Many`](type@FlagKind::Many),
+  /// [`TypedMany`](type@FlagKind::TypedMany) or
+  /// [`List`](type@FlagKind::List) flag as they were passed. Values of lists
+  /// are already split.
+  pub fn get_many(&self, id: &str) -> &[String]
+  {
+    match self.value(id) {
+      Value::Many(_, values) | Value::List(_, _, values) => values,
+      _ => panic!("Flag '{}' does not take many values.", id),
+    }
+  }
+
+  /// Values of [`TypedMany`](type@FlagKind::TypedMany) or
+  /// [`List`](type@FlagKind::List) flag parsed as `T`, see
+  /// [`get`](fn@ParsedArgs::get).
+  pub fn get_all<T>(&self, id: &str) -> Vec<T>
+    where T: FromStr
+  {
+    self.get_many(id).iter().map(|value| parse_as(id, value)).collect()
+  }
+
+  /// Values of [`Map`](type@FlagKind::Map) flag.
+  pub fn get_map(&self, id: &str) -> &HashMap<String, String>
+  {
+    match self.value(id) {
+      Value::Map(_, map) => map,
+      _ => panic!("Flag '{}' is not a map.", id),
+    }
+  }
+
+  /// Count of [`Repeat`](type@FlagKind::Repeat) flag, or the number of values
+  /// of a flag that takes many values.
+  pub fn count(&self, id: &str) -> usize
+  {
+    match self.value(id) {
+      Value::Count(count) => *count,
+      Value::Many(_, values) | Value::List(_, _, values) => values.len(),
+      Value::Map(_, map) => map.len(),
+      _ => panic!("Flag '{}' can't be counted.", id),
+    }
+  }
+
+  /// Whether flag was present in arguments, or set from its environment
+  /// variable or a configuration file.
+  pub fn is_present(&self, id: &str) -> bool
+  {
+    *self.source(id) != Source::Default
+  }
+
+  /// Where the value of flag came from.
+  pub fn source(&self, id: &str) -> &Source
+  {
+    self.values
+        .get(id)
+        .map(|parsed| &parsed.source)
+        .unwrap_or_else(|| panic!("There is no flag with id '{}'.", id))
+  }
+
+  /// Arguments that are not flags.
+  pub fn arguments(&self) -> &[A]
+  {
+    &self.arguments
+  }
+}
+
+// Parses a value that was checked when it was set.
+fn parse_as<T>(id: &str, value: &str) -> T
+  where T: FromStr
+{
+  value.parse().unwrap_or_else(|_| {
+                 panic!("Value of flag '{}' is not a {}.", id, type_name::<T>())
+               })
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+  use crate::flags::Help;
+
+  fn parser() -> Parser
+  {
+    let mut parser = Parser::new();
+    parser.flag("cache", FlagKind::Negatable, &["--cache"]);
+    let color = FlagKind::Optional("always".to_string());
+    parser.flag("color", color, &["--color"]);
+    parser.flag("name", FlagKind::String, &["-n", "--name"]).required(true);
+    parser.flag("verbose", FlagKind::Bool, &["-v"]);
+    parser
+  }
+
+  #[test]
+  fn parser_parse()
+  {
+    let parser = parser();
+
+    let argv = ["--no-cache", "-vn", "x", "argument", "--color"];
+    let mut args = argv.iter().map(|x| x.to_string());
+    let parsed = parser.parse(&mut args).unwrap();
+
+    assert!(!parsed.get_bool("cache"));
+    assert!(parsed.is_present("cache"));
+    assert_eq!(parsed.source("color"), &Source::Argument { index: 4 });
+    assert!(parsed.get_bool("verbose"));
+    assert_eq!(parsed.get_str("name"), Some("x"));
+    assert_eq!(parsed.get_str("color"), Some("always"));
+    assert_eq!(parsed.arguments(), ["argument"]);
+
+    // Parser can be used again.
+    let mut args = ["-n", "y"].iter().map(|x| x.to_string());
+    let parsed = parser.parse(&mut args).unwrap();
+
+    assert!(!parsed.is_present("cache"));
+    assert!(!parsed.get_bool("verbose"));
+    assert_eq!(parsed.get_str("color"), None);
+    assert_eq!(parsed.get_str("name"), Some("y"));
+
+    let mut args = ["-v"].iter().map(|x| x.to_string());
+    let err = parser.parse(&mut args).unwrap_err();
+
+    assert_eq!(err.to_string(), "Missing required flag --name");
+  }
+
+  #[test]
+  fn parser_typed_kinds()
+  {
+    let mut parser = Parser::new();
+    parser.flag("jobs", FlagKind::Typed(ValueType::of::<u32>()), &["-j"])
+          .initial_value("1");
+    let ports = FlagKind::TypedMany(ValueType::of::<u16>());
+    parser.flag("ports", ports, &["-p"]);
+    parser.flag("defines", FlagKind::Map(DuplicateKeys::Error), &["-D"]);
+    let tags = Separator::new(',').dedup(true);
+    let tags = FlagKind::List(ValueType::of::<String>(), tags);
+    parser.flag("tags", tags, &["--tags"]).initial_value("a");
+    parser.flag("output", FlagKind::Path, &["-o"]);
+
+    let argv = ["-p", "80", "-D", "MODE=1", "--tags=b,a", "-p=22", "-o", "x"];
+    let mut args = argv.iter().map(|x| x.to_string());
+    let parsed = parser.parse(&mut args).unwrap();
+
+    assert_eq!(parsed.get::<u32>("jobs"), Some(1));
+    assert!(!parsed.is_present("jobs"));
+    assert_eq!(parsed.get_all::<u16>("ports"), [80, 22]);
+    assert_eq!(parsed.get_map("defines")["MODE"], "1");
+    assert_eq!(parsed.get_many("tags"), ["a", "b"]);
+    assert_eq!(parsed.get_path("output"), Some(Path::new("x")));
+
+    let mut args = ["-p", "http"].iter().map(|x| x.to_string());
+    let err = parser.parse(&mut args).unwrap_err();
+
+    assert_eq!(err.to_string(),
+               "Invalid value 'http' for -p: invalid digit found in string");
+
+    let mut args = ["-D", "A", "-D", "A=1"].iter().map(|x| x.to_string());
+    let err = parser.parse(&mut args).unwrap_err();
+
+    assert_eq!(err.to_string(),
+               "Invalid value 'A=1' for -D: duplicate key A");
+
+    parser.flag("jobs", FlagKind::Typed(ValueType::of::<u32>()), &["-j"])
+          .initial_value("many");
+    let err = parser.parse(&mut [].into_iter()).unwrap_err();
+
+    assert_eq!(err.to_string(),
+               "Invalid value 'many' for -j: invalid initial value: invalid \
+                digit found in string");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn parser_parse_os()
+  {
+    use std::os::unix::ffi::OsStringExt;
+
+    let not_unicode = |bytes: &[u8]| OsString::from_vec(bytes.to_vec());
+
+    let mut parser = parser();
+    parser.flag("output", FlagKind::Path, &["-o"]);
+
+    let argv = [OsString::from("-n"),
+                OsString::from("x"),
+                OsString::from("-o"),
+                not_unicode(b"out\xff"),
+                not_unicode(b"file\xfe")];
+    let parsed = parser.parse_os(&mut argv.into_iter()).unwrap();
+
+    assert_eq!(parsed.get_str("name"), Some("x"));
+    assert_eq!(parsed.get_path("output"),
+               Some(Path::new(&not_unicode(b"out\xff"))));
+    assert_eq!(parsed.arguments(), [not_unicode(b"file\xfe")]);
+
+    let argv = [OsString::from("-n"), not_unicode(b"x\xff")];
+    let err = parser.parse_os(&mut argv.into_iter()).unwrap_err();
+
+    assert_eq!(err.error_type,
+               FlagErrorType::InvalidUnicode { value: not_unicode(b"x\xff") });
+  }
+
+  #[test]
+  fn parser_until_subcommand()
+  {
+    let parser = parser();
+
+    let argv = ["-n", "x", "--no-cache", "add", "-v"];
+    let mut args = argv.iter().map(|x| x.to_string());
+    let (parsed, subcommand) = parser.parse_until_subcommand(&mut args)
+                                     .unwrap();
+
+    assert_eq!(subcommand, "add");
+    assert_eq!(parsed.get_str("name"), Some("x"));
+    assert!(!parsed.get_bool("verbose"));
+    assert!(parsed.is_present("cache"));
+    assert!(parsed.arguments().is_empty());
+    assert_eq!(args.collect::<Vec<_>>(), ["-v"]);
+
+    let mut args = ["add"].iter().map(|x| x.to_string());
+    let err = parser.parse_until_subcommand(&mut args).unwrap_err();
+
+    assert_eq!(err.to_string(), "Missing required flag --name");
+  }
+
+  #[test]
+  fn parser_flag_specs()
+  {
+    let mut parser = parser();
+    parser.flag("jobs", FlagKind::Typed(ValueType::of::<u32>()), &["-j"])
+          .initial_value("4")
+          .value_name("N")
+          .description("Number of jobs.");
+
+    let help = parser.with_flag_specs(|flags| {
+                       Help::new("tool").colored(false).render(flags)
+                     });
+
+    assert_eq!(help,
+               "Usage: tool [OPTIONS]\n\
+                \n\
+                Options:\n      \
+                    --[no-]cache\n      \
+                    --color[=<VALUE>]\n  \
+                -n, --name <VALUE>     [required]\n  \
+                -v\n  \
+                -j <N>                 Number of jobs. [default: 4]\n");
+  }
+
+  #[test]
+  #[should_panic(expected = "Flag 'name' is not a boolean.")]
+  fn parser_wrong_kind()
+  {
+    let mut args = ["-n", "x"].iter().map(|x| x.to_string());
+    parser().parse(&mut args).unwrap().get_bool("name");
+  }
+}
@@ -0,0 +1,395 @@
+//! Positional arguments with names and arity.
+
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+use super::{ArgPosition, FlagError, FlagErrorType, FlagValue, FlagValues};
+
+#[derive(Debug)]
+enum PositionalValue<'a>
+{
+  One(&'a mut dyn FlagValue),
+  Many(&'a mut dyn FlagValues),
+  // Paths are kept as they were passed, even if they are not valid unicode.
+  Path(&'a mut PathBuf),
+  Paths(&'a mut Vec<PathBuf>),
+}
+
+/// A named positional argument that is parsed into a variable with
+/// [`parse_positionals`](fn@parse_positionals). Value can be of any type that
+/// implements [`FromStr`](trait@std::str::FromStr), like flags of
+/// [`TypedFlag`](type@super::FlagType::TypedFlag) type. Paths that are
+/// not valid unicode can be taken with [`path`](fn@Positional::path).
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::Positional;
+///
+/// let mut source = String::new();
+/// let mut destination = String::new();
+/// let mut count: u32 = 1;
+///
+/// let positionals = vec![
+///     Positional::new("SOURCE", &mut source),
+///     Positional::new("DEST", &mut destination),
+///     Positional::new("COUNT", &mut count).required(false),
+/// ];
+/// ```
+#[derive(Debug)]
+pub struct Positional<'a>
+{
+  pub(super) name: &'a str,
+  value: PositionalValue<'a>,
+  pub(super) required: bool,
+  pub(super) description: &'a str,
+}
+
+impl<'a> Positional<'a>
+{
+  /// A single argument, which is required by default.
+  pub fn new(name: &'a str, value: &'a mut dyn FlagValue) -> Self
+  {
+    Self { name,
+           value: PositionalValue::One(value),
+           required: true,
+           description: "" }
+  }
+
+  /// Takes every argument that is left, like `FILE...`. Not required by
+  /// default, so there can be none of them. There should be at most one of
+  /// these.
+  pub fn many(name: &'a str, values: &'a mut dyn FlagValues) -> Self
+  {
+    Self { name,
+           value: PositionalValue::Many(values),
+           required: false,
+           description: "" }
+  }
+
+  /// A single path, which is kept as it was passed, even if it is not valid
+  /// unicode. Required by default.
+  pub fn path(name: &'a str, value: &'a mut PathBuf) -> Self
+  {
+    Self { name,
+           value: PositionalValue::Path(value),
+           required: true,
+           description: "" }
+  }
+
+  /// Works the same as [`many`](fn@Positional::many), but keeps paths as
+  /// they were passed, like [`path`](fn@Positional::path).
+  pub fn paths(name: &'a str, values: &'a mut Vec<PathBuf>) -> Self
+  {
+    Self { name,
+           value: PositionalValue::Paths(values),
+           required: false,
+           description: "" }
+  }
+
+  /// Whether the argument has to be present. Required
+  /// [`many`](fn@Positional::many) arguments need at least one value.
+  pub fn required(mut self, required: bool) -> Self
+  {
+    self.required = required;
+    self
+  }
+
+  /// Text that describes this argument in help.
+  pub fn description(mut self, description: &'a str) -> Self
+  {
+    self.description = description;
+    self
+  }
+
+  pub(super) fn is_many(&self) -> bool
+  {
+    matches!(self.value,
+             PositionalValue::Many(_) | PositionalValue::Paths(_))
+  }
+
+  /// Returns how the argument is shown in usage, like `<FILE>` for required
+  /// arguments, `[FILE]` for optional ones, and `[FILE]...` for many.
+  pub fn synopsis(&self) -> String
+  {
+    let synopsis = if self.required {
+      format!("<{}>", self.name)
+    } else {
+      format!("[{}]", self.name)
+    };
+    if self.is_many() {
+      synopsis + "..."
+    } else {
+      synopsis
+    }
+  }
+}
+
+/// Returns usage of all arguments, like `<SOURCE> <DEST> [COUNT]`.
+pub fn positionals_synopsis(positionals: &[Positional]) -> String
+{
+  positionals.iter()
+             .map(Positional::synopsis)
+             .collect::<Vec<_>>()
+             .join(" ")
+}
+
+/// Assigns arguments, like the ones returned by
+/// [`parse_flags`](fn@super::parse_flags) or
+/// [`parse_flags_os`](fn@super::parse_flags_os), to positionals in order.
+/// Optional arguments get a value only when there are enough arguments for
+/// every required one after them, and [`many`](fn@Positional::many) takes
+/// the rest.
+///
+/// # Returns
+/// ## Err
+/// - Required argument is missing;
+/// - There are more arguments than positionals can take;
+/// - Argument could not be parsed, or is not valid unicode and is not taken
+///   by a [`path`](fn@Positional::path).
+///
+/// Errors caused by an argument have the position of that argument in
+/// `arguments`, so they can be shown with
+/// [`FlagError::render`](fn@FlagError::render) and `arguments`.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags::{Positional, parse_positionals};
+///
+/// let mut name = String::new();
+/// let mut count: u32 = 1;
+/// let mut files: Vec<String> = vec![];
+///
+/// let mut positionals = vec![
+///     Positional::new("NAME", &mut name),
+///     Positional::new("COUNT", &mut count).required(false),
+///     Positional::many("FILE", &mut files).required(true),
+/// ];
+///
+/// let args = ["hello", "a.txt"].map(String::from);
+/// parse_positionals(&args, &mut positionals).unwrap();
+///
+/// drop(positionals);
+///
+/// assert_eq!(name, "hello");
+/// assert_eq!(count, 1);
+/// assert_eq!(files, vec!["a.txt"]);
+/// ```
+pub fn parse_positionals<S>(arguments: &[S],
+                            positionals: &mut [Positional])
+                            -> Result<(), FlagError>
+  where S: AsRef<OsStr>
+{
+  debug_assert!(positionals.iter().filter(|p| p.is_many()).count() <= 1,
+                "There should be at most one positional that takes many \
+                 arguments.");
+
+  let mut arguments = arguments.iter().map(AsRef::as_ref).enumerate();
+  let mut left = arguments.len();
+  let mut missing = vec![];
+
+  for i in 0..positionals.len() {
+    // Arguments that have to be left for required positionals after this one.
+    let reserved =
+      positionals[i + 1..].iter().filter(|p| p.required).count();
+    let positional = &mut positionals[i];
+
+    let take = if positional.is_many() {
+      left.saturating_sub(reserved)
+    } else if positional.required || left > reserved {
+      1
+    } else {
+      0
+    };
+    let take = take.min(left);
+
+    if positional.required && take == 0 {
+      missing.push(positional.name.to_string());
+      continue;
+    }
+    left -= take;
+
+    for (index, argument) in arguments.by_ref().take(take) {
+      let position = ArgPosition { index, span: 0..argument.len() };
+      let name = positional.name.to_string();
+
+      match &mut positional.value {
+        PositionalValue::Path(value) => {
+          **value = PathBuf::from(argument);
+          continue;
+        }
+        PositionalValue::Paths(values) => {
+          values.push(PathBuf::from(argument));
+          continue;
+        }
+        _ => (),
+      }
+
+      let Some(argument) = argument.to_str() else {
+        let value = argument.to_os_string();
+        let error_type = FlagErrorType::InvalidUnicode { value };
+        return Err(FlagError::new(error_type, name).at(position));
+      };
+
+      let result = match &mut positional.value {
+        PositionalValue::One(value) => value.set_from_str(argument),
+        PositionalValue::Many(values) => values.push_from_str(argument),
+        PositionalValue::Path(_) | PositionalValue::Paths(_) => {
+          unreachable!()
+        }
+      };
+      if let Err(reason) = result {
+        let value = argument.to_string();
+        let error_type = FlagErrorType::InvalidValue { value, reason };
+        return Err(FlagError::new(error_type, name).at(position));
+      }
+    }
+  }
+
+  if let Some(first) = missing.first() {
+    let flag = first.clone();
+    let error_type = FlagErrorType::MissingArguments { names: missing };
+    return Err(FlagError::new(error_type, flag));
+  }
+
+  if let Some((index, extra)) = arguments.next() {
+    let error_type = FlagErrorType::UnexpectedArgument;
+    let position = ArgPosition { index, span: 0..extra.len() };
+    let extra = extra.to_string_lossy().to_string();
+    return Err(FlagError::new(error_type, extra).at(position));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+
+  fn parse(argv: &[&str])
+           -> Result<(String, String, Vec<u32>, String), FlagError>
+  {
+    let mut source = String::new();
+    let mut middle = String::new();
+    let mut numbers: Vec<u32> = vec![];
+    let mut destination = String::new();
+
+    let mut positionals =
+      vec![Positional::new("SOURCE", &mut source),
+           Positional::new("MIDDLE", &mut middle).required(false),
+           Positional::many("NUMBER", &mut numbers),
+           Positional::new("DEST", &mut destination),];
+
+    let result = parse_positionals(argv, &mut positionals);
+    drop(positionals);
+    result?;
+
+    Ok((source, middle, numbers, destination))
+  }
+
+  #[test]
+  fn positionals_arity()
+  {
+    let (source, middle, numbers, destination) = parse(&["a", "b"]).unwrap();
+
+    assert_eq!(source, "a");
+    assert_eq!(middle, "");
+    assert!(numbers.is_empty());
+    assert_eq!(destination, "b");
+
+    let (_, middle, numbers, destination) =
+      parse(&["a", "m", "1", "2", "b"]).unwrap();
+
+    assert_eq!(middle, "m");
+    assert_eq!(numbers, vec![1, 2]);
+    assert_eq!(destination, "b");
+
+    let err = parse(&[]).unwrap_err();
+    let names = vec!["SOURCE".to_string(), "DEST".to_string()];
+
+    assert_eq!(err.error_type, FlagErrorType::MissingArguments { names });
+    assert_eq!(err.to_string(), "Missing arguments SOURCE, DEST");
+
+    let err = parse(&["a"]).unwrap_err();
+
+    assert_eq!(err.to_string(), "Missing argument DEST");
+
+    let err = parse(&["a", "m", "x", "b"]).unwrap_err();
+
+    assert_eq!(err.flag, "NUMBER");
+    assert_eq!(err.to_string(),
+               "Invalid value 'x' for NUMBER: invalid digit found in string");
+    assert_eq!(err.position.as_deref(),
+               Some(&ArgPosition { index: 2, span: 0..1 }));
+  }
+
+  #[test]
+  fn positionals_unexpected()
+  {
+    let mut name = String::new();
+    let mut positionals = vec![Positional::new("NAME", &mut name)];
+
+    let err = parse_positionals(&["a", "b"], &mut positionals).unwrap_err();
+
+    assert_eq!(err.error_type, FlagErrorType::UnexpectedArgument);
+    assert_eq!(err.to_string(), "Unexpected argument b");
+    assert_eq!(err.render(&["a", "b"], false),
+               "Unexpected argument b\n  a b\n    ^");
+    assert_eq!(positionals_synopsis(&positionals), "<NAME>");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn positionals_os()
+  {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let mut name = String::new();
+    let mut path = String::new();
+    let mut positionals = vec![Positional::new("NAME", &mut name),
+                               Positional::new("PATH", &mut path)];
+
+    let invalid = OsString::from_vec(vec![b'a', 0xff]);
+    let arguments = [OsString::from("x"), invalid.clone()];
+    let err = parse_positionals(&arguments, &mut positionals).unwrap_err();
+
+    assert_eq!(err.error_type,
+               FlagErrorType::InvalidUnicode { value: invalid });
+    assert_eq!(err.position.as_deref(),
+               Some(&ArgPosition { index: 1, span: 0..2 }));
+
+    let arguments = [OsString::from("x"), OsString::from("y")];
+    parse_positionals(&arguments, &mut positionals).unwrap();
+    drop(positionals);
+
+    assert_eq!(name, "x");
+    assert_eq!(path, "y");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn positionals_os_paths()
+  {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let not_unicode = |bytes: &[u8]| OsString::from_vec(bytes.to_vec());
+
+    let mut output = PathBuf::new();
+    let mut inputs: Vec<PathBuf> = vec![];
+    let mut positionals = vec![Positional::path("OUTPUT", &mut output),
+                               Positional::paths("INPUT", &mut inputs)];
+
+    let arguments =
+      [not_unicode(b"out\xff"), not_unicode(b"a\xfe"), OsString::from("b")];
+    parse_positionals(&arguments, &mut positionals).unwrap();
+
+    assert_eq!(positionals_synopsis(&positionals), "<OUTPUT> [INPUT]...");
+    drop(positionals);
+
+    assert_eq!(output, PathBuf::from(not_unicode(b"out\xff")));
+    assert_eq!(inputs,
+               vec![PathBuf::from(not_unicode(b"a\xfe")), PathBuf::from("b")]);
+  }
+}
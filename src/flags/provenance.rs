@@ -0,0 +1,258 @@
+//! Where values of flags came from.
+
+use std::ffi::OsString;
+use std::fmt;
+
+use super::{display_name, into_string, parse_flags_seen, FlagEntry,
+            FlagError, FlagType, ParseOptions, Parser};
+
+/// Where the value of a flag came from, see
+/// [`parse_flags_with_sources`](fn@parse_flags_with_sources).
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub enum Source
+{
+  /// Flag was not set, so it has the value its variable had before parsing.
+  #[default]
+  Default,
+  /// Flag was present in arguments. When it was present several times, it's
+  /// the last one.
+  Argument
+  {
+    /// Index of the argument in the iterator that was parsed, see
+    /// [`ArgPosition::index`](struct@super::ArgPosition).
+    index: usize,
+  },
+  /// Flag was set from its environment variable, see
+  /// [`FlagSpec::env`](fn@super::FlagSpec::env).
+  Env
+  {
+    /// Name of the variable.
+    name: String,
+  },
+  /// Flag was set from a configuration file, see
+  /// [`Config`](struct@super::Config).
+  Config
+  {
+    /// Path to the file.
+    path: String,
+    /// Line with the value, starting from 1.
+    line: usize,
+  },
+}
+
+impl fmt::Display for Source
+{
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+  {
+    match self {
+      Source::Default => write!(f, "default"),
+      Source::Argument { index } => write!(f, "argument {}", index),
+      Source::Env { name } => write!(f, "environment variable {}", name),
+      Source::Config { path, line } => write!(f, "{}:{}", path, line),
+    }
+  }
+}
+
+/// Works the same way as [`parse_flags_with`](fn@super::parse_flags_with),
+/// but also returns where the value of every flag came from, in the same order
+/// as flags.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags;
+/// use toiletcli::flags::{FlagType, ParseOptions, Source,
+///                        parse_flags_with_sources};
+///
+/// let mut color;
+/// let mut verbose;
+///
+/// let mut flags = flags!(
+///     color: StringFlag, ["--color"],
+///     verbose: BoolFlag, ["-v"]
+/// );
+///
+/// let mut args = ["file", "--color", "always"].iter().map(|x| x.to_string());
+///
+/// let (_, sources) =
+///   parse_flags_with_sources(&mut args, &mut flags, &ParseOptions::new())
+///     .unwrap();
+///
+/// assert_eq!(sources, vec![Source::Argument { index: 1 }, Source::Default]);
+/// ```
+pub fn parse_flags_with_sources<'a, Args, F>(
+  args: &mut Args,
+  flags: &mut [F],
+  options: &ParseOptions)
+  -> Result<(Vec<String>, Vec<Source>), FlagError>
+  where Args: Iterator<Item = String>,
+        F: FlagEntry<'a>
+{
+  let mut args = args.by_ref().map(OsString::from);
+  let parser = Parser::describe(flags);
+  let mut seen = vec![None; flags.len()];
+
+  let arguments =
+    parse_flags_seen(&mut args, &parser, flags, &mut seen, options)?;
+  let arguments = arguments.into_iter().map(into_string).collect();
+  let sources = seen.into_iter()
+                    .map(|seen| seen.map(|seen| seen.source))
+                    .map(Option::unwrap_or_default)
+                    .collect();
+
+  Ok((arguments, sources))
+}
+
+/// Returns a report with the value of every flag and where it came from, one
+/// flag per line, for something like a `--debug-config` flag. Values of
+/// [`TypedFlag`](type@FlagType::TypedFlag) are shown with their
+/// [`Debug`](trait@fmt::Debug) representation.
+///
+/// # Example
+/// ```rust
+/// use toiletcli::flags;
+/// use toiletcli::flags::{FlagType, ParseOptions, effective_config,
+///                        parse_flags_with_sources};
+///
+/// let mut color;
+/// let mut verbose;
+///
+/// let mut flags = flags!(
+///     color: StringFlag, ["--color"],
+///     verbose: BoolFlag, ["-v"]
+/// );
+///
+/// let mut args = ["--color", "always"].iter().map(|x| x.to_string());
+///
+/// let (_, sources) =
+///   parse_flags_with_sources(&mut args, &mut flags, &ParseOptions::new())
+///     .unwrap();
+///
+/// assert_eq!(effective_config(&flags, &sources),
+///            "--color = always  (argument 0)\n\
+///             -v      = false   (default)\n");
+/// ```
+pub fn effective_config<'a, F>(flags: &[F], sources: &[Source]) -> String
+  where F: FlagEntry<'a>
+{
+  let rows = flags.iter()
+                  .zip(sources)
+                  .map(|(flag, source)| {
+                    let name = display_name(flag.aliases());
+                    (name, value(flag.flag_type()), source)
+                  })
+                  .collect::<Vec<_>>();
+
+  // Widths are in characters, so values that are not ASCII are aligned too.
+  let name_width = rows.iter()
+                       .map(|(name, ..)| name.chars().count())
+                       .max()
+                       .unwrap_or_default();
+  let value_width = rows.iter()
+                        .map(|(_, value, _)| value.chars().count())
+                        .max()
+                        .unwrap_or_default();
+
+  let mut report = String::new();
+  for (name, value, source) in &rows {
+    report += name;
+    report += &" ".repeat(name_width - name.chars().count());
+    report += " = ";
+    report += value;
+    report += &" ".repeat(value_width - value.chars().count());
+    report += &format!("  ({})\n", source);
+  }
+  report
+}
+
+// Returns the current value of a flag as text.
+fn value(flag_type: &FlagType) -> String
+{
+  match flag_type {
+    FlagType::BoolFlag(value) | FlagType::NegatableFlag(value) => {
+      value.to_string()
+    }
+    FlagType::StringFlag(value) | FlagType::OptionalFlag(value, _) => {
+      value.to_string()
+    }
+    FlagType::PathFlag(path) => path.display().to_string(),
+    FlagType::ManyFlag(values) => values.join(", "),
+    FlagType::RepeatFlag(count) => count.to_string(),
+    FlagType::TypedFlag(value) => format!("{:?}", value),
+    FlagType::TypedManyFlag(values) => format!("{:?}", values),
+    FlagType::ListFlag(values, _) => format!("{:?}", values),
+    FlagType::MapFlag(map, _) => {
+      // Keys are sorted, so the report is the same every time.
+      let mut pairs = map.iter()
+                         .map(|(key, value)| format!("{}={}", key, value))
+                         .collect::<Vec<_>>();
+      pairs.sort();
+      pairs.join(", ")
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+  use crate::flags::*;
+
+  #[test]
+  fn flag_sources()
+  {
+    std::env::set_var("TOILETCLI_TEST_SOURCES_NAME", "env");
+
+    let mut name = String::new();
+    let mut color = String::new();
+    let mut jobs: u32 = 1;
+    let mut verbose = false;
+
+    let mut flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut name), vec!["--name"])
+             .env("TOILETCLI_TEST_SOURCES_NAME"),
+           FlagSpec::new(FlagType::StringFlag(&mut color), vec!["--color"]),
+           FlagSpec::new(FlagType::TypedFlag(&mut jobs), vec!["-j"]),
+           FlagSpec::new(FlagType::BoolFlag(&mut verbose), vec!["-v"]),];
+
+    let config = Config::parse("tool.conf", "\ncolor = never").unwrap();
+    let mut options = ParseOptions::new();
+    options.config(&config, "");
+
+    let mut args = ["-v", "a", "-v"].iter().map(|x| x.to_string());
+    let (_, sources) =
+      parse_flags_with_sources(&mut args, &mut flags, &options).unwrap();
+
+    let name_source =
+      Source::Env { name: "TOILETCLI_TEST_SOURCES_NAME".to_string() };
+    let color_source = Source::Config { path: "tool.conf".to_string(),
+                                        line: 2 };
+
+    assert_eq!(sources,
+               vec![name_source,
+                    color_source,
+                    Source::Default,
+                    Source::Argument { index: 2 }]);
+    assert_eq!(effective_config(&flags, &sources),
+               "--name  = env    (environment variable \
+                TOILETCLI_TEST_SOURCES_NAME)\n\
+                --color = never  (tool.conf:2)\n\
+                -j      = 1      (default)\n\
+                -v      = true   (argument 2)\n");
+  }
+
+  #[test]
+  fn effective_config_non_ascii()
+  {
+    let mut name = "Jürgen Groß".to_string();
+    let mut city = "Berlin".to_string();
+
+    let flags =
+      vec![FlagSpec::new(FlagType::StringFlag(&mut name), vec!["--name"]),
+           FlagSpec::new(FlagType::StringFlag(&mut city), vec!["--city"])];
+    let sources = vec![Source::Default, Source::Argument { index: 1 }];
+
+    assert_eq!(effective_config(&flags, &sources),
+               "--name = Jürgen Groß  (default)\n\
+                --city = Berlin       (argument 1)\n");
+  }
+}
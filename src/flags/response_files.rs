@@ -0,0 +1,191 @@
+//! Response file (`@file`) expansion.
+
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::PathBuf;
+
+use super::{os_str_from, ArgPosition, FlagError, FlagErrorType};
+
+// How many response files can include each other.
+const MAX_DEPTH: usize = 16;
+
+struct Frame
+{
+  path: PathBuf,
+  args: std::vec::IntoIter<OsString>,
+}
+
+// Iterator over arguments that replaces `@file` arguments with contents of
+// that file. `@@` is replaced with a literal `@`.
+pub(super) struct ResponseFiles<'i, Args>
+{
+  args: &'i mut Args,
+  enabled: bool,
+  frames: Vec<Frame>,
+  errors: Vec<FlagError>,
+  consumed: usize,
+}
+
+impl<'i, Args> ResponseFiles<'i, Args> where Args: Iterator<Item = OsString>
+{
+  pub(super) fn new(args: &'i mut Args, enabled: bool) -> Self
+  {
+    Self { args, enabled, frames: vec![], errors: vec![], consumed: 0 }
+  }
+
+  // How many arguments were returned, after expansion.
+  pub(super) fn consumed(&self) -> usize
+  {
+    self.consumed
+  }
+
+  // Stop expanding response files, for example after `--`.
+  pub(super) fn stop(&mut self)
+  {
+    self.enabled = false;
+  }
+
+  // Returns errors of response files that could not be read since the last
+  // call. Arguments after such files are still returned.
+  pub(super) fn take_errors(&mut self) -> Vec<FlagError>
+  {
+    std::mem::take(&mut self.errors)
+  }
+
+  fn next_raw(&mut self) -> Option<OsString>
+  {
+    while let Some(frame) = self.frames.last_mut() {
+      if let Some(arg) = frame.args.next() {
+        return Some(arg);
+      }
+      self.frames.pop();
+    }
+    self.args.next()
+  }
+
+  fn include(&mut self, arg: &OsStr) -> Result<(), FlagError>
+  {
+    // The file is in place of the next argument.
+    let position = ArgPosition { index: self.consumed, span: 0..arg.len() };
+    let error = |reason: String| {
+      let flag = arg.to_string_lossy().to_string();
+      FlagError::new(FlagErrorType::ResponseFile { reason }, flag)
+        .at(position.clone())
+    };
+
+    let path = PathBuf::from(os_str_from(arg, 1));
+    let contents =
+      fs::read_to_string(&path).map_err(|err| error(err.to_string()))?;
+    let path = path.canonicalize().unwrap_or(path);
+
+    if self.frames.iter().any(|frame| frame.path == path) {
+      return Err(error("file includes itself".to_string()));
+    }
+    if self.frames.len() >= MAX_DEPTH {
+      return Err(error(format!("more than {} nested files", MAX_DEPTH)));
+    }
+
+    let args = split(&contents).into_iter().map(OsString::from);
+    let args = args.collect::<Vec<_>>().into_iter();
+    self.frames.push(Frame { path, args });
+
+    Ok(())
+  }
+}
+
+impl<Args> Iterator for ResponseFiles<'_, Args>
+  where Args: Iterator<Item = OsString>
+{
+  type Item = OsString;
+
+  fn next(&mut self) -> Option<OsString>
+  {
+    loop {
+      let arg = self.next_raw()?;
+
+      let bytes = arg.as_encoded_bytes();
+      if !self.enabled || !bytes.starts_with(b"@") || arg == "@" {
+        self.consumed += 1;
+        return Some(arg);
+      }
+      if bytes.starts_with(b"@@") {
+        self.consumed += 1;
+        return Some(os_str_from(&arg, 1).to_os_string());
+      }
+      // A file that could not be read still takes the place of an argument,
+      // so positions of the following arguments are right.
+      if let Err(error) = self.include(&arg) {
+        self.errors.push(error);
+        self.consumed += 1;
+      }
+    }
+  }
+}
+
+// Splits contents of a response file by whitespace. Single and double quotes
+// group words. Outside of single quotes, backslash escapes whitespace, quotes
+// and backslash. Before anything else it's kept, so Windows paths work.
+fn split(contents: &str) -> Vec<String>
+{
+  let mut args = vec![];
+  let mut arg = String::new();
+  let mut has_arg = false;
+  let mut quote: Option<char> = None;
+  let mut chars = contents.chars().peekable();
+
+  while let Some(ch) = chars.next() {
+    match (quote, ch) {
+      (Some('\''), '\'') => quote = None,
+      (Some('\''), _) => arg.push(ch),
+      (_, '\\') => {
+        match chars.peek() {
+          Some(&next) if next.is_whitespace() || "\\'\"".contains(next) => {
+            arg.push(next);
+            chars.next();
+          }
+          _ => arg.push(ch),
+        }
+        has_arg = true;
+      }
+      (Some('"'), '"') => quote = None,
+      (Some(_), _) => arg.push(ch),
+      (None, '\'' | '"') => {
+        quote = Some(ch);
+        has_arg = true;
+      }
+      (None, _) if ch.is_whitespace() => {
+        if has_arg || !arg.is_empty() {
+          args.push(std::mem::take(&mut arg));
+        }
+        has_arg = false;
+      }
+      (None, _) => arg.push(ch),
+    }
+  }
+
+  if has_arg || !arg.is_empty() {
+    args.push(arg);
+  }
+
+  args
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::*;
+
+  #[test]
+  fn response_file_split()
+  {
+    let contents = "-v  --name 'hello world'\n\"a \\\"b\\\"\" c\\ d '' \\'";
+
+    assert_eq!(split(contents),
+               vec!["-v", "--name", "hello world", "a \"b\"", "c d", "", "'"]);
+
+    let contents = "C:\\dir\\file \"C:\\Program Files\\\\\" a\\\\b \\";
+
+    assert_eq!(split(contents),
+               vec!["C:\\dir\\file", "C:\\Program Files\\", "a\\b", "\\"]);
+  }
+}
@@ -12,3 +12,7 @@ pub mod common;
 pub mod escapes;
 #[cfg(feature = "flags")]
 pub mod flags;
+
+// Derived code refers to the crate by its name.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as toiletcli;